@@ -0,0 +1,63 @@
+#![allow(clippy::needless_return)]
+
+use doodlang::eq_solver::matrix::Matrix;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const SIZES: [usize; 4] = [64, 128, 256, 512];
+const SAMPLES: usize = 3;
+
+/// The product as the `Mul` impls used to compute it, walking down the columns of `rhs`.
+fn naive_multiply(lhs: &Matrix, rhs: &Matrix) -> Matrix {
+    let mut matrix = Matrix::zeros(rhs.columns, lhs.rows);
+    for i in 0..rhs.columns {
+        for j in 0..lhs.rows {
+            let mut sum = 0f64;
+            for k in 0..lhs.columns {
+                sum += lhs[(k, j)] * rhs[(i, k)];
+            }
+            matrix[(i, j)] = sum;
+        }
+    }
+    return matrix;
+}
+
+fn square(size: usize, seed: usize) -> Matrix {
+    Matrix {
+        values: (0..size * size).map(|i| ((i * seed) % 101) as f64 / 101f64).collect(),
+        rows: size,
+        columns: size,
+    }
+}
+
+/// Returns the fastest of several timed runs to keep scheduler noise out of the comparison.
+fn time(mut multiply: impl FnMut() -> Matrix) -> Duration {
+    let mut best = Duration::MAX;
+    for _ in 0..SAMPLES {
+        let start = Instant::now();
+        black_box(multiply());
+        best = best.min(start.elapsed());
+    }
+    return best;
+}
+
+fn main() {
+    for size in SIZES {
+        let a = square(size, 7);
+        let b = square(size, 13);
+        let naive = naive_multiply(&a, &b);
+        let blocked = &a * &b;
+        let error = naive.values.iter().zip(&blocked.values).map(|(a, b)| (a - b).abs()).fold(0f64, f64::max);
+        assert!(error <= 1e-9 * size as f64, "products differ by {}", error);
+        let naive_time = time(|| naive_multiply(black_box(&a), black_box(&b)));
+        let blocked_time = time(|| black_box(&a) * black_box(&b));
+        println!(
+            "{:>4}x{:<4} naive: {:>10.3} ms  blocked: {:>10.3} ms ({:.2}x)",
+            size,
+            size,
+            naive_time.as_secs_f64() * 1e3,
+            blocked_time.as_secs_f64() * 1e3,
+            naive_time.as_secs_f64() / blocked_time.as_secs_f64(),
+        );
+    }
+}
@@ -0,0 +1,57 @@
+#![allow(clippy::needless_return)]
+
+use doodlang::doodlang::parser::{smart_generate_tree, FunctionManager};
+use doodlang::eq_solver::compile::Program;
+use doodlang::eq_solver::function::Function;
+use std::fs;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const EVALUATIONS: usize = 1_000_000;
+const SAMPLES: usize = 5;
+
+/// Returns the fastest of several timed runs to keep scheduler noise out of the comparison.
+fn time_evaluations(mut output: impl FnMut(&[f64]) -> f64, variables: &mut [f64]) -> Duration {
+    let mut best = Duration::MAX;
+    for _ in 0..SAMPLES {
+        let start = Instant::now();
+        for i in 0..EVALUATIONS {
+            variables[0] = 1f64 + (i % 1000) as f64 * 1e-3;
+            black_box(output(black_box(variables)));
+        }
+        best = best.min(start.elapsed());
+    }
+    return best;
+}
+
+fn nanos(time: Duration) -> f64 {
+    return time.as_nanos() as f64 / EVALUATIONS as f64;
+}
+
+fn main() {
+    let code = fs::read_to_string("programs/test.dood").expect("Something went wrong reading the file");
+    let variables: Vec<(&str, f64)> = vec![("x", 1f64), ("y", 1f64), ("a", 1f64)];
+    for line in code.lines() {
+        let formula = match line.find("<-") {
+            Some(index) => line[index + 2..].trim_end_matches(';').replace(" ", ""),
+            None => continue,
+        };
+        let mut function_manager = FunctionManager::new(&variables);
+        let tree = smart_generate_tree(&formula);
+        let function = function_manager.generate_func(&formula, &tree).unwrap();
+        let program = Program::compile(function.as_ref());
+        let mut values = function_manager.variables.clone();
+        let tree_time = time_evaluations(|variables| function.output(variables), &mut values);
+        let vm_time = time_evaluations(|variables| program.output(variables), &mut values);
+        let cached_time = time_evaluations(program.evaluator(), &mut values);
+        println!("{} ({} operations)", formula, program.operation_count());
+        println!(
+            "    tree: {:>8.2} ns/eval  vm: {:>8.2} ns/eval ({:.2}x)  cached vm: {:>8.2} ns/eval ({:.2}x)",
+            nanos(tree_time),
+            nanos(vm_time),
+            tree_time.as_secs_f64() / vm_time.as_secs_f64(),
+            nanos(cached_time),
+            tree_time.as_secs_f64() / cached_time.as_secs_f64(),
+        );
+    }
+}
@@ -0,0 +1,16 @@
+//! Language server for doodlang programs, speaking JSON-RPC over standard input and output.
+
+use doodlang::doodlang::lsp::serve;
+use std::io;
+use std::process;
+
+fn main() {
+    let code = match serve(io::stdin().lock(), io::stdout().lock()) {
+        Ok(code) => code,
+        Err(err) => {
+            eprintln!("doodlang-lsp: {}", err);
+            1
+        }
+    };
+    process::exit(code);
+}
@@ -0,0 +1,210 @@
+use crate::doodlang::interpreter::split_assert;
+
+const INDENT: &str = "    ";
+
+/// Re-emits a program in canonical style: one statement per line, scopes indented by four spaces
+/// and spaces around binary operators. Comments and single blank lines are kept.
+///
+/// Whitespace means nothing to the interpreter, so the formatted program runs exactly as before.
+pub fn format(code: &str) -> String {
+    let mut formatter = Formatter {
+        lines: Vec::new(),
+        depth: 0,
+        blank: false,
+    };
+    let mut statement = String::new();
+    for line in code.lines() {
+        let (code, comment) = match line.find("//") {
+            Some(index) => (&line[..index], Option::Some(line[index..].trim_end())),
+            None => (line, Option::None),
+        };
+        if code.trim().is_empty() && comment.is_none() {
+            formatter.blank = true;
+            continue;
+        }
+        let emitted = formatter.lines.len();
+        for cur in code.chars() {
+            match cur {
+                '{' | '}' => {
+                    if !statement.trim().is_empty() {
+                        formatter.emit(format_statement(&statement));
+                    }
+                    statement.clear();
+                    if cur == '{' {
+                        formatter.emit("{".to_string());
+                        formatter.depth += 1;
+                    } else {
+                        formatter.depth = formatter.depth.saturating_sub(1);
+                        formatter.emit("}".to_string());
+                    }
+                }
+                ';' => {
+                    if !statement.trim().is_empty() {
+                        formatter.emit(format_statement(&statement) + ";");
+                    }
+                    statement.clear();
+                }
+                _ => statement.push(cur),
+            }
+        }
+        // Words on either side of a line break stay apart, as `tol` has to be one of its own.
+        if !statement.trim().is_empty() {
+            statement.push(' ');
+        }
+        if let Some(comment) = comment {
+            if formatter.lines.len() > emitted && statement.trim().is_empty() {
+                let last = formatter.lines.last_mut().unwrap();
+                *last += " ";
+                *last += comment;
+            } else {
+                formatter.emit(comment.to_string());
+            }
+        }
+    }
+    if !statement.trim().is_empty() {
+        formatter.emit(format_statement(&statement));
+    }
+    let mut out = formatter.lines.join("\n");
+    out += "\n";
+    return out;
+}
+
+struct Formatter {
+    lines: Vec<String>,
+    depth: usize,
+    /// Whether a blank line was skipped since the last emitted line.
+    blank: bool,
+}
+
+impl Formatter {
+    fn emit(&mut self, line: String) {
+        let after_open = self.lines.last().is_none_or(|last| last.trim_start().starts_with('{'));
+        if self.blank && !after_open && line != "}" {
+            self.lines.push(String::new());
+        }
+        self.blank = false;
+        self.lines.push(INDENT.repeat(self.depth) + &line);
+    }
+}
+
+/// Spaces a statement the canonical way, without its `;`.
+pub fn format_statement(statement: &str) -> String {
+    let stripped: String = statement.chars().filter(|cur| !cur.is_whitespace()).collect();
+    // Keywords run into what follows them once whitespace is gone, so assertions are split first.
+    if let Some(bound) = stripped.strip_prefix("assert_residual<") {
+        return format!("assert_residual < {}", bound);
+    }
+    if let Some((lhs, rhs, tolerance)) = split_assert(statement) {
+        let tolerance = tolerance.map_or(String::new(), |tolerance| format!(" tol {}", tolerance));
+        return format!("assert {} == {}{}", format_statement(&lhs), format_statement(&rhs), tolerance);
+    }
+    let chars: Vec<char> = stripped.chars().collect();
+    let mut out = String::new();
+    let mut previous: Option<String> = Option::None;
+    let mut i = 0;
+    while i < chars.len() {
+        let start = i;
+        if chars[i].is_ascii_digit() {
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            // An exponent like `1e-6` belongs to the number.
+            if i < chars.len() && (chars[i] == 'e' || chars[i] == 'E') {
+                let sign = i + 1 < chars.len() && (chars[i + 1] == '+' || chars[i + 1] == '-');
+                let digit = if sign { i + 2 } else { i + 1 };
+                if digit < chars.len() && chars[digit].is_ascii_digit() {
+                    i = digit;
+                }
+            }
+        }
+        while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_' || chars[i] == '.') {
+            i += 1;
+        }
+        // `<-` and `==` are the only operators of two characters.
+        if i == start && i + 1 < chars.len() && ["<-", "=="].contains(&chars[i..i + 2].iter().collect::<String>().as_str()) {
+            i += 2;
+        }
+        if i == start {
+            i += 1;
+        }
+        let token: String = chars[start..i].iter().collect();
+        match token.as_str() {
+            ":" => out += ": ",
+            "," => out += ", ",
+            "^" => out += "^",
+            _ if is_operator(token.chars().next().unwrap()) => {
+                let unary = match &previous {
+                    Some(previous) => ["(", "[", ",", ":"].contains(&previous.as_str()) || is_operator(previous.chars().next().unwrap()),
+                    None => true,
+                };
+                if unary {
+                    out += &token;
+                } else {
+                    out += " ";
+                    out += &token;
+                    out += " ";
+                }
+            }
+            _ => out += &token,
+        }
+        previous = Option::Some(token);
+    }
+    return out.trim_end().to_string();
+}
+
+fn is_operator(cur: char) -> bool {
+    return "+-*/=<>!".contains(cur);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::doodlang::format::format;
+    use crate::doodlang::interpreter::Interpreter;
+
+    #[test]
+    fn formats_canonically() {
+        let code = "// header\n{ x:0; y : 1e-3;   // start\n\n\n  solve(x)<-x^2+2*x-1=5*x+8;\n\
+                    assert   x==1.5 tol 1e-6;assert x*2==y;assert_residual<1e-9;\n\
+                    eigen(l)<-[[a,1],[1,a]]; }\n{\n// alone\nsolve(y) <- max(y,0-1) = 1}\n";
+        let expected = "// header\n{\n    x: 0;\n    y: 1e-3; // start\n\n    solve(x) <- x^2 + 2 * x - 1 = 5 * x + 8;\n\
+                        \x20   assert x == 1.5 tol 1e-6;\n    assert x * 2 == y;\n    assert_residual < 1e-9;\n\
+                        \x20   eigen(l) <- [[a, 1], [1, a]];\n}\n{\n    // alone\n    solve(y) <- max(y, 0 - 1) = 1\n}\n";
+        assert_eq!(format(code), expected);
+        assert_eq!(format(expected), expected);
+    }
+
+    #[test]
+    fn keeps_meaning() {
+        let test = include_str!("../../programs/test.dood");
+        assert_eq!(format(test), test);
+        let code = "{a:2;eigen(l)<-[[a,1],[1,a]]; // two by two\n}{ x:1;solve(x)<-x*x=3; }";
+        let formatted = format(code);
+        assert_eq!(format(&formatted), formatted);
+        let mut interpreter = Interpreter::new();
+        interpreter.set_seed(5);
+        let before = interpreter.run(code);
+        interpreter.set_seed(5);
+        assert_eq!(interpreter.run(&formatted), before);
+        assert!(before.is_ok());
+    }
+
+    #[test]
+    fn splits_adjacent_operators() {
+        assert_eq!(format("{ solve(x)<-x*-2=-1-x; }"), "{\n    solve(x) <- x * -2 = -1 - x;\n}\n");
+    }
+
+    #[test]
+    fn round_trips_assertions() {
+        let code = "{ x: 1; xtol2: 5; tol: 1; assert x == xtol2; assert x==tol; assert x == 1.5 tol\n0.5; assert x*-1==-1; }";
+        let formatted = format(code);
+        assert!(formatted.contains("assert x == xtol2;\n"), "{}", formatted);
+        assert!(formatted.contains("assert x == 1.5 tol 0.5;\n"), "{}", formatted);
+        assert_eq!(format(&formatted), formatted);
+        let mut interpreter = Interpreter::new();
+        interpreter.set_seed(5);
+        let before = interpreter.run(code).to_string();
+        interpreter.set_seed(5);
+        assert_eq!(interpreter.run(&formatted).to_string(), before);
+        assert!(before.contains("failed: assert x == xtol2 (found 1, expected 5"), "{}", before);
+    }
+}
@@ -1,40 +1,301 @@
-use std::collections::HashMap;
-use std::ops::Index;
-use crate::doodlang::parser::{FunctionManager, generate_tree, smart_generate_tree};
+use crate::doodlang::format::format_statement;
+use crate::doodlang::parser::FunctionManager;
 use crate::eq_solver::approx::{SecantSolver, QuadFind};
+use crate::eq_solver::builtin::FunctionRegistry;
+use crate::eq_solver::complex::Complex;
+use crate::eq_solver::eigen::Eigen;
+use crate::eq_solver::expr::ExprArena;
+use crate::eq_solver::matrix::Matrix;
+use rand::rngs::StdRng;
+use rand::{thread_rng, Rng, SeedableRng};
+use std::fmt;
 
-fn process(code: &str) -> Vec<String> {
-    let processed = code.replace(" ", "");
-    let mut recordings: Vec<String> = vec![];
-    let mut scopes: Vec<String> = vec![];
-    for i in 0..processed.len() {
-        let current_char = &processed[i..i + 1];
-        for scope in &mut recordings {
-            *scope += current_char;
+const SOLVE_KEY: &str = "solve";
+const OPTION_KEY: &str = "option";
+const EIGEN_KEY: &str = "eigen";
+const ASSERT_KEY: &str = "assert";
+const ASSERT_RESIDUAL_KEY: &str = "assert_residual";
+const TOLERANCE_KEY: &str = "tol";
+/// The tolerance of an `assert` without `tol`.
+pub const ASSERT_TOLERANCE: f64 = 1e-9;
+/// The seed `doodlang test` and the golden tests of `programs/` run programs with.
+pub const TEST_SEED: u64 = 5;
+/// Solutions with a larger residual count as unsolved.
+pub const SOLVED_RESIDUAL: f64 = 1e-9;
+/// Above this condition number, a residual within `SOLVED_RESIDUAL` can still leave a solution
+/// off by more than `1e-5` relative to its size.
+pub const ILL_CONDITIONED: f64 = 1e4;
+
+/// A statement and the line it starts on.
+type Statement = (usize, String);
+
+/// Removes `//` comments, which run to the end of their line.
+pub fn strip_comments(code: &str) -> String {
+    return code
+        .lines()
+        .map(|line| match line.find("//") {
+            Some(index) => &line[..index],
+            None => line,
+        })
+        .collect::<Vec<&str>>()
+        .join("\n");
+}
+
+/// Splits `code` into its scopes, keeping the line of every character. Line breaks become spaces.
+fn process(code: &str) -> Vec<Vec<(char, usize)>> {
+    let mut recordings: Vec<Vec<(char, usize)>> = vec![];
+    let mut scopes: Vec<Vec<(char, usize)>> = vec![];
+    for (line, text) in strip_comments(code).lines().enumerate() {
+        for current_char in text.chars().chain(std::iter::once(' ')) {
+            for scope in &mut recordings {
+                scope.push((current_char, line + 1));
+            }
+            if current_char == '{' {
+                recordings.push(vec![]);
+            }
+            if current_char == '}' {
+                if let Some(scope) = recordings.pop() {
+                    scopes.push(scope);
+                }
+            }
         }
-        if current_char == "{" {
-            recordings.push("".to_string());
+    }
+    return scopes;
+}
+
+/// Removes all whitespace from a statement.
+fn strip_whitespace(statement: &str) -> String {
+    return statement.chars().filter(|cur| !cur.is_whitespace()).collect();
+}
+
+fn is_identifier(cur: char) -> bool {
+    return cur.is_alphanumeric() || cur == '_' || cur == '.';
+}
+
+/// Splits an `assert lhs == rhs tol t` statement into its formulas and tolerance, without whitespace.
+///
+/// `tol` has to be a word of its own, so identifiers like `xtol2` stay part of the formula.
+pub fn split_assert(statement: &str) -> Option<(String, String, Option<String>)> {
+    let rest = statement.trim_start().strip_prefix(ASSERT_KEY)?;
+    let (lhs, rhs) = rest.split_once("==")?;
+    for (index, _) in rhs.rmatch_indices(TOLERANCE_KEY) {
+        let end = index + TOLERANCE_KEY.len();
+        let separate = !rhs[..index].ends_with(is_identifier) && !rhs[end..].starts_with(is_identifier);
+        let tolerance = strip_whitespace(&rhs[end..]);
+        if separate && tolerance.parse::<f64>().is_ok() {
+            return Option::Some((strip_whitespace(lhs), strip_whitespace(&rhs[..index]), Option::Some(tolerance)));
         }
-        if current_char == "}" {
-            scopes.push(recordings.pop().unwrap());
+    }
+    return Option::Some((strip_whitespace(lhs), strip_whitespace(rhs), Option::None));
+}
+
+/// The root found by a `solve(x) <- ...;` statement.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Solution {
+    pub variable: String,
+    pub formula: String,
+    pub value: f64,
+    /// Absolute value of the formula at the solution.
+    pub residual: f64,
+    pub converged: bool,
+    /// Condition number of the solution: how far it moves, relative to its size, per unit
+    /// change of the formula, `1 / |f'(x) * max(|x|, 1)|`.
+    ///
+    /// Solves are of one equation in one unknown, so their Jacobian is the 1x1 matrix `f'(x)`.
+    /// Its SVD condition number, `Matrix::condition_number`, is 1 for every nonzero slope and
+    /// cannot tell a repeated root from a simple one, so this scalar sensitivity is used in its
+    /// place.
+    pub condition: f64,
+}
+
+impl Solution {
+    /// `slope` is the derivative of the formula at the solution.
+    pub fn new(variable: &str, formula: &str, value: f64, residual: f64, slope: f64) -> Solution {
+        return Solution {
+            variable: variable.to_string(),
+            formula: formula.to_string(),
+            value,
+            residual,
+            converged: residual <= SOLVED_RESIDUAL,
+            condition: 1f64 / (slope * value.abs().max(1f64)).abs(),
+        };
+    }
+
+    /// Whether the solution is too sensitive to the formula to trust all of its digits, as
+    /// happens at repeated roots.
+    pub fn ill_conditioned(&self) -> bool {
+        return self.converged && self.condition > ILL_CONDITIONED;
+    }
+}
+
+/// The result of an `eigen(l) <- [[...]];` statement, `vectors[i]` belonging to `values[i]`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EigenSolution {
+    pub name: String,
+    pub matrix: String,
+    pub values: Vec<Complex>,
+    pub vectors: Vec<Vec<Complex>>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Check {
+    /// `assert lhs == rhs tol tolerance;`
+    Equal { actual: f64, expected: f64, tolerance: f64 },
+    /// `assert_residual < bound;`, checking the latest solution of the scope.
+    Residual { residual: f64, bound: f64 },
+}
+
+/// The result of an `assert` or `assert_residual` statement.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Assertion {
+    /// The line of the statement, or 0 when it was executed on its own.
+    pub line: usize,
+    /// The statement in canonical style.
+    pub statement: String,
+    pub check: Check,
+    pub passed: bool,
+}
+
+impl fmt::Display for Assertion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let verdict = if self.passed { "passed" } else { "failed" };
+        return match self.check {
+            Check::Equal { actual, expected, tolerance } => {
+                write!(f, "{}: {} (found {}, expected {} within {})", verdict, self.statement, actual, expected, tolerance)
+            }
+            Check::Residual { residual, bound } => write!(f, "{}: {} (residual {}, bound {})", verdict, self.statement, residual, bound),
+        };
+    }
+}
+
+/// What a statement produced, in program order.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Outcome {
+    Seed(u64),
+    Solution(Solution),
+    Eigen(EigenSolution),
+    Assertion(Assertion),
+}
+
+/// A statement that could not be executed. The rest of its scope still runs.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    pub scope: usize,
+    /// The line the statement starts on.
+    pub line: usize,
+    pub statement: String,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return write!(f, "error in scope {}, line {}: {}\n    {};", self.scope, self.line, self.message, self.statement);
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ScopeResult {
+    /// The variables assigned in the scope, in order.
+    pub variables: Vec<(String, f64)>,
+    pub outcomes: Vec<Outcome>,
+    /// The latest value of every variable, assigned or solved, the most recently set last.
+    pub values: Vec<(String, f64)>,
+}
+
+impl ScopeResult {
+    fn set_value(&mut self, name: &str, value: f64) {
+        self.values.retain(|(variable, _)| variable != name);
+        self.values.push((name.to_string(), value));
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RunResult {
+    /// The seed the run started with.
+    pub seed: u64,
+    pub scopes: Vec<ScopeResult>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl RunResult {
+    pub fn solutions(&self) -> impl Iterator<Item = &Solution> {
+        return self.scopes.iter().flat_map(|scope| &scope.outcomes).filter_map(|outcome| match outcome {
+            Outcome::Solution(solution) => Option::Some(solution),
+            _ => Option::None,
+        });
+    }
+
+    pub fn is_ok(&self) -> bool {
+        return self.diagnostics.is_empty();
+    }
+
+    pub fn all_converged(&self) -> bool {
+        return self.solutions().all(|solution| solution.converged);
+    }
+
+    pub fn assertions(&self) -> impl Iterator<Item = &Assertion> {
+        return self.scopes.iter().flat_map(|scope| &scope.outcomes).filter_map(|outcome| match outcome {
+            Outcome::Assertion(assertion) => Option::Some(assertion),
+            _ => Option::None,
+        });
+    }
+
+    pub fn all_passed(&self) -> bool {
+        return self.assertions().all(|assertion| assertion.passed);
+    }
+}
+
+/// One or more lines, each ending with a newline.
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Outcome::Seed(seed) => writeln!(f, "seed: {}", seed)?,
+            Outcome::Solution(solution) => {
+                writeln!(f, "formula: {}", solution.formula)?;
+                writeln!(f, "{}: {} with abs err: {}", solution.variable, solution.value, solution.residual)?;
+                if solution.ill_conditioned() {
+                    writeln!(f, "warning: {} is ill-conditioned, condition number: {}", solution.variable, solution.condition)?;
+                }
+            }
+            Outcome::Eigen(eigen) => {
+                writeln!(f, "matrix: {}", eigen.matrix)?;
+                for (i, (value, vector)) in eigen.values.iter().zip(&eigen.vectors).enumerate() {
+                    let vector: Vec<String> = vector.iter().map(|val| val.to_string()).collect();
+                    writeln!(f, "{}{}: {} with vector: [{}]", eigen.name, i, value, vector.join(", "))?;
+                }
+            }
+            Outcome::Assertion(assertion) => writeln!(f, "assertion {}", assertion)?,
         }
+        return Ok(());
     }
-    return scopes;
 }
 
-pub struct DoodlangInterpreter<'a> {
-    code: &'a str,
+/// The text the command line prints for a run, without the diagnostics.
+impl fmt::Display for RunResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "seed: {}", self.seed)?;
+        for outcome in self.scopes.iter().flat_map(|scope| &scope.outcomes) {
+            write!(f, "{}", outcome)?;
+        }
+        return Ok(());
+    }
+}
+
+pub struct Interpreter {
     solver: SecantSolver,
     quad_solver: QuadFind,
-    solve_key: &'a str,
+    seed: u64,
+    rng: StdRng,
+    functions: FunctionRegistry,
+    overrides: Vec<(String, f64)>,
 }
 
-impl DoodlangInterpreter<'_> {
-    pub fn new(code: &str) -> DoodlangInterpreter {
+impl Interpreter {
+    pub fn new() -> Interpreter {
+        let seed = thread_rng().gen::<u64>();
         //ln(x + 1)
         //ln_1p(x) = ln(x + 1)
-        DoodlangInterpreter {
-            code,
+        Interpreter {
             solver: SecantSolver::new(
                 [-5f64, 5f64],
                 0.1f64,
@@ -49,90 +310,442 @@ impl DoodlangInterpreter<'_> {
                 10000,
                 0f64
             ),
-            solve_key: "solve",
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+            functions: FunctionRegistry::new(),
+            overrides: Vec::new(),
         }
     }
-    pub fn run(&mut self) {
-        let scopes: Vec<String> = process(self.code);
-        for scope in scopes {
-            self.run_scope(Box::new(scope));
+
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    pub fn seed(&self) -> u64 {
+        return self.seed;
+    }
+
+    /// Makes every assignment to `name` use `value` instead.
+    pub fn set_variable(&mut self, name: &str, value: f64) {
+        self.overrides.retain(|(overridden, _)| overridden != name);
+        self.overrides.push((name.to_string(), value));
+    }
+
+    /// The functions programs can call, where embedders register their own.
+    pub fn functions(&mut self) -> &mut FunctionRegistry {
+        return &mut self.functions;
+    }
+
+    /// Executes one statement, without its `;`, in `scope`. Assignments are pushed to the end of
+    /// its variables; the outcome is returned and left for the caller to record.
+    pub fn execute(&mut self, statement: &str, scope: &mut ScopeResult) -> Result<Option<Outcome>, String> {
+        return self.execute_line(statement, scope);
+    }
+
+    /// Evaluates a formula with the given variables and the registered functions.
+    pub fn evaluate(&self, formula: &str, variables: &[(String, f64)]) -> Result<f64, String> {
+        let formula = strip_whitespace(formula);
+        let scope: Vec<(&str, f64)> = variables.iter().map(|(name, value)| (name.as_str(), *value)).collect();
+        let mut function_manager = FunctionManager::with_functions(&scope, self.functions.clone());
+        let mut arena = ExprArena::new();
+        let expr = function_manager.parse(&mut arena, &formula).map_err(|err| err.message)?;
+        return Ok(arena.output(expr, &function_manager.variables));
+    }
+
+    /// Runs every `{ ... }` scope of `code`.
+    pub fn run(&mut self, code: &str) -> RunResult {
+        let mut result = RunResult {
+            seed: self.seed,
+            scopes: Vec::new(),
+            diagnostics: Vec::new(),
+        };
+        for (i, scope) in process(code).into_iter().enumerate() {
+            let scope = self.run_scope(i, scope, &mut result.diagnostics);
+            result.scopes.push(scope);
         }
+        return result;
     }
 
-    fn run_scope(&mut self, scope: Box<String>) {
-        let lines = self.process_scope(scope);
-        let mut variables: Vec<(&str, f64)> = Vec::new();
-        for line in &lines {
-            self.execute_line(line, &mut variables);
+    fn run_scope(&mut self, index: usize, scope: Vec<(char, usize)>, diagnostics: &mut Vec<Diagnostic>) -> ScopeResult {
+        let mut result = ScopeResult::default();
+        let (lines, unterminated) = self.process_scope(scope);
+        for (line_number, statement) in lines {
+            let line = strip_whitespace(&statement);
+            match self.execute_line(&statement, &mut result) {
+                Ok(Some(Outcome::Assertion(assertion))) => result.outcomes.push(Outcome::Assertion(Assertion {
+                    line: line_number,
+                    ..assertion
+                })),
+                Ok(Some(outcome)) => result.outcomes.push(outcome),
+                Ok(None) => {}
+                Err(message) => diagnostics.push(Diagnostic {
+                    scope: index,
+                    line: line_number,
+                    statement: line,
+                    message,
+                }),
+            }
+        }
+        if let Some((line_number, statement)) = unterminated {
+            diagnostics.push(Diagnostic {
+                scope: index,
+                line: line_number,
+                statement: strip_whitespace(&statement),
+                message: "Missing `;`, this statement is ignored".to_string(),
+            });
         }
+        return result;
     }
 
-    fn process_scope(&mut self, scope: Box<String>) -> Vec<Box<String>> {
-        let mut lines: Vec<Box<String>> = Vec::new();
+    /// Splits a scope into its statements, each with the line it starts on, and returns the text
+    /// after the last `;` if it is more than closing braces.
+    fn process_scope(&mut self, scope: Vec<(char, usize)>) -> (Vec<Statement>, Option<Statement>) {
+        let mut lines: Vec<Statement> = Vec::new();
         let mut record = "".to_string();
-        for i in 0..scope.len() {
-            let cur = &scope[i..i + 1];
-            if cur == ";" {
-                lines.push(Box::new(record.clone()));
+        let mut start = 0;
+        for (cur, line) in scope {
+            if cur == ';' {
+                lines.push((start, record.clone()));
                 record = "".to_string();
             } else {
-                record += cur;
+                if record.trim().is_empty() {
+                    start = line;
+                }
+                record.push(cur);
             }
         }
-        return lines;
+        if record.chars().all(|cur| cur == '{' || cur == '}' || cur.is_whitespace()) {
+            return (lines, Option::None);
+        }
+        let record = record.trim_end();
+        let statement = record.strip_suffix('}').unwrap_or(record).to_string();
+        return (lines, Option::Some((start, statement)));
     }
 
-    fn execute_line<'a>(&mut self, line: &'a Box<String>, variables: &mut Vec<(&'a str, f64)>) {
-        if line.contains(":") {
+    fn execute_line(&mut self, statement: &str, scope: &mut ScopeResult) -> Result<Option<Outcome>, String> {
+        let line = &strip_whitespace(statement);
+        let variables = &mut scope.variables;
+        if line.starts_with(OPTION_KEY) && line[OPTION_KEY.len()..].starts_with("(") {
+            return self.execute_option(line);
+        } else if line.starts_with(EIGEN_KEY) && line[EIGEN_KEY.len()..].starts_with("(") {
+            return self.execute_eigen(line, variables);
+        } else if line.starts_with(ASSERT_RESIDUAL_KEY) && line[ASSERT_RESIDUAL_KEY.len()..].starts_with("<") {
+            return self.execute_assert_residual(line, scope);
+        } else if let Some((lhs, rhs, tolerance)) = split_assert(statement) {
+            return self.execute_assert(statement, &lhs, &rhs, tolerance.as_deref(), scope);
+        } else if line.contains(":") {
             let end_index = line.find(":").unwrap();
-            let mut start_index = 0;
-            for i in 0..end_index {
-                if line[i..i + 1].chars().all(char::is_alphanumeric) {
-                    start_index = i;
-                    break;
-                }
-            }
+            let start_index = line[..end_index]
+                .char_indices()
+                .find(|(_, cur)| cur.is_alphanumeric())
+                .map_or(0, |(i, _)| i);
             let var_name = &line[start_index..end_index];
-            let value = match line[end_index + 1..line.len()].parse::<f64>() {
-                Ok(x) => x,
-                Err(_) => {
-                    println!("This value cannot be parsed in line: {}", &line[end_index + 1..line.len()]);
-                    unimplemented!();
-                }
+            let value = match self.overrides.iter().find(|(name, _)| name == var_name) {
+                Some((_, value)) => *value,
+                None => match line[end_index + 1..line.len()].parse::<f64>() {
+                    Ok(x) => x,
+                    Err(_) => return Err(format!("This value cannot be parsed in line: {}", &line[end_index + 1..line.len()])),
+                },
             };
-            variables.push((var_name, value));
-        } else if line.contains(self.solve_key) {
-            let end_index = line.find(self.solve_key).unwrap() + self.solve_key.len();
-            let index = end_index + self.parse_variable(&line[end_index..line.len()]);
-            if &line[index..index + 2] != "<-" {
-                println!("You need to put an arrow: {} to signify that you have finished the solve definition", "<-");
-                unimplemented!();
+            variables.push((var_name.to_string(), value));
+            scope.set_value(var_name, value);
+            return Ok(None);
+        } else if line.contains(SOLVE_KEY) {
+            let end_index = line.find(SOLVE_KEY).unwrap() + SOLVE_KEY.len();
+            let index = end_index + parse_variable(&line[end_index..line.len()])?;
+            if !line[index..].starts_with("<-") {
+                return Err("You need to put an arrow: <- to signify that you have finished the solve definition".to_string());
             }
             let variable = &line[end_index + 1..index - 1];
-            let mut function_manager = Box::new(FunctionManager::new(variables));
+            let names: Vec<(&str, f64)> = variables.iter().map(|(name, value)| (name.as_str(), *value)).collect();
+            let mut function_manager = FunctionManager::with_functions(&names, self.functions.clone());
+            let slot = match function_manager.ids.get(variable) {
+                Some(slot) => *slot,
+                None => return Err(format!("Unknown variable: {}", variable)),
+            };
             let formula = &line[index + 2..line.len()];
-            println!("formula: {}", formula);
-            let tree = smart_generate_tree(&formula.to_string());
-            let mut function = function_manager.generate_func(&formula, &tree);
-            let solution = self.solver.solve_for_param(&mut function, &mut function_manager, variable);
-            function_manager.variables[*function_manager.ids.get(variable).unwrap()] = solution;
-            println!("{}: {} with abs err: {}", variable, solution, function.output(&function_manager).abs());
-        }
-    }
-
-    fn parse_variable(&mut self, line: &str) -> usize {
-        if &line[0..1] != "(" {
-            println!("Parentheses not found: {}", &line[0..1]);
-            unimplemented!();
-        }
-        let mut index = 1usize;
-        loop {
-            let current_char = &line[index..index + 1];
-            index += 1;
-            if current_char == ")" {
-                break;
+            let mut arena = ExprArena::new();
+            let expr = function_manager.parse(&mut arena, formula).map_err(|err| err.message)?;
+            let function = arena.to_solvable(expr);
+            let solution = self.solver.solve_for_param(&mut self.rng, function.as_ref(), &function_manager, variable);
+            function_manager.variables[slot] = solution;
+            let residual = function.output(&function_manager.variables).abs();
+            let derivative = arena.derivative(expr, slot);
+            let slope = arena.output(derivative, &function_manager.variables);
+            scope.set_value(variable, solution);
+            return Ok(Some(Outcome::Solution(Solution::new(variable, formula, solution, residual, slope))));
+        } else if !line.is_empty() {
+            return Err(format!("Unknown statement: {}", line));
+        }
+        return Ok(None);
+    }
+
+    fn execute_assert(&mut self, statement: &str, lhs: &str, rhs: &str, tolerance: Option<&str>, scope: &ScopeResult) -> Result<Option<Outcome>, String> {
+        let tolerance = match tolerance {
+            Some(tolerance) => tolerance.parse::<f64>().unwrap(),
+            None => ASSERT_TOLERANCE,
+        };
+        // Whichever of an assignment or a solution came last gives the value that is checked.
+        let actual = self.evaluate(lhs, &scope.values)?;
+        let expected = self.evaluate(rhs, &scope.values)?;
+        return Ok(Some(Outcome::Assertion(Assertion {
+            line: 0,
+            statement: format_statement(statement),
+            check: Check::Equal { actual, expected, tolerance },
+            passed: (actual - expected).abs() <= tolerance,
+        })));
+    }
+
+    fn execute_assert_residual(&mut self, line: &str, scope: &ScopeResult) -> Result<Option<Outcome>, String> {
+        let bound = &line[ASSERT_RESIDUAL_KEY.len() + 1..];
+        let bound = match bound.parse::<f64>() {
+            Ok(bound) => bound,
+            Err(_) => return Err(format!("The residual bound has to be a number: {}", bound)),
+        };
+        let residual = scope.outcomes.iter().rev().find_map(|outcome| match outcome {
+            Outcome::Solution(solution) => Option::Some(solution.residual),
+            _ => Option::None,
+        });
+        let residual = match residual {
+            Some(residual) => residual,
+            None => return Err("assert_residual needs an earlier solve in the scope".to_string()),
+        };
+        return Ok(Some(Outcome::Assertion(Assertion {
+            line: 0,
+            statement: format_statement(line),
+            check: Check::Residual { residual, bound },
+            passed: residual < bound,
+        })));
+    }
+
+    fn execute_option(&mut self, line: &str) -> Result<Option<Outcome>, String> {
+        let end_index = OPTION_KEY.len();
+        let index = end_index + parse_variable(&line[end_index..line.len()])?;
+        if !line[index..].starts_with("<-") {
+            return Err("You need to put an arrow: <- to signify that you have finished the option definition".to_string());
+        }
+        let option = &line[end_index + 1..index - 1];
+        let value = &line[index + 2..line.len()];
+        match option {
+            "seed" => {
+                let seed = match value.parse::<u64>() {
+                    Ok(x) => x,
+                    Err(_) => return Err(format!("Seed has to be a non-negative integer: {}", value)),
+                };
+                self.set_seed(seed);
+                return Ok(Some(Outcome::Seed(seed)));
+            }
+            _ => return Err(format!("Unknown option: {}", option)),
+        }
+    }
+
+    fn execute_eigen(&mut self, line: &str, variables: &[(String, f64)]) -> Result<Option<Outcome>, String> {
+        let end_index = EIGEN_KEY.len();
+        let index = end_index + parse_variable(&line[end_index..line.len()])?;
+        if !line[index..].starts_with("<-") {
+            return Err("You need to put an arrow: <- to signify that you have finished the eigen definition".to_string());
+        }
+        let name = &line[end_index + 1..index - 1];
+        let literal = &line[index + 2..line.len()];
+        let matrix = parse_matrix(literal, variables, &self.functions)?;
+        let eigen = Eigen::new(&matrix).map_err(|err| format!("No eigenvalues for {}: {}", literal, err))?;
+        return Ok(Some(Outcome::Eigen(EigenSolution {
+            name: name.to_string(),
+            matrix: literal.to_string(),
+            values: eigen.values,
+            vectors: eigen.vectors,
+        })));
+    }
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        return Interpreter::new();
+    }
+}
+
+/// Returns the length of the leading `(name)`.
+fn parse_variable(line: &str) -> Result<usize, String> {
+    if !line.starts_with("(") {
+        return Err(format!("Parentheses not found: {}", line));
+    }
+    return match line.find(")") {
+        Some(index) => Ok(index + 1),
+        None => Err(format!("Parentheses not closed: {}", line)),
+    };
+}
+
+/// Parses a square matrix literal like `[[1, x], [x, 1]]`, evaluating every entry with the scope variables.
+fn parse_matrix(literal: &str, variables: &[(String, f64)], functions: &FunctionRegistry) -> Result<Matrix, String> {
+    let rows = split_list(literal)?
+        .iter()
+        .map(|row| split_list(row))
+        .collect::<Result<Vec<Vec<&str>>, String>>()?;
+    let size = rows.len();
+    if rows.iter().any(|row| row.len() != size) {
+        return Err(format!("Matrix has to be square: {}", literal));
+    }
+    let scope: Vec<(&str, f64)> = variables.iter().map(|(name, value)| (name.as_str(), *value)).collect();
+    let mut function_manager = FunctionManager::with_functions(&scope, functions.clone());
+    let mut matrix = Matrix::zeros(size, size);
+    for (j, row) in rows.iter().enumerate() {
+        for (i, entry) in row.iter().enumerate() {
+            let mut arena = ExprArena::new();
+            let expr = function_manager.parse(&mut arena, entry).map_err(|err| err.message)?;
+            matrix[(i, j)] = arena.output(expr, &function_manager.variables);
+        }
+    }
+    return Ok(matrix);
+}
+
+/// Splits `[a, b, ...]` at the commas outside of any parentheses or brackets.
+fn split_list(list: &str) -> Result<Vec<&str>, String> {
+    if !list.starts_with("[") || !list.ends_with("]") {
+        return Err(format!("Expected a list in brackets: {}", list));
+    }
+    let inner = &list[1..list.len() - 1];
+    let mut items = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, cur) in inner.char_indices() {
+        match cur {
+            '(' | '[' => depth += 1,
+            ')' | ']' => depth -= 1,
+            ',' if depth == 0 => {
+                items.push(&inner[start..i]);
+                start = i + 1;
             }
+            _ => {}
         }
-        return index;
     }
-}
\ No newline at end of file
+    items.push(&inner[start..inner.len()]);
+    return Ok(items);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::doodlang::interpreter::{split_assert, Interpreter, Outcome};
+
+    #[test]
+    fn returns_structured_results() {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_seed(5);
+        let result = interpreter.run("{ x: 1; y: 2; solve(x) <- x^2 = y; eigen(l) <- [[y, 0], [0, 1]]; }");
+        assert!(result.is_ok());
+        assert_eq!(result.seed, 5);
+        assert_eq!(result.scopes[0].variables, vec![("x".to_string(), 1.0), ("y".to_string(), 2.0)]);
+        let solution = result.solutions().next().unwrap();
+        assert_eq!(solution.formula, "x^2=y");
+        assert!((solution.value.abs() - 2f64.sqrt()).abs() < 1e-9);
+        match &result.scopes[0].outcomes[1] {
+            Outcome::Eigen(eigen) => assert_eq!(eigen.values.len(), 2),
+            outcome => panic!("expected eigenvalues, found {:?}", outcome),
+        }
+    }
+
+    #[test]
+    fn reports_diagnostics_and_continues() {
+        let mut interpreter = Interpreter::new();
+        let result = interpreter.run("{ x: 1; // x starts at one\n solve(x) <- foo(x) = 1; solve(z) <- x = 1; solve(x) <- (x = 1; solve(x) <- x = 3; }");
+        let messages: Vec<&str> = result.diagnostics.iter().map(|diagnostic| diagnostic.message.as_str()).collect();
+        assert_eq!(messages, vec!["Unknown function: foo", "Unknown variable: z", "Unclosed parenthesis in: (x=1"]);
+        assert_eq!(result.solutions().count(), 1);
+    }
+
+    #[test]
+    fn checks_assertions() {
+        let mut interpreter = Interpreter::new();
+        let code = "{\n    x: 1;\n    solve(x) <- x * 2 = 3;\n    assert x == 1.5 tol 1e-6;\n    assert x == 2;\n    assert_residual < 1e-9;\n    assert_residual < x;\n}";
+        let result = interpreter.run(code);
+        let checked: Vec<(usize, bool)> = result.assertions().map(|assertion| (assertion.line, assertion.passed)).collect();
+        assert_eq!(checked, vec![(4, true), (5, false), (6, true)]);
+        assert!(!result.all_passed());
+        assert_eq!(result.diagnostics[0].line, 7);
+        assert_eq!(result.diagnostics[0].message, "The residual bound has to be a number: x");
+    }
+
+    #[test]
+    fn asserts_the_latest_value() {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_seed(1);
+        let result = interpreter.run("{ x: 1; solve(x) <- x * 2 = 3; assert x == 1.5; x: 7; assert x == 7; }");
+        assert!(result.all_passed());
+        assert_eq!(result.scopes[0].values, vec![("x".to_string(), 7.0)]);
+    }
+
+    #[test]
+    fn reads_tol_only_as_a_word() {
+        let mut interpreter = Interpreter::new();
+        let result = interpreter.run("{ x: 1; xtol2: 5; tol: 1; assert x == xtol2; assert x == tol; assert x == 1.5 tol\n 0.5; }");
+        let checked: Vec<bool> = result.assertions().map(|assertion| assertion.passed).collect();
+        assert_eq!(checked, vec![false, true, true]);
+        assert!(result.diagnostics.is_empty());
+        assert_eq!(split_assert("assert x==1tol 2"), Option::Some(("x".to_string(), "1tol2".to_string(), Option::None)));
+    }
+
+    #[test]
+    fn warns_about_ill_conditioned_solutions() {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_seed(5);
+        let result = interpreter.run("{ x: 1; solve(x) <- (x - 1)^3 = 0; solve(x) <- x^2 = 2; solve(x) <- x / 100000 = 1; }");
+        let warned: Vec<bool> = result.solutions().map(|solution| solution.ill_conditioned()).collect();
+        assert_eq!(warned, vec![true, false, false]);
+        assert!(result.to_string().contains("warning: x is ill-conditioned"));
+    }
+
+    #[test]
+    fn does_not_warn_about_well_conditioned_solutions() {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_seed(5);
+        let code = "{ x: 1; solve(x) <- x * 2 = 3; solve(x) <- x^2 - x = 6; solve(x) <- exp(x) = 20; solve(x) <- x - 1000000 = 0; }";
+        let result = interpreter.run(code);
+        assert!(result.solutions().all(|solution| solution.converged && solution.condition <= 1f64));
+        assert!(result.solutions().all(|solution| !solution.ill_conditioned()));
+        assert!(!result.to_string().contains("warning"), "{}", result);
+    }
+
+    #[test]
+    fn assigns_non_ascii_names() {
+        let mut interpreter = Interpreter::new();
+        let result = interpreter.run("{ é: 1; ñandú: 2; }");
+        assert!(result.is_ok());
+        assert_eq!(result.scopes[0].variables, vec![("é".to_string(), 1.0), ("ñandú".to_string(), 2.0)]);
+    }
+
+    #[test]
+    fn reports_a_missing_semicolon() {
+        let mut interpreter = Interpreter::new();
+        let result = interpreter.run("{ x: 1;\n solve(x) <- x = 2 }\n{ y: 1; { z: 2; } }");
+        assert_eq!(result.solutions().count(), 0);
+        assert_eq!(result.diagnostics.len(), 1);
+        assert_eq!((result.diagnostics[0].line, result.diagnostics[0].statement.as_str()), (2, "solve(x)<-x=2"));
+        assert_eq!(result.diagnostics[0].message, "Missing `;`, this statement is ignored");
+    }
+
+    #[test]
+    fn reproduces_runs_from_their_seed() {
+        let code = "{ x: 1; y: 3; solve(x) <- x^3 - y*x = 1; solve(y) <- sin(3*y) = 0.5; }";
+        let first = Interpreter::new().run(code);
+        let mut interpreter = Interpreter::new();
+        interpreter.set_seed(first.seed);
+        assert_eq!(interpreter.run(code), first);
+        // The seed option restarts the generator just like setting the seed.
+        let mut interpreter = Interpreter::new();
+        interpreter.set_seed(7);
+        let seeded = interpreter.run(code);
+        let option = Interpreter::new().run(&format!("{{ option(seed) <- 7; }}{}", code));
+        assert_eq!(option.scopes[1], seeded.scopes[0]);
+    }
+
+    #[test]
+    fn overrides_assignments() {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_variable("y", 9.0);
+        let result = interpreter.run("{ x: 1; y: 2; solve(x) <- x^2 = y; solve(x) <- x^2 = 0 - 1; }");
+        assert_eq!(result.scopes[0].variables[1], ("y".to_string(), 9.0));
+        let converged: Vec<bool> = result.solutions().map(|solution| solution.converged).collect();
+        assert_eq!(converged, vec![true, false]);
+        assert!(!result.all_converged());
+    }
+}
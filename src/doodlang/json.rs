@@ -0,0 +1,332 @@
+use std::fmt;
+
+/// A JSON value, enough for the reports and the language server.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    /// Keys keep their order.
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    pub fn parse(text: &str) -> Result<Json, String> {
+        let mut parser = Parser {
+            chars: text.chars().collect(),
+            index: 0,
+        };
+        let value = parser.value()?;
+        parser.whitespace();
+        if parser.index < parser.chars.len() {
+            return Err(format!("Unexpected character at {}", parser.index));
+        }
+        return Ok(value);
+    }
+
+    /// Builds an object from borrowed keys.
+    pub fn object(members: Vec<(&str, Json)>) -> Json {
+        return Json::Object(members.into_iter().map(|(key, value)| (key.to_string(), value)).collect());
+    }
+
+    pub fn string(value: &str) -> Json {
+        return Json::String(value.to_string());
+    }
+
+    /// The member `key` of an object.
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        return match self {
+            Json::Object(members) => members.iter().find(|(name, _)| name == key).map(|(_, value)| value),
+            _ => Option::None,
+        };
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        return match self {
+            Json::String(value) => Option::Some(value),
+            _ => Option::None,
+        };
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        return match self {
+            Json::Number(value) => Option::Some(*value),
+            _ => Option::None,
+        };
+    }
+
+    pub fn as_array(&self) -> Option<&Vec<Json>> {
+        return match self {
+            Json::Array(values) => Option::Some(values),
+            _ => Option::None,
+        };
+    }
+
+    /// Indented JSON with every member and element on its own line, for files people diff.
+    pub fn pretty(&self) -> String {
+        let mut out = String::new();
+        self.write_pretty(&mut out, 0);
+        out += "\n";
+        return out;
+    }
+
+    fn write_pretty(&self, out: &mut String, depth: usize) {
+        let indent = "  ".repeat(depth + 1);
+        match self {
+            Json::Array(values) if !values.is_empty() => {
+                *out += "[\n";
+                for (i, value) in values.iter().enumerate() {
+                    *out += &indent;
+                    value.write_pretty(out, depth + 1);
+                    *out += if i + 1 < values.len() { ",\n" } else { "\n" };
+                }
+                *out += &"  ".repeat(depth);
+                *out += "]";
+            }
+            Json::Object(members) if !members.is_empty() => {
+                *out += "{\n";
+                for (i, (key, value)) in members.iter().enumerate() {
+                    *out += &indent;
+                    *out += &quote(key);
+                    *out += ": ";
+                    value.write_pretty(out, depth + 1);
+                    *out += if i + 1 < members.len() { ",\n" } else { "\n" };
+                }
+                *out += &"  ".repeat(depth);
+                *out += "}";
+            }
+            value => *out += &value.to_string(),
+        }
+    }
+}
+
+/// Compact JSON. NaN and infinities have no JSON form and become `null`.
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Json::Null => write!(f, "null")?,
+            Json::Bool(value) => write!(f, "{}", value)?,
+            Json::Number(value) if value.is_finite() => write!(f, "{}", value)?,
+            Json::Number(_) => write!(f, "null")?,
+            Json::String(value) => write!(f, "{}", quote(value))?,
+            Json::Array(values) => {
+                write!(f, "[")?;
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                write!(f, "]")?;
+            }
+            Json::Object(members) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in members.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}:{}", quote(key), value)?;
+                }
+                write!(f, "}}")?;
+            }
+        }
+        return Ok(());
+    }
+}
+
+/// `value` as a JSON string literal.
+pub fn quote(value: &str) -> String {
+    let mut out = String::from("\"");
+    for cur in value.chars() {
+        match cur {
+            '"' => out += "\\\"",
+            '\\' => out += "\\\\",
+            '\n' => out += "\\n",
+            '\r' => out += "\\r",
+            '\t' => out += "\\t",
+            cur if (cur as u32) < 0x20 => out += &format!("\\u{:04x}", cur as u32),
+            cur => out.push(cur),
+        }
+    }
+    out += "\"";
+    return out;
+}
+
+struct Parser {
+    chars: Vec<char>,
+    index: usize,
+}
+
+impl Parser {
+    fn whitespace(&mut self) {
+        while self.index < self.chars.len() && self.chars[self.index].is_whitespace() {
+            self.index += 1;
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        self.whitespace();
+        if self.chars.get(self.index) != Option::Some(&expected) {
+            return Err(format!("Expected '{}' at {}", expected, self.index));
+        }
+        self.index += 1;
+        return Ok(());
+    }
+
+    fn keyword(&mut self, keyword: &str, value: Json) -> Result<Json, String> {
+        let end = self.index + keyword.len();
+        if end > self.chars.len() || self.chars[self.index..end].iter().collect::<String>() != keyword {
+            return Err(format!("Unexpected character at {}", self.index));
+        }
+        self.index = end;
+        return Ok(value);
+    }
+
+    fn value(&mut self) -> Result<Json, String> {
+        self.whitespace();
+        return match self.chars.get(self.index) {
+            Some('n') => self.keyword("null", Json::Null),
+            Some('t') => self.keyword("true", Json::Bool(true)),
+            Some('f') => self.keyword("false", Json::Bool(false)),
+            Some('"') => Ok(Json::String(self.string()?)),
+            Some('[') => {
+                self.index += 1;
+                let mut values = Vec::new();
+                self.whitespace();
+                if self.chars.get(self.index) == Option::Some(&']') {
+                    self.index += 1;
+                    return Ok(Json::Array(values));
+                }
+                loop {
+                    values.push(self.value()?);
+                    self.whitespace();
+                    match self.chars.get(self.index) {
+                        Some(',') => self.index += 1,
+                        Some(']') => {
+                            self.index += 1;
+                            return Ok(Json::Array(values));
+                        }
+                        _ => return Err(format!("Expected ',' or ']' at {}", self.index)),
+                    }
+                }
+            }
+            Some('{') => {
+                self.index += 1;
+                let mut members = Vec::new();
+                self.whitespace();
+                if self.chars.get(self.index) == Option::Some(&'}') {
+                    self.index += 1;
+                    return Ok(Json::Object(members));
+                }
+                loop {
+                    self.whitespace();
+                    let key = self.string()?;
+                    self.expect(':')?;
+                    members.push((key, self.value()?));
+                    self.whitespace();
+                    match self.chars.get(self.index) {
+                        Some(',') => self.index += 1,
+                        Some('}') => {
+                            self.index += 1;
+                            return Ok(Json::Object(members));
+                        }
+                        _ => return Err(format!("Expected ',' or '}}' at {}", self.index)),
+                    }
+                }
+            }
+            Some(_) => self.number(),
+            None => Err("Unexpected end of input".to_string()),
+        };
+    }
+
+    fn number(&mut self) -> Result<Json, String> {
+        let start = self.index;
+        while self.index < self.chars.len() && "+-0123456789.eE".contains(self.chars[self.index]) {
+            self.index += 1;
+        }
+        let text: String = self.chars[start..self.index].iter().collect();
+        return match text.parse::<f64>() {
+            Ok(value) => Ok(Json::Number(value)),
+            Err(_) => Err(format!("Unexpected character at {}", start)),
+        };
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        if self.chars.get(self.index) != Option::Some(&'"') {
+            return Err(format!("Expected a string at {}", self.index));
+        }
+        self.index += 1;
+        let mut out = String::new();
+        loop {
+            let cur = match self.chars.get(self.index) {
+                Some(cur) => *cur,
+                None => return Err("Unterminated string".to_string()),
+            };
+            self.index += 1;
+            match cur {
+                '"' => return Ok(out),
+                '\\' => {
+                    let escaped = match self.chars.get(self.index) {
+                        Some(escaped) => *escaped,
+                        None => return Err("Unterminated string".to_string()),
+                    };
+                    self.index += 1;
+                    match escaped {
+                        'n' => out.push('\n'),
+                        'r' => out.push('\r'),
+                        't' => out.push('\t'),
+                        'b' => out.push('\u{8}'),
+                        'f' => out.push('\u{c}'),
+                        'u' => {
+                            let code = self.hex()?;
+                            // A surrogate pair spells one character outside the basic plane.
+                            let code = if (0xd800..0xdc00).contains(&code) && self.chars.get(self.index) == Option::Some(&'\\') {
+                                self.index += 2;
+                                let low = self.hex()?;
+                                0x10000 + ((code - 0xd800) << 10) + (low.wrapping_sub(0xdc00) & 0x3ff)
+                            } else {
+                                code
+                            };
+                            out.push(char::from_u32(code).unwrap_or('\u{fffd}'));
+                        }
+                        escaped => out.push(escaped),
+                    }
+                }
+                cur => out.push(cur),
+            }
+        }
+    }
+
+    fn hex(&mut self) -> Result<u32, String> {
+        let end = self.index + 4;
+        if end > self.chars.len() {
+            return Err("Unterminated string".to_string());
+        }
+        let digits: String = self.chars[self.index..end].iter().collect();
+        self.index = end;
+        return u32::from_str_radix(&digits, 16).map_err(|_| format!("Invalid escape: \\u{}", digits));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::doodlang::json::Json;
+
+    #[test]
+    fn round_trips() {
+        let text = r#"{"id":1,"params":{"text":"a\n\"b\" \u00e9\ud83d\ude00","list":[true,false,null,-2.5e3,[]],"empty":{}}}"#;
+        let value = Json::parse(text).unwrap();
+        assert_eq!(value.get("id").and_then(Json::as_f64), Option::Some(1.0));
+        let params = value.get("params").unwrap();
+        assert_eq!(params.get("text").and_then(Json::as_str), Option::Some("a\n\"b\" é😀"));
+        assert_eq!(params.get("list").and_then(Json::as_array).unwrap()[3], Json::Number(-2500.0));
+        assert_eq!(Json::parse(&value.to_string()).unwrap(), value);
+        assert_eq!(Json::parse(&value.pretty()).unwrap(), value);
+        assert_eq!(Json::parse("{\"a\":[1,{}]}").unwrap().pretty(), "{\n  \"a\": [\n    1,\n    {}\n  ]\n}\n");
+        assert_eq!(Json::Array(vec![Json::Number(f64::NAN), Json::string("\t")]).to_string(), "[null,\"\\t\"]");
+        assert!(Json::parse("{\"a\":1,}").is_err());
+        assert!(Json::parse("[1 2]").is_err());
+    }
+}
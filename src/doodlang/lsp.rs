@@ -0,0 +1,604 @@
+use crate::doodlang::interpreter::{strip_comments, Interpreter, Outcome, ScopeResult, Solution};
+use crate::doodlang::json::Json;
+use crate::eq_solver::builtin::BUILTINS;
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+use std::panic::{self, AssertUnwindSafe};
+
+const KEYWORDS: [(&str, &str); 5] = [
+    ("solve", "solve(x) <- formula; finds a value of x for which the formula holds"),
+    ("eigen", "eigen(l) <- [[a, b], [c, d]]; finds the eigenvalues and eigenvectors of a square matrix"),
+    ("option", "option(seed) <- n; restarts the random initial guesses from seed n"),
+    ("assert", "assert x == value tol t; checks that x, solved or assigned, is within t of value"),
+    ("assert_residual", "assert_residual < bound; checks the residual of the latest solve"),
+];
+
+const ERROR: f64 = 1.0;
+const WARNING: f64 = 2.0;
+
+/// Byte offsets of the start and end of some source text.
+type Span = (usize, usize);
+
+struct Definition {
+    name: String,
+    span: Span,
+    value: f64,
+}
+
+struct Scope {
+    span: Span,
+    definitions: Vec<Definition>,
+    solutions: Vec<Solution>,
+}
+
+struct Diagnostic {
+    span: Span,
+    severity: f64,
+    message: String,
+}
+
+/// What the server knows about a document, found by running it.
+struct Analysis {
+    scopes: Vec<Scope>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Analysis {
+    /// The innermost scope around `offset`.
+    fn scope(&self, offset: usize) -> Option<&Scope> {
+        return self
+            .scopes
+            .iter()
+            .filter(|scope| scope.span.0 <= offset && offset <= scope.span.1)
+            .min_by_key(|scope| scope.span.1 - scope.span.0);
+    }
+}
+
+impl Scope {
+    /// The last assignment to `name` before `offset`, or the first one after it.
+    fn definition(&self, name: &str, offset: usize) -> Option<&Definition> {
+        let mut definitions = self.definitions.iter().filter(|definition| definition.name == name);
+        return match definitions.clone().rfind(|definition| definition.span.0 <= offset) {
+            Some(definition) => Option::Some(definition),
+            None => definitions.next(),
+        };
+    }
+}
+
+/// Runs every statement of `text` with the interpreter, keeping where each one is.
+fn analyze(text: &str) -> Analysis {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_seed(0);
+    let mut analysis = Analysis {
+        scopes: Vec::new(),
+        diagnostics: Vec::new(),
+    };
+    let mut open: Vec<(Scope, ScopeResult)> = Vec::new();
+    let mut statement: Option<Span> = Option::None;
+    let bytes = text.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        let cur = bytes[i];
+        if cur == b'/' && bytes.get(i + 1) == Option::Some(&b'/') {
+            while i < bytes.len() && bytes[i] != b'\n' {
+                i += 1;
+            }
+            continue;
+        }
+        match cur {
+            b'{' | b'}' => {
+                if let Some(span) = statement.take() {
+                    analysis.diagnostics.push(Diagnostic {
+                        span,
+                        severity: WARNING,
+                        message: "Missing `;`, this statement is ignored".to_string(),
+                    });
+                }
+                if cur == b'{' {
+                    let scope = Scope {
+                        span: (i, i),
+                        definitions: Vec::new(),
+                        solutions: Vec::new(),
+                    };
+                    open.push((scope, ScopeResult::default()));
+                } else {
+                    match open.pop() {
+                        Some((mut scope, _)) => {
+                            scope.span.1 = i + 1;
+                            analysis.scopes.push(scope);
+                        }
+                        None => analysis.diagnostics.push(Diagnostic {
+                            span: (i, i + 1),
+                            severity: ERROR,
+                            message: "Unmatched closing brace".to_string(),
+                        }),
+                    }
+                }
+            }
+            b';' => {
+                if let Some(span) = statement.take() {
+                    // Statements spanning several lines can have comments in between.
+                    let source = strip_comments(&String::from_utf8_lossy(&bytes[span.0..span.1]));
+                    match open.last_mut() {
+                        Some((scope, result)) => {
+                            if let Err((severity, message)) = execute(&mut interpreter, scope, result, &source, span) {
+                                analysis.diagnostics.push(Diagnostic { span, severity, message });
+                            }
+                        }
+                        None => analysis.diagnostics.push(Diagnostic {
+                            span,
+                            severity: WARNING,
+                            message: "Statements outside of a scope are ignored".to_string(),
+                        }),
+                    }
+                }
+            }
+            cur if cur.is_ascii_whitespace() => {}
+            _ => {
+                let start = statement.map_or(i, |span| span.0);
+                statement = Option::Some((start, i + 1));
+            }
+        }
+        i += 1;
+    }
+    if let Some(span) = statement {
+        analysis.diagnostics.push(Diagnostic {
+            span,
+            severity: WARNING,
+            message: "Missing `;`, this statement is ignored".to_string(),
+        });
+    }
+    for (mut scope, _) in open {
+        analysis.diagnostics.push(Diagnostic {
+            span: (scope.span.0, scope.span.0 + 1),
+            severity: ERROR,
+            message: "Unclosed scope".to_string(),
+        });
+        scope.span.1 = text.len();
+        analysis.scopes.push(scope);
+    }
+    return analysis;
+}
+
+/// Returns the severity and message of a statement that failed.
+fn execute(interpreter: &mut Interpreter, scope: &mut Scope, result: &mut ScopeResult, source: &str, span: Span) -> Result<(), (f64, String)> {
+    // Half typed statements must not take the server down with them.
+    let outcome = panic::catch_unwind(AssertUnwindSafe(|| interpreter.execute(source, result)));
+    match outcome {
+        Ok(Ok(Some(outcome))) => {
+            result.outcomes.push(outcome.clone());
+            match outcome {
+                Outcome::Solution(solution) => {
+                    let warning = solution.ill_conditioned().then(|| format!("{} is ill-conditioned, condition number: {}", solution.variable, solution.condition));
+                    scope.solutions.push(solution);
+                    if let Some(warning) = warning {
+                        return Err((WARNING, warning));
+                    }
+                }
+                Outcome::Assertion(assertion) if !assertion.passed => return Err((WARNING, format!("Assertion {}", assertion))),
+                _ => {}
+            }
+        }
+        Ok(Ok(None)) => {
+            let (name, value) = result.variables.last().unwrap();
+            let before = &source[..source.find(':').unwrap()];
+            let start = span.0 + before.len() - before.trim_start().len();
+            scope.definitions.push(Definition {
+                name: name.clone(),
+                span: (start, span.0 + before.trim_end().len()),
+                value: *value,
+            });
+        }
+        Ok(Err(message)) => return Err((ERROR, message)),
+        Err(_) => return Err((ERROR, "The interpreter failed on this statement".to_string())),
+    }
+    return Ok(());
+}
+
+/// LSP positions count lines and UTF-16 code units.
+fn position(text: &str, offset: usize) -> Json {
+    let before = String::from_utf8_lossy(&text.as_bytes()[..offset]).to_string();
+    let line_start = before.rfind('\n').map_or(0, |index| index + 1);
+    return Json::object(vec![
+        ("line", Json::Number(before.matches('\n').count() as f64)),
+        ("character", Json::Number(before[line_start..].encode_utf16().count() as f64)),
+    ]);
+}
+
+fn range(text: &str, span: Span) -> Json {
+    return Json::object(vec![("start", position(text, span.0)), ("end", position(text, span.1))]);
+}
+
+fn offset(text: &str, position: &Json) -> Option<usize> {
+    let line = position.get("line")?.as_f64()? as usize;
+    let character = position.get("character")?.as_f64()? as usize;
+    let mut start = 0;
+    for _ in 0..line {
+        start += text[start..].find('\n')? + 1;
+    }
+    let mut units = 0;
+    for (index, cur) in text[start..].char_indices() {
+        if units >= character || cur == '\n' {
+            return Option::Some(start + index);
+        }
+        units += cur.len_utf16();
+    }
+    return Option::Some(text.len());
+}
+
+/// The identifier around `offset`.
+fn word(text: &str, offset: usize) -> Option<(&str, Span)> {
+    let bytes = text.as_bytes();
+    let is_word = |index: usize| bytes[index].is_ascii_alphanumeric() || bytes[index] == b'_';
+    let mut start = offset;
+    while start > 0 && is_word(start - 1) {
+        start -= 1;
+    }
+    let mut end = offset;
+    while end < bytes.len() && is_word(end) {
+        end += 1;
+    }
+    if start == end {
+        return Option::None;
+    }
+    return Option::Some((&text[start..end], (start, end)));
+}
+
+/// How many arguments a builtin takes, like `1 or 2 arguments`.
+fn arguments(name: &str) -> String {
+    let arities: Vec<String> = BUILTINS.iter().filter(|builtin| builtin.name == name).map(|builtin| builtin.arity.to_string()).collect();
+    let plural = if arities == ["1"] { "" } else { "s" };
+    return format!("{} argument{}", arities.join(" or "), plural);
+}
+
+/// A language server for one client. Documents are synced in full on every change.
+pub struct Server {
+    documents: HashMap<String, (String, Analysis)>,
+    /// Whether the client asked the server to shut down.
+    pub shutdown: bool,
+    pub exited: bool,
+}
+
+impl Server {
+    pub fn new() -> Server {
+        return Server {
+            documents: HashMap::new(),
+            shutdown: false,
+            exited: false,
+        };
+    }
+
+    /// Handles one request or notification and returns the messages to send back.
+    pub fn handle(&mut self, message: &Json) -> Vec<Json> {
+        let method = message.get("method").and_then(Json::as_str).unwrap_or("");
+        let params = message.get("params").unwrap_or(&Json::Null);
+        let uri = params.get("textDocument").and_then(|document| document.get("uri")).and_then(Json::as_str).unwrap_or("").to_string();
+        let result = match method {
+            "initialize" => Json::object(vec![
+                (
+                    "capabilities",
+                    Json::object(vec![
+                        ("textDocumentSync", Json::Number(1.0)),
+                        ("hoverProvider", Json::Bool(true)),
+                        ("definitionProvider", Json::Bool(true)),
+                        ("completionProvider", Json::object(vec![])),
+                    ]),
+                ),
+                ("serverInfo", Json::object(vec![("name", Json::string("doodlang"))])),
+            ]),
+            "shutdown" => {
+                self.shutdown = true;
+                Json::Null
+            }
+            "exit" => {
+                self.exited = true;
+                return Vec::new();
+            }
+            "textDocument/didOpen" => {
+                let text = params.get("textDocument").and_then(|document| document.get("text")).and_then(Json::as_str);
+                return self.update(uri, text.unwrap_or("").to_string());
+            }
+            "textDocument/didChange" => {
+                let changes = params.get("contentChanges").and_then(Json::as_array);
+                let text = changes.and_then(|changes| changes.last()).and_then(|change| change.get("text")).and_then(Json::as_str);
+                return match text {
+                    Some(text) => self.update(uri, text.to_string()),
+                    None => Vec::new(),
+                };
+            }
+            "textDocument/didClose" => {
+                self.documents.remove(&uri);
+                return vec![diagnostics(&uri, Vec::new())];
+            }
+            "textDocument/hover" => self.hover(&uri, params).unwrap_or(Json::Null),
+            "textDocument/definition" => self.definition(&uri, params).unwrap_or(Json::Null),
+            "textDocument/completion" => self.completion(&uri, params),
+            _ => {
+                return match message.get("id") {
+                    Some(id) => vec![error(id.clone(), -32601.0, &format!("Method not found: {}", method))],
+                    None => Vec::new(),
+                };
+            }
+        };
+        return match message.get("id") {
+            Some(id) => vec![Json::object(vec![("jsonrpc", Json::string("2.0")), ("id", id.clone()), ("result", result)])],
+            None => Vec::new(),
+        };
+    }
+
+    fn update(&mut self, uri: String, text: String) -> Vec<Json> {
+        let analysis = analyze(&text);
+        let found = analysis
+            .diagnostics
+            .iter()
+            .map(|diagnostic| {
+                Json::object(vec![
+                    ("range", range(&text, diagnostic.span)),
+                    ("severity", Json::Number(diagnostic.severity)),
+                    ("source", Json::string("doodlang")),
+                    ("message", Json::string(&diagnostic.message)),
+                ])
+            })
+            .collect();
+        let notification = diagnostics(&uri, found);
+        self.documents.insert(uri, (text, analysis));
+        return vec![notification];
+    }
+
+    fn hover(&self, uri: &str, params: &Json) -> Option<Json> {
+        let (text, analysis) = self.documents.get(uri)?;
+        let offset = offset(text, params.get("position")?)?;
+        let (name, span) = word(text, offset)?;
+        let mut lines = Vec::new();
+        if let Some(scope) = analysis.scope(offset) {
+            if let Some(definition) = scope.definition(name, offset) {
+                lines.push(format!("{}: {}", name, definition.value));
+            }
+            for solution in scope.solutions.iter().filter(|solution| solution.variable == name) {
+                let converged = if solution.converged { "" } else { ", not converged" };
+                lines.push(format!("solved {} = {} (abs err {}{}) from {}", name, solution.value, solution.residual, converged, solution.formula));
+            }
+        }
+        if lines.is_empty() {
+            if let Some((_, description)) = KEYWORDS.iter().find(|(keyword, _)| *keyword == name) {
+                lines.push(description.to_string());
+            } else if BUILTINS.iter().any(|builtin| builtin.name == name) {
+                lines.push(format!("builtin function {}, taking {}", name, arguments(name)));
+            } else {
+                return Option::None;
+            }
+        }
+        return Option::Some(Json::object(vec![
+            ("contents", Json::object(vec![("kind", Json::string("plaintext")), ("value", Json::string(&lines.join("\n")))])),
+            ("range", range(text, span)),
+        ]));
+    }
+
+    fn definition(&self, uri: &str, params: &Json) -> Option<Json> {
+        let (text, analysis) = self.documents.get(uri)?;
+        let offset = offset(text, params.get("position")?)?;
+        let (name, _) = word(text, offset)?;
+        let definition = analysis.scope(offset)?.definition(name, offset)?;
+        return Option::Some(Json::object(vec![("uri", Json::string(uri)), ("range", range(text, definition.span))]));
+    }
+
+    fn completion(&self, uri: &str, params: &Json) -> Json {
+        let mut items = Vec::new();
+        let item = |label: &str, kind: f64, detail: String| {
+            return Json::object(vec![("label", Json::string(label)), ("kind", Json::Number(kind)), ("detail", Json::string(&detail))]);
+        };
+        if let Some((text, analysis)) = self.documents.get(uri) {
+            let scope = params.get("position").and_then(|position| offset(text, position)).and_then(|offset| analysis.scope(offset));
+            if let Some(scope) = scope {
+                let mut names: Vec<&str> = Vec::new();
+                for definition in scope.definitions.iter().rev() {
+                    if !names.contains(&definition.name.as_str()) {
+                        names.push(&definition.name);
+                        items.push(item(&definition.name, 6.0, definition.value.to_string()));
+                    }
+                }
+            }
+        }
+        for (keyword, description) in KEYWORDS {
+            items.push(item(keyword, 14.0, description.to_string()));
+        }
+        let mut names: Vec<&str> = BUILTINS.iter().map(|builtin| builtin.name.as_str()).collect();
+        names.dedup();
+        for name in names {
+            items.push(item(name, 3.0, format!("builtin function, {}", arguments(name))));
+        }
+        return Json::Array(items);
+    }
+}
+
+impl Default for Server {
+    fn default() -> Self {
+        return Server::new();
+    }
+}
+
+fn diagnostics(uri: &str, diagnostics: Vec<Json>) -> Json {
+    return Json::object(vec![
+        ("jsonrpc", Json::string("2.0")),
+        ("method", Json::string("textDocument/publishDiagnostics")),
+        ("params", Json::object(vec![("uri", Json::string(uri)), ("diagnostics", Json::Array(diagnostics))])),
+    ]);
+}
+
+fn error(id: Json, code: f64, message: &str) -> Json {
+    return Json::object(vec![
+        ("jsonrpc", Json::string("2.0")),
+        ("id", id),
+        ("error", Json::object(vec![("code", Json::Number(code)), ("message", Json::string(message))])),
+    ]);
+}
+
+/// Reads one `Content-Length` framed message. Returns `None` at the end of the input.
+pub fn read_message(reader: &mut impl BufRead) -> io::Result<Option<Result<Json, String>>> {
+    let mut length: Option<usize> = Option::None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(Option::None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some(value) = line.strip_prefix("Content-Length:") {
+            length = value.trim().parse::<usize>().ok();
+        }
+    }
+    let length = match length {
+        Some(length) => length,
+        None => return Ok(Option::Some(Err("Missing Content-Length header".to_string()))),
+    };
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    return Ok(Option::Some(match String::from_utf8(body) {
+        Ok(body) => Json::parse(&body),
+        Err(_) => Err("Message is not UTF-8".to_string()),
+    }));
+}
+
+pub fn write_message(writer: &mut impl Write, message: &Json) -> io::Result<()> {
+    let body = message.to_string();
+    write!(writer, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    return writer.flush();
+}
+
+/// Serves one client until it sends `exit`. Returns the exit code the protocol asks for, which is 1
+/// when the client never requested a shutdown.
+pub fn serve(mut reader: impl BufRead, mut writer: impl Write) -> io::Result<i32> {
+    let mut server = Server::new();
+    while !server.exited {
+        let message = match read_message(&mut reader)? {
+            Some(Ok(message)) => message,
+            Some(Err(message)) => {
+                write_message(&mut writer, &error(Json::Null, -32700.0, &message))?;
+                continue;
+            }
+            None => break,
+        };
+        for reply in server.handle(&message) {
+            write_message(&mut writer, &reply)?;
+        }
+    }
+    return Ok(if server.shutdown { 0 } else { 1 });
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::doodlang::json::Json;
+    use crate::doodlang::lsp::{read_message, serve};
+    use std::io::Cursor;
+
+    /// Runs a scripted session and returns everything the server sent.
+    fn transcript(messages: &[&str]) -> (Vec<Json>, i32) {
+        let mut input = String::new();
+        for message in messages {
+            input += &format!("Content-Length: {}\r\n\r\n{}", message.len(), message);
+        }
+        let mut output = Vec::new();
+        let code = serve(Cursor::new(input), &mut output).unwrap();
+        let mut reader = Cursor::new(output);
+        let mut replies = Vec::new();
+        while let Some(reply) = read_message(&mut reader).unwrap() {
+            replies.push(reply.unwrap());
+        }
+        return (replies, code);
+    }
+
+    fn request(id: usize, method: &str, line: usize, character: usize) -> String {
+        return format!(
+            r#"{{"jsonrpc":"2.0","id":{},"method":"{}","params":{{"textDocument":{{"uri":"file:///a.dood"}},"position":{{"line":{},"character":{}}}}}}}"#,
+            id, method, line, character
+        );
+    }
+
+    fn value<'a>(reply: &'a Json, path: &[&str]) -> &'a Json {
+        return path.iter().fold(reply, |value, key| value.get(key).unwrap());
+    }
+
+    #[test]
+    fn answers_a_session() {
+        let text = r#"{\n    x: 1;\n    y: 2; // two\n    solve(x) <- x^2 = y;\n    solve(x) <- foo(x) = sin(1);\n    z\n}\nw: 3;"#;
+        let open = format!(
+            r#"{{"jsonrpc":"2.0","method":"textDocument/didOpen","params":{{"textDocument":{{"uri":"file:///a.dood","languageId":"doodlang","version":1,"text":"{}"}}}}}}"#,
+            text
+        );
+        let (replies, code) = transcript(&[
+            r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{}}"#,
+            r#"{"jsonrpc":"2.0","method":"initialized","params":{}}"#,
+            &open,
+            &request(2, "textDocument/hover", 3, 22),
+            &request(3, "textDocument/definition", 3, 22),
+            &request(4, "textDocument/completion", 3, 0),
+            &request(5, "textDocument/hover", 3, 16),
+            &request(6, "textDocument/hover", 4, 26),
+            &request(7, "textDocument/hover", 3, 5),
+            r#"{"jsonrpc":"2.0","id":8,"method":"textDocument/formatting","params":{}}"#,
+            r#"{"jsonrpc":"2.0","id":9,"method":"shutdown"}"#,
+            r#"{"jsonrpc":"2.0","method":"exit"}"#,
+        ]);
+        assert_eq!(code, 0);
+        assert_eq!(replies.len(), 10);
+        assert_eq!(value(&replies[0], &["result", "capabilities", "hoverProvider"]), &Json::Bool(true));
+
+        assert_eq!(value(&replies[1], &["method"]).as_str(), Option::Some("textDocument/publishDiagnostics"));
+        let messages: Vec<String> = value(&replies[1], &["params", "diagnostics"])
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|diagnostic| {
+                let start = value(diagnostic, &["range", "start"]);
+                format!("{}:{} {}", value(start, &["line"]), value(start, &["character"]), value(diagnostic, &["message"]).as_str().unwrap())
+            })
+            .collect();
+        assert_eq!(
+            messages,
+            vec!["4:4 Unknown function: foo", "5:4 Missing `;`, this statement is ignored", "7:0 Statements outside of a scope are ignored"]
+        );
+
+        assert_eq!(value(&replies[2], &["result", "contents", "value"]).as_str(), Option::Some("y: 2"));
+        assert_eq!(
+            value(&replies[3], &["result", "range"]).to_string(),
+            r#"{"start":{"line":2,"character":4},"end":{"line":2,"character":5}}"#
+        );
+        let labels: Vec<&str> = value(&replies[4], &["result"])
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| value(item, &["label"]).as_str().unwrap())
+            .collect();
+        assert_eq!(&labels[..4], &["y", "x", "solve", "eigen"]);
+        assert!(labels.contains(&"sin") && labels.contains(&"log"));
+        let solved = value(&replies[5], &["result", "contents", "value"]).as_str().unwrap();
+        assert!(solved.starts_with("x: 1\nsolved x = ") && solved.contains("1.41421356237309"), "{}", solved);
+        assert!(solved.ends_with("from x^2=y"), "{}", solved);
+        assert_eq!(value(&replies[6], &["result", "contents", "value"]).as_str(), Option::Some("builtin function sin, taking 1 argument"));
+        assert!(value(&replies[7], &["result", "contents", "value"]).as_str().unwrap().starts_with("solve(x) <- formula;"));
+        assert_eq!(value(&replies[8], &["error", "code"]), &Json::Number(-32601.0));
+        assert_eq!(value(&replies[9], &["result"]), &Json::Null);
+    }
+
+    #[test]
+    fn ignores_comments_inside_statements() {
+        let text = r#"{\n    x: 1;\n    solve(x) <- x // half\n        * 2 = 3;\n}"#;
+        let open = format!(
+            r#"{{"jsonrpc":"2.0","method":"textDocument/didOpen","params":{{"textDocument":{{"uri":"file:///a.dood","languageId":"doodlang","version":1,"text":"{}"}}}}}}"#,
+            text
+        );
+        let (replies, _) = transcript(&[&open, &request(1, "textDocument/hover", 2, 16), r#"{"jsonrpc":"2.0","method":"exit"}"#]);
+        assert_eq!(value(&replies[0], &["params", "diagnostics"]).as_array().map(|diagnostics| diagnostics.len()), Option::Some(0));
+        let solved = value(&replies[1], &["result", "contents", "value"]).as_str().unwrap();
+        assert!(solved.contains("solved x = 1.5"), "{}", solved);
+    }
+
+    #[test]
+    fn exits_with_one_without_shutdown() {
+        let (replies, code) = transcript(&["{broken", r#"{"jsonrpc":"2.0","method":"exit"}"#]);
+        assert_eq!(replies[0].get("error").and_then(|error| error.get("code")), Option::Some(&Json::Number(-32700.0)));
+        assert_eq!(code, 1);
+    }
+}
@@ -1,2 +1,7 @@
+pub mod format;
 pub mod interpreter;
+pub mod json;
+pub mod lsp;
 pub mod parser;
+pub mod repl;
+pub mod report;
@@ -1,15 +1,34 @@
 use std::collections::HashMap;
-use crate::eq_solver::function::{Function, Sum, Mul, Variable, Sub, Div, Pow, Ln, Log, Cosine, Sine, Constant};
-use std::ops::Deref;
-use crate::print_vec;
-use std::rc::Rc;
+use crate::eq_solver::builtin::FunctionRegistry;
+use crate::eq_solver::expr::{Expr, ExprArena, ExprId};
+use crate::eq_solver::function::Function;
 use std::cmp::min;
+use std::fmt;
+use std::sync::Arc;
 
 pub struct Parser;
 
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseError {
+    pub message: String,
+}
+
+impl ParseError {
+    pub fn new(message: String) -> ParseError {
+        ParseError {
+            message,
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return write!(f, "{}", self.message);
+    }
+}
+
 const OPER: [&str; 6] = ["=", "-", "+", "/", "*", "^"];
 const PRIORITIES: [usize; 6] = [0, 1, 1, 2, 2, 3];
-const FUNC: [&str; 4] = ["log", "ln", "sin", "cos"];
 
 pub fn smart_generate_tree(formula: &str) -> Node<[usize; 2]> {
     let mut cur_scope = 0usize;
@@ -31,7 +50,10 @@ pub fn smart_generate_tree(formula: &str) -> Node<[usize; 2]> {
     return parent;
 }
 
-pub fn recursive_generate(reference_point: usize, parent: &mut Node<[usize; 2]>, formula: &str, scopes: &Vec<usize>) {
+pub fn recursive_generate(reference_point: usize, parent: &mut Node<[usize; 2]>, formula: &str, scopes: &[usize]) {
+    if formula.is_empty() {
+        return;
+    }
     let current_scope = scopes[0];
     let min_local_scope = find_min_local_scope(formula);
     let mut max_index = 0;
@@ -40,8 +62,7 @@ pub fn recursive_generate(reference_point: usize, parent: &mut Node<[usize; 2]>,
     for i in 0..OPER.len() {
         let oper = OPER[i];
         let pos = operator_in_scope(formula, oper, min_local_scope);
-        if pos.is_some() {
-            let index = pos.unwrap();
+        if let Some(index) = pos {
             if PRIORITIES[i] > min_priority {
                 break;
             } else {
@@ -89,40 +110,34 @@ pub fn recursive_generate(reference_point: usize, parent: &mut Node<[usize; 2]>,
             reference_point + start_index,
             &mut left,
             &formula[start_index..index],
-            &scopes[start_index..index].to_vec(),
+            &scopes[start_index..index],
         );
         recursive_generate(
             reference_point + index + 1,
             &mut right,
             &formula[index + 1..end_index],
-            &scopes[index + 1..end_index].to_vec(),
+            &scopes[index + 1..end_index],
         );
         parent.children.push(left);
         parent.children.push(right);
         return;
     }
-    for func in &FUNC {
-        if formula.len() > func.len() + 2 {
-            let pos = function_in_scope(formula, func, min_local_scope);
-            if pos.is_some() {
-                let func_start = pos.unwrap();
-                let start = func_start + func.len();
-                let start_scope = scopes[start];
-                let mut end_index = formula.len();
-                for i in start..formula.len() {
-                    if scopes[i] != start_scope {
-                        end_index = i;
-                    }
-                }
-                parse_csv(
-                    reference_point + start + 1,
-                    parent,
-                    &formula[start + 1..end_index - 1],
-                    &scopes[start + 1..end_index - 1].to_vec(),
-                );
-                return;
+    if let Some((_, start)) = call_in_scope(formula, min_local_scope) {
+        let start_scope = scopes[start];
+        let mut end_index = formula.len();
+        for (i, scope) in scopes.iter().enumerate().skip(start) {
+            if *scope < start_scope {
+                end_index = i;
+                break;
             }
         }
+        parse_csv(
+            reference_point + start + 1,
+            parent,
+            &formula[start + 1..end_index - 1],
+            &scopes[start + 1..end_index - 1],
+        );
+        return;
     }
     let mut end_parse = true;
     for i in 0..scopes.len() {
@@ -139,40 +154,54 @@ pub fn recursive_generate(reference_point: usize, parent: &mut Node<[usize; 2]>,
     let end = parent.value[1] - 1;
     if end > start {
         let mut sub_node = Node::new([start, end]);
-        recursive_generate(reference_point + 1, &mut sub_node, &formula[1..formula.len() - 1], &scopes[1..formula.len() - 1].to_vec());
+        recursive_generate(reference_point + 1, &mut sub_node, &formula[1..formula.len() - 1], &scopes[1..formula.len() - 1]);
         parent.children.push(sub_node);
     }
 }
 
-fn parse_csv(reference_point: usize, node: &mut Node<[usize; 2]>, values: &str, scopes: &Vec<usize>) {
+fn parse_csv(reference_point: usize, node: &mut Node<[usize; 2]>, values: &str, scopes: &[usize]) {
     let mut act_pos = 0;
     loop {
-        let comma = (&values[act_pos..values.len()]).find(",");
-        if comma.is_none() {
-            let mut inner = Node::new([reference_point + act_pos, reference_point + values.len()]);
+        let comma = top_level_comma(&values[act_pos..values.len()]);
+        if let Some(pos) = comma {
+            let mut inner = Node::new([reference_point + act_pos, reference_point + act_pos + pos]);
             recursive_generate(
                 reference_point + act_pos,
                 &mut inner,
-                &values[act_pos..values.len()],
-                &scopes[act_pos..values.len()].to_vec(),
+                &values[act_pos..act_pos + pos],
+                &scopes[act_pos..act_pos + pos],
             );
+            act_pos += pos + 1;
             node.children.push(inner);
-            break;
         } else {
-            let pos = comma.unwrap();
-            let mut inner = Node::new([reference_point + act_pos, reference_point + act_pos + pos]);
+            let mut inner = Node::new([reference_point + act_pos, reference_point + values.len()]);
             recursive_generate(
                 reference_point + act_pos,
                 &mut inner,
-                &values[act_pos..act_pos + pos],
-                &scopes[act_pos..act_pos + pos].to_vec(),
+                &values[act_pos..values.len()],
+                &scopes[act_pos..values.len()],
             );
-            act_pos += pos + 1;
             node.children.push(inner);
+            break;
         }
     }
 }
 
+/// Finds the first comma outside of any parentheses, which ends a call argument.
+fn top_level_comma(values: &str) -> Option<usize> {
+    let mut scope = 0;
+    for (i, cur) in values.char_indices() {
+        if cur == '(' {
+            scope += 1;
+        } else if cur == ')' {
+            scope -= 1;
+        } else if cur == ',' && scope == 0 {
+            return Option::Some(i);
+        }
+    }
+    return Option::None;
+}
+
 fn operator_in_scope(formula: &str, operator: &str, current_scope: usize) -> Option<usize> {
     let mut scope = 0;
     for i in (0..formula.len()).rev() {
@@ -189,21 +218,27 @@ fn operator_in_scope(formula: &str, operator: &str, current_scope: usize) -> Opt
     return Option::None;
 }
 
-fn function_in_scope(formula: &str, function: &str, current_scope: usize) -> Option<usize> {
-    if formula.len() < function.len() {
-        return Option::None;
-    }
-    let func_len = function.len();
+/// Finds a call `name(...)` whose name is in the given scope, returning the positions of the
+/// name and of its opening parenthesis.
+fn call_in_scope(formula: &str, current_scope: usize) -> Option<(usize, usize)> {
     let mut scope = 0;
-    for i in 0..formula.len() + 1 - func_len {
-        let cur = &formula[i..i + 1];
-        if cur == "(" {
+    let mut name_start = Option::None;
+    for (i, cur) in formula.char_indices() {
+        if cur == '(' {
+            if let (true, Some(start)) = (scope == current_scope, name_start) {
+                return Option::Some((start, i));
+            }
             scope += 1;
-        } else if cur == ")" {
+            name_start = Option::None;
+        } else if cur == ')' {
             scope -= 1;
-        }
-        if scope == current_scope && &formula[i..i + func_len] == function {
-            return Option::Some(i);
+            name_start = Option::None;
+        } else if scope == current_scope && (cur.is_ascii_alphanumeric() || cur == '_') {
+            if name_start.is_none() && cur.is_ascii_alphabetic() {
+                name_start = Option::Some(i);
+            }
+        } else {
+            name_start = Option::None;
         }
     }
     return Option::None;
@@ -234,6 +269,19 @@ pub fn generate_tree(formula: &str) -> Node<[usize; 2]> {
     return nodes.pop().unwrap();
 }
 
+/// Prints the slices of `formula` covered by the leaves of `node`, nested in parentheses.
+pub fn print_tree(node: &Node<[usize; 2]>, formula: &str) {
+    if node.children.is_empty() {
+        print!("({})", &formula[node.value[0]..node.value[1]]);
+    } else {
+        for child in &node.children {
+            print!("(");
+            print_tree(child, formula);
+            print!(")");
+        }
+    }
+}
+
 pub struct Node<T> {
     pub value: T,
     pub children: Vec<Node<T>>,
@@ -255,120 +303,127 @@ impl<T> Node<T> {
 pub struct FunctionManager<'a> {
     pub ids: HashMap<&'a str, usize>,
     pub variables: Vec<f64>,
+    pub functions: FunctionRegistry,
 }
 
 impl FunctionManager<'_> {
-    pub fn new<'a>(variables: &'a Vec<(&str, f64)>) -> FunctionManager<'a> {
+    pub fn new<'a>(variables: &'a [(&str, f64)]) -> FunctionManager<'a> {
+        return FunctionManager::with_functions(variables, FunctionRegistry::new());
+    }
+
+    /// A manager resolving calls with `functions`, which may hold host registered functions.
+    pub fn with_functions<'a>(variables: &'a [(&str, f64)], functions: FunctionRegistry) -> FunctionManager<'a> {
         let mut ids = HashMap::new();
         let mut values = Vec::new();
-        for i in 0..variables.len() {
-            ids.insert(variables[i].0, i);
-            values.push(variables[i].1);
+        for (i, variable) in variables.iter().enumerate() {
+            ids.insert(variable.0, i);
+            values.push(variable.1);
         }
         FunctionManager {
             ids,
             variables: values,
+            functions,
         }
     }
-    pub fn generate_func(&mut self, formula: &str, node: &Node<[usize; 2]>) -> Box<dyn Function> {
-        return self.recursive_parse(formula, node);
+    pub fn generate_func(&mut self, formula: &str, node: &Node<[usize; 2]>) -> Result<Box<dyn Function>, ParseError> {
+        let mut arena = ExprArena::new();
+        let expr = self.generate_expr(&mut arena, formula, node)?;
+        return Ok(arena.to_function(expr));
     }
 
+    pub fn generate_expr(&mut self, arena: &mut ExprArena, formula: &str, node: &Node<[usize; 2]>) -> Result<ExprId, ParseError> {
+        return self.recursive_parse(arena, formula, node);
+    }
 
-    fn recursive_parse(&mut self, formula: &str, node: &Node<[usize; 2]>) -> Box<dyn Function> {
+    /// Checks `formula` before building its tree, so malformed input is reported instead of panicking.
+    pub fn parse(&mut self, arena: &mut ExprArena, formula: &str) -> Result<ExprId, ParseError> {
+        if let Some(cur) = formula.chars().find(|cur| !cur.is_ascii() || cur.is_ascii_whitespace()) {
+            return Err(ParseError::new(format!("Unexpected character {:?} in: {}", cur, formula)));
+        }
+        let mut scope = 0;
+        for cur in formula.chars() {
+            if cur == '(' {
+                scope += 1;
+            } else if cur == ')' {
+                if scope == 0 {
+                    return Err(ParseError::new(format!("Unmatched closing parenthesis in: {}", formula)));
+                }
+                scope -= 1;
+            }
+        }
+        if scope != 0 {
+            return Err(ParseError::new(format!("Unclosed parenthesis in: {}", formula)));
+        }
+        let tree = smart_generate_tree(formula);
+        return self.generate_expr(arena, formula, &tree);
+    }
+
+    fn recursive_parse(&mut self, arena: &mut ExprArena, formula: &str, node: &Node<[usize; 2]>) -> Result<ExprId, ParseError> {
         let start_index = node.value[0];
         let end_index = node.value[1];
         let node_formula = &formula[start_index..end_index];
         let min_local_scope = find_min_local_scope(node_formula);
-        if node.children.len() == 1 {
-            let mut min_func_scope = function_in_scope(node_formula, "ln", min_local_scope);
-            if min_func_scope.is_some() {
-                return Box::new(
-                    Ln {
-                        input: self.recursive_parse(formula, &node.children[0]),
-                    }
-                );
-            }
-            let mut min_func_scope = function_in_scope(node_formula, "sin", min_local_scope);
-            if min_func_scope.is_some() {
-                return Box::new(
-                    Sine {
-                        input: self.recursive_parse(formula, &node.children[0]),
-                    }
-                );
+        let has_operator = OPER.iter().any(|oper| find_index(node_formula, oper, min_local_scope).is_some());
+        if !has_operator {
+            if let Some((name_start, name_end)) = call_in_scope(node_formula, min_local_scope) {
+                return self.parse_call(arena, formula, node, &node_formula[name_start..name_end]);
             }
-            let mut min_func_scope = function_in_scope(node_formula, "cos", min_local_scope);
-            if min_func_scope.is_some() {
-                return Box::new(
-                    Cosine {
-                        input: self.recursive_parse(formula, &node.children[0]),
-                    }
-                );
-            }
-            return self.recursive_parse(formula, &node.children[0]);
+        }
+        if node.children.len() == 1 {
+            return self.recursive_parse(arena, formula, &node.children[0]);
+        }
+        if has_operator && node.children.len() != 2 {
+            return Err(ParseError::new(format!("Malformed expression: {}", node_formula)));
         }
         if find_index(node_formula, "-", min_local_scope).is_some() ||
             find_index(node_formula, "=", min_local_scope).is_some() {
-            return Box::new(
-                Sub {
-                    left: self.recursive_parse(formula, &node.children[0]),
-                    right: self.recursive_parse(formula, &node.children[1]),
-                }
-            );
+            let left = self.recursive_parse(arena, formula, &node.children[0])?;
+            let right = self.recursive_parse(arena, formula, &node.children[1])?;
+            return Ok(arena.add(Expr::Sub(left, right)));
         } else if find_index(node_formula, "+", min_local_scope).is_some() {
-            return Box::new(
-                Sum {
-                    left: self.recursive_parse(formula, &node.children[0]),
-                    right: self.recursive_parse(formula, &node.children[1]),
-                }
-            );
+            let left = self.recursive_parse(arena, formula, &node.children[0])?;
+            let right = self.recursive_parse(arena, formula, &node.children[1])?;
+            return Ok(arena.add(Expr::Sum(left, right)));
         } else if find_index(node_formula, "/", min_local_scope).is_some() {
-            return Box::new(
-                Div {
-                    left: self.recursive_parse(formula, &node.children[0]),
-                    right: self.recursive_parse(formula, &node.children[1]),
-                }
-            );
+            let left = self.recursive_parse(arena, formula, &node.children[0])?;
+            let right = self.recursive_parse(arena, formula, &node.children[1])?;
+            return Ok(arena.add(Expr::Div(left, right)));
         } else if find_index(node_formula, "*", min_local_scope).is_some() {
-            return Box::new(
-                Mul {
-                    left: self.recursive_parse(formula, &node.children[0]),
-                    right: self.recursive_parse(formula, &node.children[1]),
-                }
-            );
+            let left = self.recursive_parse(arena, formula, &node.children[0])?;
+            let right = self.recursive_parse(arena, formula, &node.children[1])?;
+            return Ok(arena.add(Expr::Mul(left, right)));
         } else if find_index(node_formula, "^", min_local_scope).is_some() {
-            return Box::new(
-                Pow {
-                    base: self.recursive_parse(formula, &node.children[0]),
-                    power: self.recursive_parse(formula, &node.children[1]),
-                }
-            );
+            let base = self.recursive_parse(arena, formula, &node.children[0])?;
+            let power = self.recursive_parse(arena, formula, &node.children[1])?;
+            return Ok(arena.add(Expr::Pow(base, power)));
         } else {
-            let min_func_scope = function_in_scope(node_formula, "log", min_local_scope);
-            if min_func_scope.is_some() {
-                return Box::new(
-                    Log {
-                        base: self.recursive_parse(formula, &node.children[0]),
-                        input: self.recursive_parse(formula, &node.children[1]),
-                    }
-                );
-            }
-            let val = self.ids.get(node_formula);
-            if val.is_some() {
-                Box::new(
-                    Variable {
-                        id: *self.ids.get(node_formula).unwrap(),
-                    }
-                )
-            } else {
-                Box::new(
-                    Constant {
-                        val: node_formula.parse::<f64>().unwrap(),
-                    }
-                )
+            if let Some(id) = self.ids.get(node_formula) {
+                return Ok(arena.add(Expr::Variable(*id)));
             }
+            return match node_formula.parse::<f64>() {
+                Ok(val) => Ok(arena.add(Expr::Constant(val))),
+                Err(_) if node_formula.is_empty() => Err(ParseError::new(format!("Missing operand in: {}", formula))),
+                Err(_) => Err(ParseError::new(format!("Unknown variable: {}", node_formula))),
+            };
         }
     }
+
+    fn parse_call(&mut self, arena: &mut ExprArena, formula: &str, node: &Node<[usize; 2]>, name: &str) -> Result<ExprId, ParseError> {
+        let function = match self.functions.get(name, node.children.len()) {
+            Some(function) => Arc::clone(function),
+            None if self.functions.contains(name) => {
+                let arities: Vec<String> = self.functions.arities(name).iter().map(|arity| arity.to_string()).collect();
+                let message = format!("{} takes {} arguments, found {}", name, arities.join(" or "), node.children.len());
+                return Err(ParseError::new(message));
+            }
+            None => return Err(ParseError::new(format!("Unknown function: {}", name))),
+        };
+        let arguments = node.children
+            .iter()
+            .map(|child| self.recursive_parse(arena, formula, child))
+            .collect::<Result<Vec<ExprId>, ParseError>>()?;
+        return Ok(arena.add_call(&function, &arguments));
+    }
 }
 
 fn find_min_local_scope(formula: &str) -> usize {
@@ -377,7 +432,7 @@ fn find_min_local_scope(formula: &str) -> usize {
     let mut last = true;
     for i in 0..formula.len() {
         let cur = &formula[i..i + 1];
-        if last == false {
+        if !last {
             min_scope = min(scope, min_scope);
         }
         if cur == "(" {
@@ -407,4 +462,37 @@ fn find_index(formula: &str, operation: &str, local_scope: usize) -> Option<usiz
         }
     }
     return Option::None;
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::doodlang::parser::{smart_generate_tree, FunctionManager};
+    use crate::eq_solver::builtin::FunctionRegistry;
+
+    fn evaluate(formula: &str, x: f64) -> f64 {
+        let variables = [("x", x)];
+        let mut function_manager = FunctionManager::new(&variables);
+        let function = function_manager.generate_func(formula, &smart_generate_tree(formula)).unwrap();
+        return function.output(&function_manager.variables);
+    }
+
+    #[test]
+    fn parses_builtin_calls() {
+        assert_eq!(evaluate("sin(cos(x))", 0.5), 0.5f64.cos().sin());
+        assert_eq!(evaluate("max(min(x,1),0.2)*2", 0.1), 0.4);
+        assert_eq!(evaluate("log(2,x^2)+log(x)", 4.0), 4.0 + 4f64.ln());
+        assert_eq!(evaluate("(hypot(sqrt(x),(4)))", 9.0), 5.0);
+        assert_eq!(evaluate("atan2(x,1)", 1.0), std::f64::consts::FRAC_PI_4);
+    }
+
+    #[test]
+    fn parses_host_functions() {
+        let mut functions = FunctionRegistry::new();
+        functions.register("clamp", 3, |x| x[0].max(x[1]).min(x[2]));
+        let variables = [("x", 5.0)];
+        let mut function_manager = FunctionManager::with_functions(&variables, functions);
+        let formula = "clamp(x,0,2)+sqrt(x-1)";
+        let function = function_manager.generate_func(formula, &smart_generate_tree(formula)).unwrap();
+        assert_eq!(function.output(&function_manager.variables), 4.0);
+    }
+}
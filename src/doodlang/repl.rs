@@ -0,0 +1,177 @@
+use crate::doodlang::interpreter::{strip_comments, Interpreter, Outcome, ScopeResult};
+
+const HELP: &str = "statements end with `;`, formulas without one are evaluated
+{ ... }       a block, which may span several lines
+:vars         list the variables in scope
+:clear        forget every variable
+:options      show the seed
+:history      list earlier inputs, `!n` runs input n again
+:quit         leave the repl";
+
+/// An interactive session that keeps one scope alive across inputs.
+///
+/// Unlike a program scope, reassigning a variable replaces it and solving for a variable assigns the
+/// solution to it, so later inputs can build on earlier results.
+pub struct Repl {
+    pub interpreter: Interpreter,
+    pub scope: ScopeResult,
+    /// Every complete input, in order.
+    pub history: Vec<String>,
+    pub finished: bool,
+    pending: String,
+}
+
+impl Repl {
+    pub fn new(interpreter: Interpreter) -> Repl {
+        return Repl {
+            interpreter,
+            scope: ScopeResult::default(),
+            history: Vec::new(),
+            finished: false,
+            pending: String::new(),
+        };
+    }
+
+    /// Whether a block is still open and more lines are expected.
+    pub fn is_pending(&self) -> bool {
+        return !self.pending.is_empty();
+    }
+
+    /// Handles one line of input. Returns what to print, errors as `Err`.
+    pub fn input(&mut self, line: &str) -> Vec<Result<String, String>> {
+        if !self.is_pending() {
+            let command = line.trim();
+            if command.starts_with(':') {
+                return self.command(command);
+            }
+            if let Some(index) = command.strip_prefix('!') {
+                return match index.parse::<usize>().ok().and_then(|index| self.history.get(index)) {
+                    Some(entry) => {
+                        let entry = entry.clone();
+                        self.history.push(entry.clone());
+                        self.run(&entry)
+                    }
+                    None => vec![Err(format!("No history entry: {}", index))],
+                };
+            }
+        }
+        self.pending += &strip_comments(line);
+        self.pending += "\n";
+        let depth = self.pending.matches('{').count() as isize - self.pending.matches('}').count() as isize;
+        if depth > 0 {
+            return Vec::new();
+        }
+        let entry = self.pending.trim().to_string();
+        self.pending.clear();
+        if entry.is_empty() {
+            return Vec::new();
+        }
+        self.history.push(entry.clone());
+        return self.run(&entry);
+    }
+
+    fn command(&mut self, command: &str) -> Vec<Result<String, String>> {
+        return match command {
+            ":vars" => self.scope.variables.iter().map(|(name, value)| Ok(format!("{}: {}", name, value))).collect(),
+            ":clear" => {
+                self.scope = ScopeResult::default();
+                Vec::new()
+            }
+            ":options" => vec![Ok(format!("seed: {}", self.interpreter.seed()))],
+            ":history" => self.history.iter().enumerate().map(|(i, entry)| Ok(format!("{}: {}", i, entry))).collect(),
+            ":help" => vec![Ok(HELP.to_string())],
+            ":quit" | ":q" => {
+                self.finished = true;
+                Vec::new()
+            }
+            _ => vec![Err(format!("Unknown command: {}, try :help", command))],
+        };
+    }
+
+    /// Runs the statements of an input, treating blocks as part of the session scope.
+    fn run(&mut self, entry: &str) -> Vec<Result<String, String>> {
+        let code: String = entry.chars().filter(|cur| *cur != '{' && *cur != '}').collect();
+        let mut output = Vec::new();
+        for statement in code.split(';') {
+            let statement = statement.trim();
+            if statement.is_empty() {
+                continue;
+            }
+            // Every statement assigns with `:` or `<-` or is an assertion, anything else is a formula to evaluate.
+            if !statement.contains(':') && !statement.contains("<-") && !statement.starts_with("assert") {
+                output.push(self.interpreter.evaluate(statement, &self.scope.variables).map(|value| value.to_string()));
+                continue;
+            }
+            match self.interpreter.execute(statement, &mut self.scope) {
+                Ok(outcome) => {
+                    if let Some(Outcome::Solution(solution)) = &outcome {
+                        self.assign(solution.variable.clone(), solution.value);
+                    } else if outcome.is_none() {
+                        let (name, value) = self.scope.variables.pop().unwrap();
+                        self.assign(name, value);
+                    }
+                    if let Some(outcome) = outcome {
+                        output.push(Ok(outcome.to_string().trim_end().to_string()));
+                        self.scope.outcomes.push(outcome);
+                    }
+                }
+                Err(message) => output.push(Err(message)),
+            }
+        }
+        return output;
+    }
+
+    fn assign(&mut self, name: String, value: f64) {
+        match self.scope.variables.iter_mut().find(|(variable, _)| *variable == name) {
+            Some(variable) => variable.1 = value,
+            None => self.scope.variables.push((name, value)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::doodlang::interpreter::Interpreter;
+    use crate::doodlang::repl::Repl;
+
+    fn output(repl: &mut Repl, line: &str) -> Vec<String> {
+        return repl.input(line).into_iter().map(|line| line.unwrap_or_else(|err| format!("error: {}", err))).collect();
+    }
+
+    #[test]
+    fn keeps_scope_across_inputs() {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_seed(1);
+        let mut repl = Repl::new(interpreter);
+        assert!(output(&mut repl, "x: 1; y: 2;").is_empty());
+        assert_eq!(output(&mut repl, "x + y"), vec!["3"]);
+        assert_eq!(output(&mut repl, "y: 9;"), Vec::<String>::new());
+        assert_eq!(output(&mut repl, ":vars"), vec!["x: 1", "y: 9"]);
+        let solved = output(&mut repl, "solve(x) <- x * 2 = y;");
+        assert_eq!(solved, vec!["formula: x*2=y\nx: 4.5 with abs err: 0"]);
+        assert_eq!(output(&mut repl, "x"), vec!["4.5"]);
+        assert_eq!(output(&mut repl, "assert x * 2 == y;"), vec!["assertion passed: assert x * 2 == y (found 9, expected 9 within 0.000000001)"]);
+        assert_eq!(output(&mut repl, "x: 5; x"), vec!["5"]);
+        assert!(output(&mut repl, "assert x == 5;")[0].starts_with("assertion passed"));
+        assert_eq!(output(&mut repl, "w + 1"), vec!["error: Unknown variable: w"]);
+        assert_eq!(output(&mut repl, ":options"), vec!["seed: 1"]);
+        output(&mut repl, ":clear");
+        assert!(output(&mut repl, ":vars").is_empty());
+    }
+
+    #[test]
+    fn reads_blocks_and_history() {
+        let mut repl = Repl::new(Interpreter::new());
+        assert!(output(&mut repl, "{").is_empty());
+        assert!(repl.is_pending());
+        assert!(output(&mut repl, "a: 2; // {").is_empty());
+        assert!(output(&mut repl, "a * 3; }").contains(&"6".to_string()));
+        assert!(!repl.is_pending());
+        assert_eq!(output(&mut repl, "a ^ 2"), vec!["4"]);
+        assert_eq!(output(&mut repl, ":history"), vec!["0: {\na: 2; \na * 3; }", "1: a ^ 2"]);
+        assert_eq!(output(&mut repl, "!1"), vec!["4"]);
+        assert_eq!(output(&mut repl, "!7"), vec!["error: No history entry: 7"]);
+        output(&mut repl, ":quit");
+        assert!(repl.finished);
+    }
+}
@@ -0,0 +1,241 @@
+use crate::doodlang::interpreter::{Check, Outcome, RunResult};
+use crate::doodlang::json::quote;
+use crate::eq_solver::complex::Complex;
+use std::fmt::Write;
+
+/// How the command line prints a run.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    /// The same lines `RunResult` displays as.
+    Text,
+    /// One JSON object per program, on its own line.
+    Json,
+    /// One row per solution, eigenvalue and assertion, after `CSV_HEADER`.
+    Csv,
+}
+
+impl Format {
+    pub fn parse(name: &str) -> Option<Format> {
+        return match name {
+            "text" => Option::Some(Format::Text),
+            "json" => Option::Some(Format::Json),
+            "csv" => Option::Some(Format::Csv),
+            _ => Option::None,
+        };
+    }
+}
+
+pub const CSV_HEADER: &str = "program,scope,kind,name,value,imaginary,residual,converged";
+
+/// Formats the result of running `program`. Diagnostics are only part of the JSON output.
+pub fn report(format: Format, program: &str, result: &RunResult) -> String {
+    return match format {
+        Format::Text => result.to_string(),
+        Format::Json => json(program, result) + "\n",
+        Format::Csv => csv(program, result),
+    };
+}
+
+fn json(program: &str, result: &RunResult) -> String {
+    let mut out = String::new();
+    write!(out, "{{\"program\":{},\"seed\":{},\"scopes\":[", quote(program), result.seed).unwrap();
+    for (i, scope) in result.scopes.iter().enumerate() {
+        if i > 0 {
+            out += ",";
+        }
+        let variables: Vec<String> = scope.variables.iter().map(|(name, value)| format!("{}:{}", quote(name), json_number(*value))).collect();
+        let outcomes: Vec<String> = scope.outcomes.iter().map(json_outcome).collect();
+        write!(out, "{{\"variables\":{{{}}},\"outcomes\":[{}]}}", variables.join(","), outcomes.join(",")).unwrap();
+    }
+    out += "],\"diagnostics\":[";
+    let diagnostics: Vec<String> = result
+        .diagnostics
+        .iter()
+        .map(|diagnostic| {
+            format!(
+                "{{\"scope\":{},\"statement\":{},\"message\":{}}}",
+                diagnostic.scope,
+                quote(&diagnostic.statement),
+                quote(&diagnostic.message)
+            )
+        })
+        .collect();
+    out += &diagnostics.join(",");
+    out += "]}";
+    return out;
+}
+
+fn json_outcome(outcome: &Outcome) -> String {
+    return match outcome {
+        Outcome::Seed(seed) => format!("{{\"kind\":\"seed\",\"seed\":{}}}", seed),
+        Outcome::Solution(solution) => format!(
+            "{{\"kind\":\"solution\",\"variable\":{},\"formula\":{},\"value\":{},\"residual\":{},\"converged\":{},\"condition\":{}}}",
+            quote(&solution.variable),
+            quote(&solution.formula),
+            json_number(solution.value),
+            json_number(solution.residual),
+            solution.converged,
+            json_number(solution.condition)
+        ),
+        Outcome::Eigen(eigen) => {
+            let values: Vec<String> = eigen.values.iter().map(json_complex).collect();
+            let vectors: Vec<String> = eigen
+                .vectors
+                .iter()
+                .map(|vector| format!("[{}]", vector.iter().map(json_complex).collect::<Vec<String>>().join(",")))
+                .collect();
+            format!(
+                "{{\"kind\":\"eigen\",\"name\":{},\"matrix\":{},\"values\":[{}],\"vectors\":[{}]}}",
+                quote(&eigen.name),
+                quote(&eigen.matrix),
+                values.join(","),
+                vectors.join(",")
+            )
+        }
+        Outcome::Assertion(assertion) => {
+            let check = match assertion.check {
+                Check::Equal { actual, expected, tolerance } => format!(
+                    "\"actual\":{},\"expected\":{},\"tolerance\":{}",
+                    json_number(actual),
+                    json_number(expected),
+                    json_number(tolerance)
+                ),
+                Check::Residual { residual, bound } => format!("\"residual\":{},\"bound\":{}", json_number(residual), json_number(bound)),
+            };
+            format!(
+                "{{\"kind\":\"assertion\",\"line\":{},\"statement\":{},{},\"passed\":{}}}",
+                assertion.line,
+                quote(&assertion.statement),
+                check,
+                assertion.passed
+            )
+        }
+    };
+}
+
+fn json_complex(value: &Complex) -> String {
+    return format!("{{\"re\":{},\"im\":{}}}", json_number(value.re), json_number(value.im));
+}
+
+/// JSON has no NaN or infinity, so those become `null`.
+fn json_number(value: f64) -> String {
+    if value.is_finite() {
+        return value.to_string();
+    }
+    return "null".to_string();
+}
+
+fn csv(program: &str, result: &RunResult) -> String {
+    let program = csv_field(program);
+    let mut out = String::new();
+    for (i, scope) in result.scopes.iter().enumerate() {
+        for outcome in &scope.outcomes {
+            match outcome {
+                Outcome::Seed(_) => {}
+                Outcome::Solution(solution) => {
+                    writeln!(
+                        out,
+                        "{},{},solution,{},{},,{},{}",
+                        program,
+                        i,
+                        csv_field(&solution.variable),
+                        solution.value,
+                        solution.residual,
+                        solution.converged
+                    )
+                    .unwrap();
+                }
+                Outcome::Eigen(eigen) => {
+                    for (j, value) in eigen.values.iter().enumerate() {
+                        let name = csv_field(&format!("{}{}", eigen.name, j));
+                        writeln!(out, "{},{},eigenvalue,{},{},{},,", program, i, name, value.re, value.im).unwrap();
+                    }
+                }
+                Outcome::Assertion(assertion) => {
+                    let (value, residual) = match assertion.check {
+                        Check::Equal { actual, .. } => (actual.to_string(), String::new()),
+                        Check::Residual { residual, .. } => (String::new(), residual.to_string()),
+                    };
+                    let name = csv_field(&assertion.statement);
+                    writeln!(out, "{},{},assertion,{},{},,{},{}", program, i, name, value, residual, assertion.passed).unwrap();
+                }
+            }
+        }
+    }
+    return out;
+}
+
+/// Quotes a field that holds a separator or a quote.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        return format!("\"{}\"", value.replace('"', "\"\""));
+    }
+    return value.to_string();
+}
+
+/// Compares two reports line by line. Unchanged lines start with two spaces, removed ones with
+/// `- ` and added ones with `+ `.
+pub fn diff(previous: &str, current: &str) -> String {
+    let old: Vec<&str> = previous.lines().collect();
+    let new: Vec<&str> = current.lines().collect();
+    // common[i][j] is the length of the longest common subsequence of old[i..] and new[j..].
+    let mut common = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i] == new[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+    let mut out = String::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            writeln!(out, "  {}", old[i]).unwrap();
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || common[i + 1][j] >= common[i][j + 1]) {
+            writeln!(out, "- {}", old[i]).unwrap();
+            i += 1;
+        } else {
+            writeln!(out, "+ {}", new[j]).unwrap();
+            j += 1;
+        }
+    }
+    return out;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::doodlang::interpreter::Interpreter;
+    use crate::doodlang::report::{diff, report, Format};
+
+    #[test]
+    fn formats_runs() {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_seed(3);
+        let result = interpreter.run("{ x: 0; solve(x) <- x * 2 = 1; eigen(l) <- [[2, 0], [0, 1]]; solve(y) <- y = 1; }");
+        let json = report(Format::Json, "a \"b\".dood", &result);
+        assert!(json.starts_with("{\"program\":\"a \\\"b\\\".dood\",\"seed\":3,\"scopes\":[{\"variables\":{\"x\":0},"));
+        assert!(json.contains("{\"kind\":\"solution\",\"variable\":\"x\",\"formula\":\"x*2=1\",\"value\":0.5,\"residual\":0,\"converged\":true,\"condition\":0.5}"));
+        assert!(json.contains("\"values\":[{\"re\":1,\"im\":0},{\"re\":2,\"im\":0}]"));
+        assert!(json.ends_with("\"diagnostics\":[{\"scope\":0,\"statement\":\"solve(y)<-y=1\",\"message\":\"Unknown variable: y\"}]}\n"));
+        let csv = report(Format::Csv, "a,b", &result);
+        assert_eq!(csv, "\"a,b\",0,solution,x,0.5,,0,true\n\"a,b\",0,eigenvalue,l0,1,0,,\n\"a,b\",0,eigenvalue,l1,2,0,,\n");
+        assert_eq!(report(Format::Text, "a", &result), result.to_string());
+        assert_eq!(Format::parse("xml"), Option::None);
+    }
+
+    #[test]
+    fn diffs_reports() {
+        let previous = "seed: 1\nx: 1 with abs err: 0\ny: 2 with abs err: 0\n";
+        let current = "seed: 1\nx: 1.5 with abs err: 0\ny: 2 with abs err: 0\nz: 3 with abs err: 0\n";
+        assert_eq!(
+            diff(previous, current),
+            "  seed: 1\n- x: 1 with abs err: 0\n+ x: 1.5 with abs err: 0\n  y: 2 with abs err: 0\n+ z: 3 with abs err: 0\n"
+        );
+        assert_eq!(diff(current, current).lines().filter(|line| !line.starts_with("  ")).count(), 0);
+    }
+}
@@ -1,8 +1,12 @@
 use crate::eq_solver::function::{Function};
-use crate::eq_solver::matrix::Matrix;
+use crate::eq_solver::matrix::{Matrix, MatrixError};
 use crate::doodlang::parser::FunctionManager;
-use crate::print_vec;
-use rand::{thread_rng, Rng};
+use rand::Rng;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+/// Up to this degree the normal equations are well enough conditioned to solve with Cholesky.
+const CHOLESKY_MAX_DEGREE: usize = 3;
 
 pub struct PolyApprox {
     degree: usize,
@@ -15,16 +19,17 @@ impl PolyApprox {
         }
     }
 
-    pub fn approx1d(&mut self, function_manager: &mut Box<FunctionManager>, function: &mut Box<dyn Function>, parameter: &str, range: (f64, f64), num_samples: usize) -> Matrix {
+    pub fn approx1d(&self, function_manager: &FunctionManager, function: &dyn Function, parameter: &str, range: (f64, f64), num_samples: usize) -> Result<Matrix, MatrixError> {
         let diff = range.1 - range.0;
         let mut in_v = Vec::with_capacity(num_samples);
         let mut out_v = Vec::with_capacity(num_samples);
         let id = function_manager.ids.get(parameter).unwrap();
+        let mut variables = function_manager.variables.clone();
         for i in 0..num_samples {
             let val = range.0 + (i as f64) * diff / ((num_samples - 1) as f64);
-            function_manager.variables[*id] = val;
+            variables[*id] = val;
             in_v.push(val);
-            out_v.push(function.output(function_manager));
+            out_v.push(function.output(&variables));
         }
         let mut vandermonde_matrix = Matrix::zeros(self.degree + 1, num_samples);
         for i in 0..vandermonde_matrix.columns {
@@ -37,8 +42,21 @@ impl PolyApprox {
             columns: 1,
             rows: num_samples,
         };
-        let transpose = &vandermonde_matrix.transpose();
-        return ((transpose * vandermonde_matrix).invert() * transpose).transpose() * out;
+        if self.degree <= CHOLESKY_MAX_DEGREE {
+            let columns = vandermonde_matrix.columns;
+            let mut normal = Matrix::zeros(columns, columns);
+            let mut projected = Matrix::zeros(1, columns);
+            for i in 0..columns {
+                for j in 0..columns {
+                    normal[(i, j)] = (0..num_samples).map(|k| vandermonde_matrix[(i, k)] * vandermonde_matrix[(j, k)]).sum();
+                }
+                projected[(0, i)] = (0..num_samples).map(|k| vandermonde_matrix[(i, k)] * out[(0, k)]).sum();
+            }
+            if let Ok(cholesky) = normal.cholesky() {
+                return Ok(cholesky.solve(&projected));
+            }
+        }
+        return vandermonde_matrix.least_squares(&out);
     }
 }
 
@@ -48,6 +66,7 @@ pub struct SecantSolver {
     attempt_num: usize,
     iter: usize,
     margin: f64,
+    threads: usize,
 }
 
 impl SecantSolver {
@@ -58,36 +77,84 @@ impl SecantSolver {
             attempt_num,
             iter,
             margin,
+            threads: thread::available_parallelism().map_or(1, |threads| threads.get()),
         }
     }
 
-    pub fn solve_for_param(&mut self, func: &mut Box<dyn Function>, func_manager: &mut Box<FunctionManager>, param: &str) -> f64 {
-        let id = func_manager.ids.get(param).unwrap();
-        let start_val = func_manager.variables[*id];
+    pub fn solve_for_param<R: Rng>(&self, rng: &mut R, func: &dyn Function, func_manager: &FunctionManager, param: &str) -> f64 {
+        let id = *func_manager.ids.get(param).unwrap();
+        let start_val = func_manager.variables[id];
+        let starts: Vec<f64> = (0..self.attempt_num)
+            .map(|_| self.range[0] + rng.gen::<f64>() * (self.range[1] - self.range[0]))
+            .collect();
+        let next_attempt = AtomicUsize::new(0);
+        let solved_attempt = AtomicUsize::new(usize::MAX);
+        let attempts: Vec<(usize, f64, f64)> = thread::scope(|scope| {
+            let workers: Vec<_> = (0..self.threads.min(self.attempt_num))
+                .map(|_| scope.spawn(|| {
+                    let mut variables = func_manager.variables.clone();
+                    let mut attempts = Vec::new();
+                    loop {
+                        let attempt = next_attempt.fetch_add(1, Ordering::Relaxed);
+                        if attempt >= starts.len() || attempt > solved_attempt.load(Ordering::Relaxed) {
+                            break;
+                        }
+                        let result = self.attempt(func, &mut variables, id, starts[attempt], attempt, &solved_attempt);
+                        if let Some((err, point)) = result {
+                            attempts.push((attempt, err, point));
+                        }
+                    }
+                    attempts
+                }))
+                .collect();
+            workers.into_iter().flat_map(|worker| worker.join().unwrap()).collect()
+        });
+        let solved = solved_attempt.load(Ordering::Relaxed);
         let mut lowest_error = f64::INFINITY;
         let mut zero_val = Option::None;
-        for _i in 0..self.attempt_num {
-            let mut point = self.range[0] + thread_rng().gen::<f64>() * (self.range[1] - self.range[0]);
-            for _j in 0..self.iter {
-                func_manager.variables[*id] = point;
-                let current_val = func.output(&func_manager);
-                let err = current_val.abs();
-                if err < lowest_error {
-                    lowest_error = err;
-                    zero_val = Option::Some(point);
-                    if err <= self.margin {
-                        return zero_val.unwrap();
-                    }
+        let mut first_attempt = usize::MAX;
+        for (attempt, err, point) in attempts {
+            if attempt == solved {
+                return point;
+            }
+            if err < lowest_error || (err == lowest_error && attempt < first_attempt) {
+                lowest_error = err;
+                zero_val = Option::Some(point);
+                first_attempt = attempt;
+            }
+        }
+        return zero_val.unwrap_or(start_val);
+    }
+
+    /// Runs one restart from `start`, returning its lowest error and where it was found.
+    /// Gives up early once an earlier restart has reached the margin.
+    fn attempt(&self, func: &dyn Function, variables: &mut [f64], id: usize, start: f64, attempt: usize, solved_attempt: &AtomicUsize) -> Option<(f64, f64)> {
+        let mut output = func.evaluator();
+        let mut point = start;
+        let mut lowest_error = f64::INFINITY;
+        let mut zero_val = Option::None;
+        for _j in 0..self.iter {
+            if solved_attempt.load(Ordering::Relaxed) < attempt {
+                return Option::None;
+            }
+            variables[id] = point;
+            let current_val = output(variables);
+            let err = current_val.abs();
+            if err < lowest_error {
+                lowest_error = err;
+                zero_val = Option::Some(point);
+                if err <= self.margin {
+                    solved_attempt.fetch_min(attempt, Ordering::Relaxed);
+                    break;
                 }
-                func_manager.variables[*id] = point - self.derivative_step;
-                let prev = func.output(func_manager);
-                func_manager.variables[*id] = point + self.derivative_step;
-                let derivative = (func.output(func_manager) - prev) / (self.derivative_step * 2f64);
-                point -= current_val / derivative;
             }
+            variables[id] = point - self.derivative_step;
+            let prev = output(variables);
+            variables[id] = point + self.derivative_step;
+            let derivative = (output(variables) - prev) / (self.derivative_step * 2f64);
+            point -= current_val / derivative;
         }
-        func_manager.variables[*id] = start_val;
-        return zero_val.unwrap();
+        return zero_val.map(|point| (lowest_error, point));
     }
 }
 
@@ -114,14 +181,16 @@ impl QuadFind {
         }
     }
 
-    pub fn solve_for_param(&mut self, func: &mut Box<dyn Function>, func_manager: &mut Box<FunctionManager>, param: &str) -> f64 {
+    pub fn solve_for_param<R: Rng>(&self, rng: &mut R, func: &dyn Function, func_manager: &FunctionManager, param: &str) -> f64 {
         let id = *func_manager.ids.get(param).unwrap();
+        let mut variables = func_manager.variables.clone();
         let mut lowest_error = f64::INFINITY;
         let mut zero_val = Option::None;
         for _i in 0..self.attempt_num {
-            let mut point = self.range[0] + thread_rng().gen::<f64>() * (self.range[1] - self.range[0]);
+            let mut point = self.range[0] + rng.gen::<f64>() * (self.range[1] - self.range[0]);
             for _j in 0..self.iter {
-                let current_val = func.output(func_manager);
+                variables[id] = point;
+                let current_val = func.output(&variables);
                 let err = current_val.abs();
                 if err < lowest_error {
                     lowest_error = err;
@@ -130,42 +199,147 @@ impl QuadFind {
                         return zero_val.unwrap();
                     }
                 }
-                let coeffs = self.poly.approx1d(
+                let coeffs = match self.poly.approx1d(
                     func_manager,
                     func,
                     param,
                     (point + self.regress_range, point - self.regress_range),
                     10,
-                );
+                ) {
+                    Ok(coeffs) => coeffs,
+                    Err(_) => break,
+                };
                 let a = coeffs[(0, 0)];
-                let b = coeffs[(1, 0)];
-                let c = coeffs[(2, 0)];
+                let b = coeffs[(0, 1)];
+                let c = coeffs[(0, 2)];
                 let delta = -(b.powi(2) - 4f64 * a * c);
                 if delta < 0f64 {
                     if a < 0f64 {
                         let point0 = point - self.regress_range;
                         let point1 = point + self.regress_range;
-                        func_manager.variables[id] = point0;
-                        let val0 = func.output(func_manager);
-                        func_manager.variables[id] = point1;
-                        let val1 = func.output(func_manager);
+                        variables[id] = point0;
+                        let val0 = func.output(&variables);
+                        variables[id] = point1;
+                        let val1 = func.output(&variables);
                         point = if val0 < val1 { point0 } else { point1 };
                     } else {
                         point = -b / (2f64 * a);
-                        func_manager.variables[id] = point;
+                        variables[id] = point;
                     }
                 } else {
                     let sqrt_delta = delta.sqrt();
-                    let root0 = (-coeffs[(1, 0)] + sqrt_delta) / (2f64 * a);
-                    let root1 = (-coeffs[(1, 0)] - sqrt_delta) / (2f64 * a);
-                    func_manager.variables[id] = root0;
-                    let val0 = func.output(func_manager);
-                    func_manager.variables[id] = root1;
-                    let val1 = func.output(func_manager);
+                    let root0 = (-coeffs[(0, 1)] + sqrt_delta) / (2f64 * a);
+                    let root1 = (-coeffs[(0, 1)] - sqrt_delta) / (2f64 * a);
+                    variables[id] = root0;
+                    let val0 = func.output(&variables);
+                    variables[id] = root1;
+                    let val1 = func.output(&variables);
                     point = if val0 < val1 { root0 } else { root1 };
                 }
             }
         }
         return zero_val.unwrap();
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use crate::doodlang::parser::{smart_generate_tree, FunctionManager};
+    use crate::eq_solver::approx::{PolyApprox, QuadFind, SecantSolver};
+    use crate::eq_solver::compile::Program;
+    use crate::eq_solver::function::Function;
+    use crate::eq_solver::matrix::Matrix;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn fit(formula: &str, degree: usize, range: (f64, f64), num_samples: usize) -> Matrix {
+        let variables = [("x", 0f64)];
+        let mut function_manager = FunctionManager::new(&variables);
+        let function = function_manager.generate_func(formula, &smart_generate_tree(formula)).unwrap();
+        return PolyApprox::new(degree).approx1d(&function_manager, function.as_ref(), "x", range, num_samples).unwrap();
+    }
+
+    #[test]
+    fn recovers_degree_10_polynomial() {
+        let coeffs = fit("1+2*x+3*x^2+4*x^3+5*x^4+6*x^5+7*x^6+8*x^7+9*x^8+10*x^9+11*x^10", 10, (-1.0, 1.0), 50);
+        let expected = [1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0, 10.0, 11.0];
+        for (i, val) in expected.iter().enumerate() {
+            assert!((coeffs[(0, i)] - val).abs() < 1e-8, "coefficient {}: {}", i, coeffs[(0, i)]);
+        }
+    }
+
+    #[test]
+    fn recovers_degree_12_polynomial_away_from_origin() {
+        let coeffs = fit("2+x^6-x^12", 12, (0.0, 2.0), 60);
+        for i in 0..=12 {
+            let expected = match i {
+                0 => 2.0,
+                6 => 1.0,
+                12 => -1.0,
+                _ => 0.0,
+            };
+            assert!((coeffs[(0, i)] - expected).abs() < 1e-5, "coefficient {}: {}", i, coeffs[(0, i)]);
+        }
+    }
+
+    #[test]
+    fn approximates_smooth_function_at_degree_14() {
+        let coeffs = fit("sin(3*x)", 14, (-2.0, 2.0), 80);
+        for i in 0..=40 {
+            let x = -2.0 + i as f64 * 0.1;
+            let approx: f64 = (0..=14).map(|k| coeffs[(0, k)] * x.powi(k as i32)).sum();
+            assert!((approx - (3.0 * x).sin()).abs() < 1e-4, "x = {}: {}", x, approx);
+        }
+    }
+
+    #[test]
+    fn recovers_quadratic_through_normal_equations() {
+        let coeffs = fit("3+2*x+x^2", 2, (1.0, 7.0), 10);
+        for (i, val) in [3.0, 2.0, 1.0].iter().enumerate() {
+            assert!((coeffs[(0, i)] - val).abs() < 1e-9, "coefficient {}: {}", i, coeffs[(0, i)]);
+        }
+    }
+
+    #[test]
+    fn seeded_solves_repeat_exactly() {
+        let variables = [("x", 0f64), ("y", 3f64)];
+        let mut function_manager = FunctionManager::new(&variables);
+        // Several roots and a minimum without a root, so the restarts matter.
+        let formulas = ["x^3-y*x-1", "sin(3*x)-0.5", "x^2+y"];
+        let secant = SecantSolver::new([-5f64, 5f64], 0.1f64, 20, 200, 1e-12);
+        let quad = QuadFind::new([-5f64, 5f64], 0.5f64, 5, 10, 1e-12);
+        for formula in formulas {
+            let function = function_manager.generate_func(formula, &smart_generate_tree(formula)).unwrap();
+            let solve = |seed: u64| {
+                let secant = secant.solve_for_param(&mut StdRng::seed_from_u64(seed), function.as_ref(), &function_manager, "x");
+                let quad = quad.solve_for_param(&mut StdRng::seed_from_u64(seed), function.as_ref(), &function_manager, "x");
+                return (secant.to_bits(), quad.to_bits());
+            };
+            for seed in [0, 5, u64::MAX] {
+                assert_eq!(solve(seed), solve(seed), "{} with seed {}", formula, seed);
+            }
+        }
+    }
+
+    #[test]
+    fn parallel_restarts_match_any_thread_count() {
+        let variables = [("x", 0f64), ("y", 3f64)];
+        let mut function_manager = FunctionManager::new(&variables);
+        let formulas = ["x^3-y*x-1", "sin(3*x)-0.5", "x^2+y", "x^2-y"];
+        for formula in formulas {
+            let tree = function_manager.generate_func(formula, &smart_generate_tree(formula)).unwrap();
+            let program = Program::compile(tree.as_ref());
+            let solve = |function: &dyn Function, threads: usize| {
+                let mut solver = SecantSolver::new([-5f64, 5f64], 0.1f64, 50, 500, 1e-12);
+                solver.threads = threads;
+                return solver.solve_for_param(&mut StdRng::seed_from_u64(3), function, &function_manager, "x").to_bits();
+            };
+            let expected = solve(tree.as_ref(), 1);
+            for threads in [1, 2, 3, 8, 64] {
+                for _run in 0..3 {
+                    assert_eq!(solve(tree.as_ref(), threads), expected, "{} on {} threads", formula, threads);
+                    assert_eq!(solve(&program, threads), expected, "{} compiled, on {} threads", formula, threads);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,605 @@
+use crate::eq_solver::expr::{ExprArena, ExprId};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, LazyLock, OnceLock};
+
+pub type Evaluate = dyn Fn(&[f64]) -> f64 + Send + Sync;
+pub type Derivative = dyn Fn(&mut ExprArena, &[ExprId], usize) -> ExprId + Send + Sync;
+/// Partial derivative of a native function with respect to the argument at the given index.
+pub type NativeDerivative = dyn Fn(&[f64], usize) -> f64 + Send + Sync;
+
+/// A function callable from formulas as `name(arguments)`.
+pub struct Builtin {
+    pub name: String,
+    pub arity: usize,
+    pub evaluate: Box<Evaluate>,
+    /// Partial derivative with respect to the argument at the given index, built from the arguments.
+    pub derivative: Box<Derivative>,
+}
+
+impl fmt::Debug for Builtin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return write!(f, "{}/{}", self.name, self.arity);
+    }
+}
+
+/// Builtins are compared by identity, every one of them is a distinct function.
+impl PartialEq for Builtin {
+    fn eq(&self, other: &Self) -> bool {
+        return std::ptr::eq(self, other);
+    }
+}
+
+impl Eq for Builtin {}
+
+/// A function of the math library, which `BUILTINS` turns into a `Builtin`.
+struct Definition {
+    name: &'static str,
+    arity: usize,
+    evaluate: &'static Evaluate,
+    derivative: &'static Derivative,
+}
+
+/// The math library, shared by every registry.
+pub static BUILTINS: LazyLock<Vec<Arc<Builtin>>> = LazyLock::new(|| {
+    return LIBRARY
+        .iter()
+        .map(|definition| {
+            Arc::new(Builtin {
+                name: definition.name.to_string(),
+                arity: definition.arity,
+                evaluate: Box::new(definition.evaluate),
+                derivative: Box::new(definition.derivative),
+            })
+        })
+        .collect();
+});
+
+static LIBRARY: &[Definition] = &[
+    Definition {
+        name: "ln",
+        arity: 1,
+        evaluate: &|x| x[0].ln(),
+        derivative: &|arena, x, _| reciprocal(arena, x[0]),
+    },
+    Definition {
+        name: "log",
+        arity: 1,
+        evaluate: &|x| x[0].ln(),
+        derivative: &|arena, x, _| reciprocal(arena, x[0]),
+    },
+    Definition {
+        name: "log",
+        arity: 2,
+        evaluate: &|x| x[1].log(x[0]),
+        derivative: &|arena, x, argument| {
+            // log_b(x) = ln(x) / ln(b)
+            let ln_base = arena.call(builtin("ln", 1), &[x[0]]);
+            if argument == 1 {
+                let denominator = arena.mul(x[1], ln_base);
+                return reciprocal(arena, denominator);
+            }
+            let ln_input = arena.call(builtin("ln", 1), &[x[1]]);
+            let two = arena.constant(2f64);
+            let squared = arena.pow(ln_base, two);
+            let denominator = arena.mul(x[0], squared);
+            let quotient = arena.div(ln_input, denominator);
+            return negate(arena, quotient);
+        },
+    },
+    Definition {
+        name: "log2",
+        arity: 1,
+        evaluate: &|x| x[0].log2(),
+        derivative: &|arena, x, _| {
+            let ln_2 = arena.constant(std::f64::consts::LN_2);
+            let denominator = arena.mul(x[0], ln_2);
+            return reciprocal(arena, denominator);
+        },
+    },
+    Definition {
+        name: "log10",
+        arity: 1,
+        evaluate: &|x| x[0].log10(),
+        derivative: &|arena, x, _| {
+            let ln_10 = arena.constant(std::f64::consts::LN_10);
+            let denominator = arena.mul(x[0], ln_10);
+            return reciprocal(arena, denominator);
+        },
+    },
+    Definition {
+        name: "exp",
+        arity: 1,
+        evaluate: &|x| x[0].exp(),
+        derivative: &|arena, x, _| arena.call(builtin("exp", 1), x),
+    },
+    Definition {
+        name: "sqrt",
+        arity: 1,
+        evaluate: &|x| x[0].sqrt(),
+        derivative: &|arena, x, _| {
+            let sqrt = arena.call(builtin("sqrt", 1), x);
+            let two = arena.constant(2f64);
+            let denominator = arena.mul(two, sqrt);
+            return reciprocal(arena, denominator);
+        },
+    },
+    Definition {
+        name: "cbrt",
+        arity: 1,
+        evaluate: &|x| x[0].cbrt(),
+        derivative: &|arena, x, _| {
+            let cbrt = arena.call(builtin("cbrt", 1), x);
+            let two = arena.constant(2f64);
+            let squared = arena.pow(cbrt, two);
+            let three = arena.constant(3f64);
+            let denominator = arena.mul(three, squared);
+            return reciprocal(arena, denominator);
+        },
+    },
+    Definition {
+        name: "abs",
+        arity: 1,
+        evaluate: &|x| x[0].abs(),
+        derivative: &|arena, x, _| arena.call(builtin("sign", 1), x),
+    },
+    Definition {
+        name: "sign",
+        arity: 1,
+        evaluate: &|x| if x[0] == 0f64 { 0f64 } else { x[0].signum() },
+        derivative: &|arena, _, _| arena.constant(0f64),
+    },
+    Definition {
+        name: "floor",
+        arity: 1,
+        evaluate: &|x| x[0].floor(),
+        derivative: &|arena, _, _| arena.constant(0f64),
+    },
+    Definition {
+        name: "ceil",
+        arity: 1,
+        evaluate: &|x| x[0].ceil(),
+        derivative: &|arena, _, _| arena.constant(0f64),
+    },
+    Definition {
+        name: "round",
+        arity: 1,
+        evaluate: &|x| x[0].round(),
+        derivative: &|arena, _, _| arena.constant(0f64),
+    },
+    Definition {
+        name: "sin",
+        arity: 1,
+        evaluate: &|x| x[0].sin(),
+        derivative: &|arena, x, _| arena.call(builtin("cos", 1), x),
+    },
+    Definition {
+        name: "cos",
+        arity: 1,
+        evaluate: &|x| x[0].cos(),
+        derivative: &|arena, x, _| {
+            let sin = arena.call(builtin("sin", 1), x);
+            return negate(arena, sin);
+        },
+    },
+    Definition {
+        name: "tan",
+        arity: 1,
+        evaluate: &|x| x[0].tan(),
+        derivative: &|arena, x, _| {
+            let cos = arena.call(builtin("cos", 1), x);
+            let two = arena.constant(2f64);
+            let squared = arena.pow(cos, two);
+            return reciprocal(arena, squared);
+        },
+    },
+    Definition {
+        name: "asin",
+        arity: 1,
+        evaluate: &|x| x[0].asin(),
+        derivative: &|arena, x, _| {
+            let root = one_minus_square_root(arena, x[0]);
+            return reciprocal(arena, root);
+        },
+    },
+    Definition {
+        name: "acos",
+        arity: 1,
+        evaluate: &|x| x[0].acos(),
+        derivative: &|arena, x, _| {
+            let root = one_minus_square_root(arena, x[0]);
+            let inverse = reciprocal(arena, root);
+            return negate(arena, inverse);
+        },
+    },
+    Definition {
+        name: "atan",
+        arity: 1,
+        evaluate: &|x| x[0].atan(),
+        derivative: &|arena, x, _| {
+            let one = arena.constant(1f64);
+            let square = square(arena, x[0]);
+            let denominator = arena.sum(one, square);
+            return reciprocal(arena, denominator);
+        },
+    },
+    Definition {
+        name: "atan2",
+        arity: 2,
+        evaluate: &|x| x[0].atan2(x[1]),
+        derivative: &|arena, x, argument| {
+            // atan2(y, x) changes by (x dy - y dx) / (x^2 + y^2)
+            let (y_square, x_square) = (square(arena, x[0]), square(arena, x[1]));
+            let denominator = arena.sum(y_square, x_square);
+            return if argument == 0 {
+                arena.div(x[1], denominator)
+            } else {
+                let quotient = arena.div(x[0], denominator);
+                negate(arena, quotient)
+            };
+        },
+    },
+    Definition {
+        name: "sinh",
+        arity: 1,
+        evaluate: &|x| x[0].sinh(),
+        derivative: &|arena, x, _| arena.call(builtin("cosh", 1), x),
+    },
+    Definition {
+        name: "cosh",
+        arity: 1,
+        evaluate: &|x| x[0].cosh(),
+        derivative: &|arena, x, _| arena.call(builtin("sinh", 1), x),
+    },
+    Definition {
+        name: "tanh",
+        arity: 1,
+        evaluate: &|x| x[0].tanh(),
+        derivative: &|arena, x, _| {
+            let cosh = arena.call(builtin("cosh", 1), x);
+            let two = arena.constant(2f64);
+            let squared = arena.pow(cosh, two);
+            return reciprocal(arena, squared);
+        },
+    },
+    Definition {
+        name: "asinh",
+        arity: 1,
+        evaluate: &|x| x[0].asinh(),
+        derivative: &|arena, x, _| {
+            let one = arena.constant(1f64);
+            let square = square(arena, x[0]);
+            let sum = arena.sum(square, one);
+            let root = arena.call(builtin("sqrt", 1), &[sum]);
+            return reciprocal(arena, root);
+        },
+    },
+    Definition {
+        name: "acosh",
+        arity: 1,
+        evaluate: &|x| x[0].acosh(),
+        derivative: &|arena, x, _| {
+            let one = arena.constant(1f64);
+            let square = square(arena, x[0]);
+            let difference = arena.sub(square, one);
+            let root = arena.call(builtin("sqrt", 1), &[difference]);
+            return reciprocal(arena, root);
+        },
+    },
+    Definition {
+        name: "atanh",
+        arity: 1,
+        evaluate: &|x| x[0].atanh(),
+        derivative: &|arena, x, _| {
+            let one = arena.constant(1f64);
+            let square = square(arena, x[0]);
+            let difference = arena.sub(one, square);
+            return reciprocal(arena, difference);
+        },
+    },
+    Definition {
+        name: "min",
+        arity: 2,
+        evaluate: &|x| x[0].min(x[1]),
+        // Half of the derivative goes to each argument where they are equal.
+        derivative: &|arena, x, argument| {
+            let difference = arena.sub(x[1 - argument], x[argument]);
+            return step(arena, difference);
+        },
+    },
+    Definition {
+        name: "max",
+        arity: 2,
+        evaluate: &|x| x[0].max(x[1]),
+        derivative: &|arena, x, argument| {
+            let difference = arena.sub(x[argument], x[1 - argument]);
+            return step(arena, difference);
+        },
+    },
+    Definition {
+        name: "hypot",
+        arity: 2,
+        evaluate: &|x| x[0].hypot(x[1]),
+        derivative: &|arena, x, argument| {
+            let hypot = arena.call(builtin("hypot", 2), x);
+            return arena.div(x[argument], hypot);
+        },
+    },
+];
+
+/// Looks up a builtin that is known to exist.
+pub fn builtin(name: &str, arity: usize) -> &'static Arc<Builtin> {
+    return BUILTINS
+        .iter()
+        .find(|builtin| builtin.name == name && builtin.arity == arity)
+        .unwrap();
+}
+
+/// The functions formulas can call, looked up by name and number of arguments.
+///
+/// Registered functions belong to the registry and its clones, and are dropped with the last of
+/// them and of the expressions and programs calling them.
+#[derive(Clone)]
+pub struct FunctionRegistry {
+    functions: HashMap<String, Vec<Arc<Builtin>>>,
+}
+
+impl FunctionRegistry {
+    /// A registry of every builtin.
+    pub fn new() -> FunctionRegistry {
+        let mut registry = FunctionRegistry {
+            functions: HashMap::new(),
+        };
+        for builtin in BUILTINS.iter() {
+            registry.add(Arc::clone(builtin));
+        }
+        return registry;
+    }
+
+    /// Registers a native function, estimating its derivatives with central differences.
+    pub fn register<F>(&mut self, name: &str, arity: usize, evaluate: F) -> Arc<Builtin>
+    where
+        F: Fn(&[f64]) -> f64 + Send + Sync + 'static,
+    {
+        let evaluate: Arc<Evaluate> = Arc::new(evaluate);
+        let estimated = Arc::clone(&evaluate);
+        let derivative = move |x: &[f64], argument| central_difference(&*estimated, x, argument);
+        return self.register_native(name, arity, evaluate, Arc::new(derivative));
+    }
+
+    /// Registers a native function with `derivative(x, i)` giving its partial derivative
+    /// with respect to argument `i`.
+    pub fn register_with_derivative<F, D>(&mut self, name: &str, arity: usize, evaluate: F, derivative: D) -> Arc<Builtin>
+    where
+        F: Fn(&[f64]) -> f64 + Send + Sync + 'static,
+        D: Fn(&[f64], usize) -> f64 + Send + Sync + 'static,
+    {
+        return self.register_native(name, arity, Arc::new(evaluate), Arc::new(derivative));
+    }
+
+    fn register_native(&mut self, name: &str, arity: usize, evaluate: Arc<Evaluate>, derivative: Arc<NativeDerivative>) -> Arc<Builtin> {
+        let partials = (0..arity)
+            .map(|argument| {
+                let derivative = Arc::clone(&derivative);
+                let evaluate: Arc<Evaluate> = Arc::new(move |x| derivative(x, argument));
+                native(format!("{}_d{}", name, argument), arity, evaluate)
+            })
+            .collect::<Vec<Arc<Builtin>>>();
+        let function = Arc::new(Builtin {
+            name: name.to_string(),
+            arity,
+            evaluate: Box::new(move |x: &[f64]| evaluate(x)),
+            derivative: Box::new(move |arena: &mut ExprArena, x: &[ExprId], argument| {
+                arena.call(&partials[argument], x)
+            }),
+        });
+        self.add(Arc::clone(&function));
+        return function;
+    }
+
+    /// Adds `function`, replacing any function with the same name and arity.
+    pub fn add(&mut self, function: Arc<Builtin>) {
+        let overloads = self.functions.entry(function.name.clone()).or_default();
+        overloads.retain(|overload| overload.arity != function.arity);
+        overloads.push(function);
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        return self.functions.contains_key(name);
+    }
+
+    pub fn get(&self, name: &str, arity: usize) -> Option<&Arc<Builtin>> {
+        return self.functions.get(name)?.iter().find(|function| function.arity == arity);
+    }
+
+    /// The numbers of arguments `name` can be called with, in ascending order.
+    pub fn arities(&self, name: &str) -> Vec<usize> {
+        let mut arities: Vec<usize> = match self.functions.get(name) {
+            Some(overloads) => overloads.iter().map(|function| function.arity).collect(),
+            None => Vec::new(),
+        };
+        arities.sort();
+        return arities;
+    }
+}
+
+impl Default for FunctionRegistry {
+    fn default() -> Self {
+        return FunctionRegistry::new();
+    }
+}
+
+/// A native function whose derivatives are estimated with central differences, creating the
+/// functions for them the first time they are needed.
+fn native(name: String, arity: usize, evaluate: Arc<Evaluate>) -> Arc<Builtin> {
+    let partials: OnceLock<Vec<Arc<Builtin>>> = OnceLock::new();
+    let estimated = Arc::clone(&evaluate);
+    let prefix = name.clone();
+    return Arc::new(Builtin {
+        name,
+        arity,
+        evaluate: Box::new(move |x: &[f64]| evaluate(x)),
+        derivative: Box::new(move |arena: &mut ExprArena, x: &[ExprId], argument| {
+            let partials = partials.get_or_init(|| {
+                (0..arity)
+                    .map(|i| {
+                        let estimated = Arc::clone(&estimated);
+                        let partial: Arc<Evaluate> = Arc::new(move |x| central_difference(&*estimated, x, i));
+                        native(format!("{}_d{}", prefix, i), arity, partial)
+                    })
+                    .collect()
+            });
+            return arena.call(&partials[argument], x);
+        }),
+    });
+}
+
+/// Estimates the partial derivative of `f` at `x` with respect to the argument at `argument`.
+fn central_difference(f: &Evaluate, x: &[f64], argument: usize) -> f64 {
+    let step = f64::EPSILON.cbrt() * x[argument].abs().max(1f64);
+    let mut shifted = x.to_vec();
+    shifted[argument] = x[argument] + step;
+    let above = f(&shifted);
+    shifted[argument] = x[argument] - step;
+    let below = f(&shifted);
+    return (above - below) / (2f64 * step);
+}
+
+fn reciprocal(arena: &mut ExprArena, x: ExprId) -> ExprId {
+    let one = arena.constant(1f64);
+    return arena.div(one, x);
+}
+
+fn negate(arena: &mut ExprArena, x: ExprId) -> ExprId {
+    let zero = arena.constant(0f64);
+    return arena.sub(zero, x);
+}
+
+fn square(arena: &mut ExprArena, x: ExprId) -> ExprId {
+    let two = arena.constant(2f64);
+    return arena.pow(x, two);
+}
+
+/// `sqrt(1 - x^2)`
+fn one_minus_square_root(arena: &mut ExprArena, x: ExprId) -> ExprId {
+    let one = arena.constant(1f64);
+    let square = square(arena, x);
+    let difference = arena.sub(one, square);
+    return arena.call(builtin("sqrt", 1), &[difference]);
+}
+
+/// `(1 + sign(x)) / 2`, one for positive `x` and zero for negative `x`.
+fn step(arena: &mut ExprArena, x: ExprId) -> ExprId {
+    let sign = arena.call(builtin("sign", 1), &[x]);
+    let one = arena.constant(1f64);
+    let sum = arena.sum(one, sign);
+    let two = arena.constant(2f64);
+    return arena.div(sum, two);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::eq_solver::builtin::{FunctionRegistry, BUILTINS};
+    use std::sync::Arc;
+    use crate::eq_solver::expr::{Expr, ExprArena};
+
+    /// Compares every partial derivative with a central difference at `point`.
+    fn check_derivatives(name: &str, point: &[f64]) {
+        let registry = FunctionRegistry::new();
+        let function = registry.get(name, point.len()).unwrap();
+        let mut arena = ExprArena::new();
+        let arguments: Vec<_> = (0..point.len()).map(|i| arena.add(Expr::Variable(i))).collect();
+        let call = arena.call(function, &arguments);
+        assert_eq!(arena.output(call, point), (function.evaluate)(point));
+        for i in 0..point.len() {
+            let derivative = arena.derivative(call, i);
+            let step = 1e-6;
+            let (mut above, mut below) = (point.to_vec(), point.to_vec());
+            above[i] += step;
+            below[i] -= step;
+            let expected = ((function.evaluate)(&above) - (function.evaluate)(&below)) / (2f64 * step);
+            let actual = arena.output(derivative, point);
+            assert!((actual - expected).abs() < 1e-6 * expected.abs().max(1f64), "{}: {} != {}", name, actual, expected);
+        }
+    }
+
+    #[test]
+    fn derivatives_match_finite_differences() {
+        for builtin in BUILTINS.iter() {
+            let point: &[f64] = match (builtin.name.as_str(), builtin.arity) {
+                ("acosh", _) => &[1.7],
+                ("floor" | "ceil" | "round", _) => &[0.3],
+                ("log", 2) => &[3.0, 1.4],
+                (_, 1) => &[0.4],
+                _ => &[0.7, -1.3],
+            };
+            check_derivatives(&builtin.name, point);
+        }
+    }
+
+    #[test]
+    fn overloads_by_arity() {
+        let registry = FunctionRegistry::new();
+        assert_eq!(registry.arities("log"), vec![1, 2]);
+        assert!(registry.get("atan2", 1).is_none());
+        assert!(registry.get("sec", 1).is_none());
+        assert!(!registry.contains("sec"));
+        assert_eq!((registry.get("log", 2).unwrap().evaluate)(&[2.0, 8.0]), 3.0);
+    }
+
+    #[test]
+    fn host_functions() {
+        let mut registry = FunctionRegistry::new();
+        // A lookup table interpolated linearly, with derivatives estimated numerically.
+        let table = [(0.0, 1.0), (1.0, 3.0), (2.0, 4.0)];
+        let lookup = registry.register("lookup", 1, move |x| {
+            let i = table.iter().rposition(|(at, _)| *at <= x[0]).unwrap_or(0).min(table.len() - 2);
+            let ((x0, y0), (x1, y1)) = (table[i], table[i + 1]);
+            return y0 + (y1 - y0) * (x[0] - x0) / (x1 - x0);
+        });
+        let model = registry.register_with_derivative("model", 3, |x| x[0] * x[1] + x[2], |x, i| match i {
+            0 => x[1],
+            1 => x[0],
+            _ => 1.0,
+        });
+        assert!(registry.get("lookup", 1) == Some(&lookup));
+        let mut arena = ExprArena::new();
+        let x = arena.add(Expr::Variable(0));
+        let y = arena.add(Expr::Variable(1));
+        let table_call = arena.call(&lookup, &[x]);
+        assert_eq!(arena.output(table_call, &[1.5]), 3.5);
+        let slope = arena.derivative(table_call, 0);
+        assert!((arena.output(slope, &[0.5]) - 2.0).abs() < 1e-8);
+        let curvature = arena.derivative(slope, 0);
+        assert!(arena.output(curvature, &[0.5]).abs() < 1e-4);
+        let model_call = arena.call(&model, &[x, y, y]);
+        assert_eq!(arena.output(model_call, &[2.0, 3.0]), 9.0);
+        let derivative = arena.derivative(model_call, 1);
+        assert_eq!(arena.output(derivative, &[2.0, 3.0]), 3.0);
+        let program = arena.compile(model_call);
+        assert_eq!(crate::eq_solver::function::Function::output(&program, &[2.0, 3.0]), 9.0);
+    }
+
+    #[test]
+    fn drops_host_functions_with_their_registry() {
+        let captured = Arc::new(2.0);
+        for _ in 0..3 {
+            let mut registry = FunctionRegistry::new();
+            let scale = Arc::clone(&captured);
+            registry.register("scale", 2, move |x| *scale * x[0] * x[1]);
+            let scale = Arc::clone(&captured);
+            registry.register_with_derivative("shift", 1, move |x| *scale + x[0], |_, _| 1.0);
+            let mut arena = ExprArena::new();
+            let x = arena.add(Expr::Variable(0));
+            let call = arena.call(registry.get("scale", 2).unwrap(), &[x, x]);
+            let shifted = arena.call(registry.get("shift", 1).unwrap(), &[call]);
+            // Second derivatives create the partials of the partials.
+            let first = arena.derivative(shifted, 0);
+            let second = arena.derivative(first, 0);
+            let program = arena.compile(second);
+            assert!((crate::eq_solver::function::Function::output(&program, &[3.0]) - 4.0).abs() < 1e-4);
+            assert!(Arc::strong_count(&captured) > 1);
+        }
+        assert_eq!(Arc::strong_count(&captured), 1);
+    }
+}
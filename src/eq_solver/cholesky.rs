@@ -0,0 +1,112 @@
+use crate::eq_solver::matrix::{Matrix, MatrixError};
+
+/// Cholesky decomposition `A = L * Lᵀ` of a symmetric positive definite matrix.
+///
+/// Only the lower triangle of the matrix is read.
+pub struct CholeskyDecomposition {
+    pub l: Matrix,
+}
+
+impl CholeskyDecomposition {
+    pub fn new(matrix: &Matrix) -> Result<CholeskyDecomposition, MatrixError> {
+        assert_eq!(matrix.rows, matrix.columns);
+        let size = matrix.rows;
+        let mut l = Matrix::zeros(size, size);
+        for j in 0..size {
+            for i in 0..=j {
+                let mut val = matrix[(i, j)];
+                for k in 0..i {
+                    val -= l[(k, j)] * l[(k, i)];
+                }
+                if i < j {
+                    l[(i, j)] = val / l[(i, i)];
+                } else if val > 0f64 {
+                    l[(j, j)] = val.sqrt();
+                } else {
+                    return Err(MatrixError::NotPositiveDefinite);
+                }
+            }
+        }
+        Ok(CholeskyDecomposition {
+            l,
+        })
+    }
+
+    /// Solves `A * x = b` for every column of `b` with one forward and one backward substitution.
+    pub fn solve(&self, b: &Matrix) -> Matrix {
+        let size = self.l.rows;
+        assert_eq!(b.rows, size);
+        let mut x = Matrix::zeros(b.columns, size);
+        for column in 0..b.columns {
+            for j in 0..size {
+                let mut val = b[(column, j)];
+                for k in 0..j {
+                    val -= self.l[(k, j)] * x[(column, k)];
+                }
+                x[(column, j)] = val / self.l[(j, j)];
+            }
+            for j in (0..size).rev() {
+                let mut val = x[(column, j)];
+                for k in j + 1..size {
+                    val -= self.l[(j, k)] * x[(column, k)];
+                }
+                x[(column, j)] = val / self.l[(j, j)];
+            }
+        }
+        return x;
+    }
+
+    /// Logarithm of the determinant, which stays finite where the determinant itself would overflow.
+    pub fn log_determinant(&self) -> f64 {
+        return 2f64 * (0..self.l.rows).map(|i| self.l[(i, i)].ln()).sum::<f64>();
+    }
+
+    pub fn determinant(&self) -> f64 {
+        return self.log_determinant().exp();
+    }
+}
+
+impl Matrix {
+    pub fn cholesky(&self) -> Result<CholeskyDecomposition, MatrixError> {
+        return CholeskyDecomposition::new(self);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::eq_solver::matrix::{Matrix, MatrixError};
+
+    #[test]
+    fn solves_positive_definite_system() {
+        let matrix = Matrix {
+            values: vec![4.0, 12.0, -16.0, 12.0, 37.0, -43.0, -16.0, -43.0, 98.0],
+            rows: 3,
+            columns: 3,
+        };
+        let cholesky = matrix.cholesky().unwrap();
+        for (val, expected) in cholesky.l.values.iter().zip([2.0, 0.0, 0.0, 6.0, 1.0, 0.0, -8.0, 5.0, 3.0]) {
+            assert!((val - expected).abs() < 1e-12);
+        }
+        assert!((cholesky.log_determinant() - 36f64.ln()).abs() < 1e-12);
+        let b = Matrix {
+            values: vec![1.0, 2.0, 3.0],
+            rows: 3,
+            columns: 1,
+        };
+        let x = cholesky.solve(&b);
+        let expected = matrix.solve(&b).unwrap();
+        for (val, expected) in x.values.iter().zip(&expected.values) {
+            assert!((val - expected).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn rejects_indefinite_matrix() {
+        let matrix = Matrix {
+            values: vec![1.0, 2.0, 2.0, 1.0],
+            rows: 2,
+            columns: 2,
+        };
+        assert_eq!(matrix.cholesky().err(), Some(MatrixError::NotPositiveDefinite));
+    }
+}
@@ -0,0 +1,569 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+use crate::eq_solver::builtin::Builtin;
+use crate::eq_solver::expr::{Expr, ExprArena, ExprId};
+use crate::eq_solver::function::{Evaluator, Function};
+
+const SMALL_REGISTERS: usize = 16;
+const REGISTERS: usize = 64;
+/// Calls with at most this many arguments gather them on the stack.
+const CALL_INPUTS: usize = 4;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Operator {
+    Sum,
+    Sub,
+    Mul,
+    Div,
+    Pow,
+    Call(Arc<Builtin>),
+}
+
+impl Operator {
+    pub fn inputs(&self) -> usize {
+        match self {
+            Operator::Call(function) => function.arity,
+            _ => 2,
+        }
+    }
+
+    #[inline(always)]
+    fn apply(&self, left: f64, right: f64) -> f64 {
+        match self {
+            Operator::Sum => left + right,
+            Operator::Sub => left - right,
+            Operator::Mul => left * right,
+            Operator::Div => left / right,
+            Operator::Pow => left.powf(right),
+            Operator::Call(_) => unreachable!(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Instruction {
+    Constant(f64),
+    Variable(usize),
+    Operator(Operator),
+}
+
+/// Which inputs of an operation are the result of the operation just before it.
+///
+/// Evaluation keeps that result in a local instead of reading it back from its register, so
+/// chains of operations do not wait on a store and a load at every step.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Inputs {
+    Registers,
+    PreviousLeft,
+    PreviousRight,
+    PreviousBoth,
+    Call,
+}
+
+/// An operation reading its inputs from registers. Operation `i` writes register
+/// `Program::operations_start() + i`.
+///
+/// Calls read the registers of their arguments from `Program::arguments`, starting at `left`.
+#[derive(Clone, Debug)]
+struct Operation {
+    operator: Operator,
+    inputs: Inputs,
+    left: u32,
+    right: u32,
+}
+
+/// A function compiled into register operations.
+///
+/// Registers hold the variables first, then the constants, then one register per distinct
+/// subexpression, so leaves cost nothing at evaluation time and shared subexpressions are
+/// computed once per evaluation.
+pub struct Program {
+    operations: Vec<Operation>,
+    /// Bit `min(id, 63)` of `dependencies[i]` is set for every variable operation `i` depends on.
+    dependencies: Vec<u64>,
+    arguments: Vec<u32>,
+    constants: Vec<f64>,
+    variable_count: usize,
+    register_count: usize,
+    result: usize,
+    /// The registers of small programs with the constants in place, copied for every evaluation.
+    template: [f64; SMALL_REGISTERS],
+}
+
+impl Program {
+    pub fn compile(function: &dyn Function) -> Program {
+        let mut code = Vec::new();
+        function.compile(&mut code);
+        return Program::from_code(&code);
+    }
+
+    /// Builds a program from postfix instructions, merging identical subexpressions.
+    pub fn from_code(code: &[Instruction]) -> Program {
+        let mut arena = ExprArena::new();
+        let root = arena.add_code(code);
+        return Program::from_arena(&arena, root);
+    }
+
+    pub fn from_arena(arena: &ExprArena, root: ExprId) -> Program {
+        let mut reachable = Vec::new();
+        let mut seen = HashSet::new();
+        let mut pending = vec![root];
+        while let Some(id) = pending.pop() {
+            if seen.insert(id) {
+                reachable.push(id);
+                pending.extend(arena.children(id));
+            }
+        }
+        // Children are always added to the arena before their parents.
+        reachable.sort();
+
+        let mut variable_count = 0;
+        for id in &reachable {
+            if let Expr::Variable(variable) = arena[*id] {
+                variable_count = variable_count.max(variable + 1);
+            }
+        }
+        let mut registers = vec![0usize; arena.len()];
+        let mut dependencies = vec![0u64; arena.len()];
+        let mut constants = Vec::new();
+        let mut pending_operations = Vec::new();
+        for id in &reachable {
+            let index = id.index();
+            match arena[*id] {
+                Expr::Variable(variable) => {
+                    registers[index] = variable;
+                    dependencies[index] = 1u64 << variable.min(63);
+                }
+                Expr::Constant(val) => {
+                    registers[index] = variable_count + constants.len();
+                    constants.push(val);
+                }
+                _ => {
+                    dependencies[index] = arena.children(*id)
+                        .iter()
+                        .fold(0, |mask, child| mask | dependencies[child.index()]);
+                    if dependencies[index] == 0 {
+                        registers[index] = variable_count + constants.len();
+                        constants.push(arena.output(*id, &[]));
+                    } else {
+                        pending_operations.push(*id);
+                    }
+                }
+            }
+        }
+        let mut register_count = variable_count + constants.len();
+        let mut operations = Vec::with_capacity(pending_operations.len());
+        let mut operation_dependencies = Vec::with_capacity(pending_operations.len());
+        let mut arguments = Vec::new();
+        for id in pending_operations {
+            if let Some(same) = identity(arena, id) {
+                registers[id.index()] = registers[same.index()];
+                continue;
+            }
+            let children = arena.children(id);
+            registers[id.index()] = register_count;
+            register_count += 1;
+            let operator = operator(arena, id);
+            let operation = if let Operator::Call(_) = operator {
+                let first = arguments.len() as u32;
+                arguments.extend(children.iter().map(|child| registers[child.index()] as u32));
+                Operation {
+                    operator,
+                    inputs: Inputs::Call,
+                    left: first,
+                    right: first,
+                }
+            } else {
+                let (left, right) = (registers[children[0].index()], registers[children[1].index()]);
+                // The first operation has no operation before it.
+                let previous = if operations.is_empty() { usize::MAX } else { register_count - 2 };
+                let inputs = match (left == previous, right == previous) {
+                    (false, false) => Inputs::Registers,
+                    (true, false) => Inputs::PreviousLeft,
+                    (false, true) => Inputs::PreviousRight,
+                    (true, true) => Inputs::PreviousBoth,
+                };
+                Operation {
+                    operator,
+                    inputs,
+                    left: left as u32,
+                    right: right as u32,
+                }
+            };
+            operations.push(operation);
+            operation_dependencies.push(dependencies[id.index()]);
+        }
+        let mut template = [0f64; SMALL_REGISTERS];
+        for (register, constant) in template.iter_mut().skip(variable_count).zip(&constants) {
+            *register = *constant;
+        }
+        Program {
+            operations,
+            dependencies: operation_dependencies,
+            arguments,
+            constants,
+            variable_count,
+            register_count,
+            result: registers[root.index()],
+            template,
+        }
+    }
+
+    pub fn operation_count(&self) -> usize {
+        return self.operations.len();
+    }
+
+    /// Whether the program is small enough that a tree of functions evaluates it faster, as
+    /// setting up the registers costs more than the few operations without calls save.
+    pub fn is_trivial(&self) -> bool {
+        return self.operations.len() <= 3 && self.arguments.is_empty();
+    }
+
+    fn operations_start(&self) -> usize {
+        return self.variable_count + self.constants.len();
+    }
+
+    fn run(&self, variables: &[f64], registers: &mut [f64]) -> f64 {
+        let start = self.operations_start();
+        registers[..self.variable_count].copy_from_slice(&variables[..self.variable_count]);
+        registers[self.variable_count..start].copy_from_slice(&self.constants);
+        let mut previous = 0f64;
+        for (i, operation) in self.operations.iter().enumerate() {
+            previous = self.execute(operation, registers, previous);
+            registers[start + i] = previous;
+        }
+        return registers[self.result];
+    }
+
+    #[inline(always)]
+    fn execute(&self, operation: &Operation, registers: &[f64], previous: f64) -> f64 {
+        let (left, right) = (operation.left as usize, operation.right as usize);
+        return match operation.inputs {
+            Inputs::Registers => operation.operator.apply(registers[left], registers[right]),
+            Inputs::PreviousLeft => operation.operator.apply(previous, registers[right]),
+            Inputs::PreviousRight => operation.operator.apply(registers[left], previous),
+            Inputs::PreviousBoth => operation.operator.apply(previous, previous),
+            Inputs::Call => self.call(operation, registers),
+        };
+    }
+
+    fn call(&self, operation: &Operation, registers: &[f64]) -> f64 {
+        let function = match &operation.operator {
+            Operator::Call(function) => function,
+            _ => unreachable!(),
+        };
+        let inputs = &self.arguments[operation.left as usize..operation.left as usize + function.arity];
+        if inputs.len() <= CALL_INPUTS {
+            let mut values = [0f64; CALL_INPUTS];
+            for (value, input) in values.iter_mut().zip(inputs) {
+                *value = registers[*input as usize];
+            }
+            return (function.evaluate)(&values[..inputs.len()]);
+        }
+        let values: Vec<f64> = inputs.iter().map(|input| registers[*input as usize]).collect();
+        return (function.evaluate)(&values);
+    }
+
+    fn emit(&self, register: usize, code: &mut Vec<Instruction>) {
+        let constants_end = self.operations_start();
+        if register < self.variable_count {
+            code.push(Instruction::Variable(register));
+        } else if register < constants_end {
+            code.push(Instruction::Constant(self.constants[register - self.variable_count]));
+        } else {
+            let operation = &self.operations[register - constants_end];
+            if let Operator::Call(function) = &operation.operator {
+                for input in &self.arguments[operation.left as usize..operation.left as usize + function.arity] {
+                    self.emit(*input as usize, code);
+                }
+            } else {
+                self.emit(operation.left as usize, code);
+                self.emit(operation.right as usize, code);
+            }
+            code.push(Instruction::Operator(operation.operator.clone()));
+        }
+    }
+}
+
+impl Function for Program {
+    fn output(&self, variables: &[f64]) -> f64 {
+        return if self.register_count <= SMALL_REGISTERS {
+            // Copying a fixed size array is cheaper than copying the constants one by one.
+            let mut registers = self.template;
+            for (register, variable) in registers.iter_mut().zip(&variables[..self.variable_count]) {
+                *register = *variable;
+            }
+            let start = self.operations_start();
+            let mut previous = 0f64;
+            for (i, operation) in self.operations.iter().enumerate() {
+                previous = self.execute(operation, &registers, previous);
+                registers[start + i] = previous;
+            }
+            registers[self.result]
+        } else if self.register_count <= REGISTERS {
+            self.run(variables, &mut [0f64; REGISTERS])
+        } else {
+            self.run(variables, &mut vec![0f64; self.register_count])
+        };
+    }
+
+    fn compile(&self, code: &mut Vec<Instruction>) {
+        self.emit(self.result, code);
+    }
+
+    fn evaluator(&self) -> Evaluator<'_> {
+        let mut cache = ProgramCache::new(self);
+        return Box::new(move |variables| cache.output(variables));
+    }
+}
+
+/// Keeps every register of a program between evaluations and only recomputes
+/// the operations depending on variables that changed since the previous call.
+pub struct ProgramCache<'a> {
+    program: &'a Program,
+    registers: Vec<f64>,
+    initialized: bool,
+}
+
+impl ProgramCache<'_> {
+    pub fn new(program: &Program) -> ProgramCache<'_> {
+        ProgramCache {
+            program,
+            registers: vec![0f64; program.register_count],
+            initialized: false,
+        }
+    }
+
+    pub fn invalidate(&mut self) {
+        self.initialized = false;
+    }
+
+    pub fn output(&mut self, variables: &[f64]) -> f64 {
+        let program = self.program;
+        if !self.initialized {
+            self.initialized = true;
+            return program.run(variables, &mut self.registers);
+        }
+        let mut changed = 0u64;
+        let current = self.registers.iter_mut().zip(&variables[..program.variable_count]);
+        for (id, (register, variable)) in current.enumerate() {
+            if register.to_bits() != variable.to_bits() {
+                *register = *variable;
+                changed |= 1u64 << id.min(63);
+            }
+        }
+        if changed != 0 {
+            let start = program.operations_start();
+            // A skipped operation still leaves its register up to date for the one after it.
+            let mut previous = 0f64;
+            for (i, (operation, dependencies)) in program.operations.iter().zip(&program.dependencies).enumerate() {
+                if dependencies & changed != 0 {
+                    previous = program.execute(operation, &self.registers, previous);
+                    self.registers[start + i] = previous;
+                } else {
+                    previous = self.registers[start + i];
+                }
+            }
+        }
+        return self.registers[program.result];
+    }
+}
+
+fn operator(arena: &ExprArena, id: ExprId) -> Operator {
+    return match arena[id] {
+        Expr::Sum(..) => Operator::Sum,
+        Expr::Sub(..) => Operator::Sub,
+        Expr::Mul(..) => Operator::Mul,
+        Expr::Div(..) => Operator::Div,
+        Expr::Pow(..) => Operator::Pow,
+        Expr::Call(function, _) => Operator::Call(Arc::clone(arena.function(function))),
+        Expr::Constant(_) | Expr::Variable(_) => unreachable!(),
+    };
+}
+
+/// The input an operation returns unchanged for every value of it, NaN and signed zeros
+/// included: `a - 0`, `a * 1` and `a / 1`. Equations compile to `lhs - rhs`, so every
+/// `... = 0` ends in such an operation.
+fn identity(arena: &ExprArena, id: ExprId) -> Option<ExprId> {
+    let constant = |id: ExprId, expected: f64| match arena[id] {
+        Expr::Constant(val) => val.to_bits() == expected.to_bits(),
+        _ => false,
+    };
+    return match arena[id] {
+        Expr::Sub(a, b) if constant(b, 0f64) => Option::Some(a),
+        Expr::Mul(a, b) | Expr::Div(a, b) if constant(b, 1f64) => Option::Some(a),
+        Expr::Mul(a, b) if constant(a, 1f64) => Option::Some(b),
+        _ => Option::None,
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::eq_solver::builtin::BUILTINS;
+    use crate::eq_solver::compile::{Program, ProgramCache};
+    use crate::eq_solver::expr::{Expr, ExprArena, ExprId};
+    use crate::eq_solver::function::Function;
+
+    const INPUTS: [f64; 9] = [0.0, -0.0, 1.0, -1.0, 0.5, 2.5, f64::INFINITY, f64::NEG_INFINITY, f64::NAN];
+
+    fn same(a: f64, b: f64) -> bool {
+        return a.to_bits() == b.to_bits() || (a.is_nan() && b.is_nan());
+    }
+
+    /// Evaluates `root` as a tree, as a program and through a cache at every combination of
+    /// inputs, reusing the cache so that only some variables change between evaluations.
+    fn check(arena: &ExprArena, root: ExprId, variables: usize) {
+        let tree = arena.to_function(root);
+        let program = arena.compile(root);
+        let mut cache = ProgramCache::new(&program);
+        let mut values = vec![0f64; variables];
+        for combination in 0..INPUTS.len().pow(variables as u32) {
+            let mut rest = combination;
+            for value in values.iter_mut() {
+                *value = INPUTS[rest % INPUTS.len()];
+                rest /= INPUTS.len();
+            }
+            let expected = tree.output(&values);
+            assert!(same(program.output(&values), expected), "{:?} at {:?}", arena[root], values);
+            assert!(same(cache.output(&values), expected), "{:?} at {:?}", arena[root], values);
+        }
+    }
+
+    #[test]
+    fn matches_tree_for_operators() {
+        let exprs: [fn(ExprId, ExprId) -> Expr; 5] = [Expr::Sum, Expr::Sub, Expr::Mul, Expr::Div, Expr::Pow];
+        for expr in exprs {
+            let mut arena = ExprArena::new();
+            let (x, y) = (arena.add(Expr::Variable(0)), arena.add(Expr::Variable(1)));
+            let root = arena.add(expr(x, y));
+            check(&arena, root, 2);
+            // The result of the operation before feeds either input, or both.
+            let both = arena.add(expr(root, root));
+            let left = arena.add(expr(both, y));
+            let right = arena.add(expr(x, left));
+            check(&arena, right, 2);
+        }
+    }
+
+    #[test]
+    fn matches_tree_for_builtins() {
+        for function in BUILTINS.iter() {
+            let mut arena = ExprArena::new();
+            let arguments: Vec<_> = (0..function.arity).map(|i| arena.add(Expr::Variable(i))).collect();
+            let call = arena.add_call(function, &arguments);
+            check(&arena, call, function.arity);
+            let x = arena.add(Expr::Variable(0));
+            let root = arena.add(Expr::Mul(call, x));
+            check(&arena, root, function.arity);
+        }
+    }
+
+    #[test]
+    fn identities_keep_signed_zeros_and_nan() {
+        let mut arena = ExprArena::new();
+        let x = arena.add(Expr::Variable(0));
+        let y = arena.add(Expr::Variable(1));
+        let (zero, one) = (arena.add(Expr::Constant(0.0)), arena.add(Expr::Constant(1.0)));
+        let product = arena.add(Expr::Mul(x, y));
+        let sub = arena.add(Expr::Sub(product, zero));
+        let left = arena.add(Expr::Mul(one, sub));
+        let right = arena.add(Expr::Mul(left, one));
+        let root = arena.add(Expr::Div(right, one));
+        assert_eq!(arena.compile(root).operation_count(), 1);
+        check(&arena, root, 2);
+        // Adding zero turns -0 into 0, so it stays an operation.
+        let sum = arena.add(Expr::Sum(product, zero));
+        assert_eq!(arena.compile(sum).operation_count(), 2);
+        check(&arena, sum, 2);
+    }
+
+    #[test]
+    fn compiles_code_with_many_registers() {
+        let mut arena = ExprArena::new();
+        let x = arena.add(Expr::Variable(0));
+        let mut root = x;
+        for i in 0..80 {
+            let constant = arena.add(Expr::Constant(i as f64 + 0.5));
+            let sum = arena.add(Expr::Sum(root, constant));
+            root = arena.add(Expr::Div(sum, x));
+        }
+        assert!(arena.compile(root).operation_count() > 64);
+        check(&arena, root, 1);
+        let mut code = Vec::new();
+        arena.compile(root).compile(&mut code);
+        check(&arena, root, 1);
+        let program = Program::from_code(&code);
+        assert!(same(program.output(&[2.5]), arena.to_function(root).output(&[2.5])));
+    }
+
+    #[test]
+    fn shares_identical_subexpressions() {
+        let mut arena = ExprArena::new();
+        let x = arena.add(Expr::Variable(0));
+        let y = arena.add(Expr::Variable(1));
+        let first = arena.add(Expr::Mul(x, y));
+        let second = arena.add(Expr::Mul(x, y));
+        assert_eq!(first, second);
+        let sin = arena.add_call(crate::eq_solver::builtin::builtin("sin", 1), &[first]);
+        let root = arena.add(Expr::Sum(sin, second));
+        let root = arena.add(Expr::Div(root, first));
+        let program = arena.compile(root);
+        // x * y is computed once and read three times.
+        assert_eq!(program.operation_count(), 4);
+        let registers: Vec<_> = program.operations.iter().map(|operation| (operation.left, operation.right)).collect();
+        assert_eq!(registers[2].1, 2);
+        assert_eq!(registers[3].1, 2);
+        assert_eq!(program.arguments, vec![2]);
+        check(&arena, root, 2);
+    }
+
+    /// `x * y + (z - 1)`, with `x`, `y` and `z` the variables at `slots`.
+    fn cached(slots: [usize; 3]) -> (ExprArena, ExprId) {
+        let mut arena = ExprArena::new();
+        let [x, y, z] = slots.map(|slot| arena.add(Expr::Variable(slot)));
+        let one = arena.add(Expr::Constant(1.0));
+        let product = arena.add(Expr::Mul(x, y));
+        let difference = arena.add(Expr::Sub(z, one));
+        let root = arena.add(Expr::Sum(product, difference));
+        return (arena, root);
+    }
+
+    #[test]
+    fn recomputes_only_operations_depending_on_changes() {
+        let (arena, root) = cached([0, 1, 2]);
+        let program = arena.compile(root);
+        let mut cache = ProgramCache::new(&program);
+        assert_eq!(cache.output(&[2.0, 3.0, 5.0]), 10.0);
+        // Overwriting the register of x * y shows whether it was recomputed.
+        let product = program.operations_start();
+        cache.registers[product] = 100.0;
+        assert_eq!(cache.output(&[2.0, 3.0, 6.0]), 105.0);
+        assert_eq!(cache.output(&[2.0, 4.0, 6.0]), 13.0);
+        cache.registers[product] = 100.0;
+        cache.invalidate();
+        assert_eq!(cache.output(&[2.0, 4.0, 6.0]), 13.0);
+    }
+
+    #[test]
+    fn tracks_variables_past_the_mask_together() {
+        // Variables from 63 on share the last bit of the dependency mask.
+        let (arena, root) = cached([63, 64, 0]);
+        let program = arena.compile(root);
+        let mut cache = ProgramCache::new(&program);
+        let mut values = vec![0f64; 65];
+        values[63] = 2.0;
+        values[64] = 3.0;
+        values[0] = 5.0;
+        assert_eq!(cache.output(&values), 10.0);
+        let product = program.operations_start();
+        cache.registers[product] = 100.0;
+        values[0] = 6.0;
+        assert_eq!(cache.output(&values), 105.0);
+        values[64] = 4.0;
+        assert_eq!(cache.output(&values), 13.0);
+        cache.registers[product] = 100.0;
+        values[63] = 1.0;
+        assert_eq!(cache.output(&values), 9.0);
+        assert_eq!(program.output(&values), 9.0);
+    }
+}
@@ -0,0 +1,114 @@
+use crate::eq_solver::scalar::Scalar;
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Complex {
+    pub re: f64,
+    pub im: f64,
+}
+
+impl Complex {
+    pub fn new(re: f64, im: f64) -> Complex {
+        Complex {
+            re,
+            im,
+        }
+    }
+
+    pub fn conj(&self) -> Complex {
+        return Complex::new(self.re, -self.im);
+    }
+
+    pub fn norm(&self) -> f64 {
+        return self.re.hypot(self.im);
+    }
+
+    pub fn is_real(&self) -> bool {
+        return self.im == 0f64;
+    }
+}
+
+impl Scalar for Complex {
+    fn zero() -> Self {
+        return Complex::new(0f64, 0f64);
+    }
+
+    fn one() -> Self {
+        return Complex::new(1f64, 0f64);
+    }
+
+    fn magnitude(self) -> f64 {
+        return self.norm();
+    }
+
+    fn epsilon() -> f64 {
+        return f64::EPSILON;
+    }
+}
+
+impl From<f64> for Complex {
+    fn from(re: f64) -> Self {
+        return Complex::new(re, 0f64);
+    }
+}
+
+impl fmt::Display for Complex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return if self.im == 0f64 {
+            write!(f, "{}", self.re)
+        } else if self.im < 0f64 {
+            write!(f, "{} - {}i", self.re, -self.im)
+        } else {
+            write!(f, "{} + {}i", self.re, self.im)
+        };
+    }
+}
+
+impl std::ops::Add for Complex {
+    type Output = Complex;
+
+    fn add(self, rhs: Complex) -> Self::Output {
+        return Complex::new(self.re + rhs.re, self.im + rhs.im);
+    }
+}
+
+impl std::ops::Sub for Complex {
+    type Output = Complex;
+
+    fn sub(self, rhs: Complex) -> Self::Output {
+        return Complex::new(self.re - rhs.re, self.im - rhs.im);
+    }
+}
+
+impl std::ops::Mul for Complex {
+    type Output = Complex;
+
+    fn mul(self, rhs: Complex) -> Self::Output {
+        return Complex::new(self.re * rhs.re - self.im * rhs.im, self.re * rhs.im + self.im * rhs.re);
+    }
+}
+
+impl std::ops::Div for Complex {
+    type Output = Complex;
+
+    /// Smith's algorithm, avoiding overflow in the denominator.
+    fn div(self, rhs: Complex) -> Self::Output {
+        return if rhs.re.abs() >= rhs.im.abs() {
+            let ratio = rhs.im / rhs.re;
+            let denominator = rhs.re + ratio * rhs.im;
+            Complex::new((self.re + ratio * self.im) / denominator, (self.im - ratio * self.re) / denominator)
+        } else {
+            let ratio = rhs.re / rhs.im;
+            let denominator = rhs.im + ratio * rhs.re;
+            Complex::new((ratio * self.re + self.im) / denominator, (ratio * self.im - self.re) / denominator)
+        };
+    }
+}
+
+impl std::ops::Neg for Complex {
+    type Output = Complex;
+
+    fn neg(self) -> Self::Output {
+        return Complex::new(-self.re, -self.im);
+    }
+}
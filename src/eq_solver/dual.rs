@@ -0,0 +1,93 @@
+use crate::eq_solver::scalar::Scalar;
+use std::fmt;
+
+/// A value together with its derivative, so that arithmetic on duals applies the chain rule.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Dual {
+    pub value: f64,
+    pub derivative: f64,
+}
+
+impl Dual {
+    pub fn new(value: f64, derivative: f64) -> Dual {
+        Dual {
+            value,
+            derivative,
+        }
+    }
+
+    /// The variable being differentiated for, with a derivative of one.
+    pub fn variable(value: f64) -> Dual {
+        return Dual::new(value, 1f64);
+    }
+
+    pub fn constant(value: f64) -> Dual {
+        return Dual::new(value, 0f64);
+    }
+}
+
+impl Scalar for Dual {
+    fn zero() -> Self {
+        return Dual::constant(0f64);
+    }
+
+    fn one() -> Self {
+        return Dual::constant(1f64);
+    }
+
+    /// The magnitude of the value alone, so pivots are the ones the values would choose.
+    fn magnitude(self) -> f64 {
+        return self.value.abs();
+    }
+
+    fn epsilon() -> f64 {
+        return f64::EPSILON;
+    }
+}
+
+impl fmt::Display for Dual {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return write!(f, "{} + {}ε", self.value, self.derivative);
+    }
+}
+
+impl std::ops::Add for Dual {
+    type Output = Dual;
+
+    fn add(self, rhs: Dual) -> Self::Output {
+        return Dual::new(self.value + rhs.value, self.derivative + rhs.derivative);
+    }
+}
+
+impl std::ops::Sub for Dual {
+    type Output = Dual;
+
+    fn sub(self, rhs: Dual) -> Self::Output {
+        return Dual::new(self.value - rhs.value, self.derivative - rhs.derivative);
+    }
+}
+
+impl std::ops::Mul for Dual {
+    type Output = Dual;
+
+    fn mul(self, rhs: Dual) -> Self::Output {
+        return Dual::new(self.value * rhs.value, self.derivative * rhs.value + self.value * rhs.derivative);
+    }
+}
+
+impl std::ops::Div for Dual {
+    type Output = Dual;
+
+    fn div(self, rhs: Dual) -> Self::Output {
+        let derivative = (self.derivative * rhs.value - self.value * rhs.derivative) / (rhs.value * rhs.value);
+        return Dual::new(self.value / rhs.value, derivative);
+    }
+}
+
+impl std::ops::Neg for Dual {
+    type Output = Dual;
+
+    fn neg(self) -> Self::Output {
+        return Dual::new(-self.value, -self.derivative);
+    }
+}
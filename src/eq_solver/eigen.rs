@@ -0,0 +1,649 @@
+// The Hessenberg and Schur routines index rows and columns of several arrays at once.
+#![allow(clippy::needless_range_loop)]
+
+use crate::eq_solver::complex::Complex;
+use crate::eq_solver::matrix::{Matrix, MatrixError};
+
+const JACOBI_SWEEPS: usize = 100;
+/// Shifted QR steps allowed per eigenvalue, of at least 10 eigenvalues, as in LAPACK.
+const QR_ITERATIONS: usize = 30;
+
+/// Eigen decomposition of a symmetric matrix, eigenvalues in ascending order.
+///
+/// Column `k` of `vectors` is the unit eigenvector of `values[k]`.
+pub struct SymmetricEigen {
+    pub values: Vec<f64>,
+    pub vectors: Matrix,
+}
+
+/// Eigen decomposition of a general square matrix.
+///
+/// Complex eigenvalues come in conjugate pairs, the one with positive imaginary part first.
+/// `vectors[k]` is the unit eigenvector of `values[k]`.
+pub struct Eigen {
+    pub values: Vec<Complex>,
+    pub vectors: Vec<Vec<Complex>>,
+}
+
+impl SymmetricEigen {
+    /// Cyclic Jacobi rotations, only reading the matrix as symmetric.
+    pub fn new(matrix: &Matrix) -> SymmetricEigen {
+        assert_eq!(matrix.rows, matrix.columns);
+        let size = matrix.rows;
+        let mut a = rows_of(matrix);
+        let mut v = identity_rows(size);
+        let frobenius = a.iter().flatten().fold(0f64, |norm, val| norm.hypot(*val));
+        for _sweep in 0..JACOBI_SWEEPS {
+            let mut off_diagonal = 0f64;
+            for p in 0..size {
+                for q in p + 1..size {
+                    off_diagonal = off_diagonal.hypot(a[p][q]);
+                }
+            }
+            if off_diagonal <= f64::EPSILON * frobenius {
+                break;
+            }
+            for p in 0..size {
+                for q in p + 1..size {
+                    if a[p][q] == 0f64 {
+                        continue;
+                    }
+                    let theta = (a[q][q] - a[p][p]) / (2f64 * a[p][q]);
+                    let t = theta.signum() / (theta.abs() + theta.hypot(1f64));
+                    let c = 1f64 / t.hypot(1f64);
+                    let s = t * c;
+                    for row in a.iter_mut().chain(v.iter_mut()) {
+                        let (kp, kq) = (row[p], row[q]);
+                        row[p] = c * kp - s * kq;
+                        row[q] = s * kp + c * kq;
+                    }
+                    for k in 0..size {
+                        let (pk, qk) = (a[p][k], a[q][k]);
+                        a[p][k] = c * pk - s * qk;
+                        a[q][k] = s * pk + c * qk;
+                    }
+                }
+            }
+        }
+        let mut order: Vec<usize> = (0..size).collect();
+        order.sort_by(|i, j| a[*i][*i].total_cmp(&a[*j][*j]));
+        let mut vectors = Matrix::zeros(size, size);
+        for (k, i) in order.iter().enumerate() {
+            for j in 0..size {
+                vectors[(k, j)] = v[j][*i];
+            }
+        }
+        SymmetricEigen {
+            values: order.iter().map(|i| a[*i][*i]).collect(),
+            vectors,
+        }
+    }
+}
+
+impl Eigen {
+    /// Symmetric matrices use Jacobi rotations, anything else is reduced to Hessenberg form
+    /// and solved with the shifted QR algorithm, which fails if it does not converge, as with
+    /// NaN entries.
+    pub fn new(matrix: &Matrix) -> Result<Eigen, MatrixError> {
+        assert_eq!(matrix.rows, matrix.columns);
+        if matrix.is_symmetric() {
+            let symmetric = SymmetricEigen::new(matrix);
+            let size = matrix.rows;
+            return Ok(Eigen {
+                values: symmetric.values.iter().map(|val| Complex::from(*val)).collect(),
+                vectors: (0..size)
+                    .map(|k| (0..size).map(|j| Complex::from(symmetric.vectors[(k, j)])).collect())
+                    .collect(),
+            });
+        }
+        let mut h = rows_of(matrix);
+        let mut v = hessenberg(&mut h);
+        let (real, imaginary) = schur(&mut h, &mut v)?;
+        let size = matrix.rows;
+        let mut values = Vec::with_capacity(size);
+        let mut vectors = Vec::with_capacity(size);
+        let mut k = 0;
+        while k < size {
+            if imaginary[k] == 0f64 {
+                values.push(Complex::from(real[k]));
+                vectors.push(normalize((0..size).map(|j| Complex::from(v[j][k])).collect()));
+                k += 1;
+            } else {
+                let vector = normalize((0..size).map(|j| Complex::new(v[j][k], v[j][k + 1])).collect());
+                values.push(Complex::new(real[k], imaginary[k]));
+                values.push(Complex::new(real[k + 1], imaginary[k + 1]));
+                let conjugate = vector.iter().map(|val| val.conj()).collect();
+                vectors.push(vector);
+                vectors.push(conjugate);
+                k += 2;
+            }
+        }
+        Ok(Eigen {
+            values,
+            vectors,
+        })
+    }
+
+    /// Largest absolute value of the eigenvalues.
+    pub fn spectral_radius(&self) -> f64 {
+        return self.values.iter().fold(0f64, |max, val| max.max(val.norm()));
+    }
+}
+
+impl Matrix {
+    pub fn is_symmetric(&self) -> bool {
+        if self.rows != self.columns {
+            return false;
+        }
+        for i in 0..self.columns {
+            for j in i + 1..self.rows {
+                if self[(i, j)] != self[(j, i)] {
+                    return false;
+                }
+            }
+        }
+        return true;
+    }
+
+    pub fn symmetric_eigen(&self) -> SymmetricEigen {
+        return SymmetricEigen::new(self);
+    }
+
+    pub fn eigen(&self) -> Result<Eigen, MatrixError> {
+        return Eigen::new(self);
+    }
+
+    pub fn eigenvalues(&self) -> Result<Vec<Complex>, MatrixError> {
+        return Ok(Eigen::new(self)?.values);
+    }
+}
+
+/// Roots of `coefficients[0] + coefficients[1] * x + ...` as the eigenvalues of the companion matrix.
+pub fn polynomial_roots(coefficients: &[f64]) -> Result<Vec<Complex>, MatrixError> {
+    let degree = match coefficients.iter().rposition(|val| *val != 0f64) {
+        Some(degree) => degree,
+        None => return Ok(Vec::new()),
+    };
+    let mut companion = Matrix::zeros(degree, degree);
+    for i in 0..degree {
+        companion[(degree - 1, i)] = -coefficients[i] / coefficients[degree];
+        if i > 0 {
+            companion[(i - 1, i)] = 1f64;
+        }
+    }
+    return companion.eigenvalues();
+}
+
+fn rows_of(matrix: &Matrix) -> Vec<Vec<f64>> {
+    return (0..matrix.rows)
+        .map(|row| (0..matrix.columns).map(|column| matrix[(column, row)]).collect())
+        .collect();
+}
+
+fn identity_rows(size: usize) -> Vec<Vec<f64>> {
+    return (0..size)
+        .map(|row| (0..size).map(|column| if row == column { 1f64 } else { 0f64 }).collect())
+        .collect();
+}
+
+fn normalize(vector: Vec<Complex>) -> Vec<Complex> {
+    let norm = vector.iter().fold(0f64, |norm, val| norm.hypot(val.norm()));
+    if norm == 0f64 {
+        return vector;
+    }
+    return vector.iter().map(|val| Complex::new(val.re / norm, val.im / norm)).collect();
+}
+
+/// Reduces `h` to upper Hessenberg form with Householder reflections, returning the accumulated transformations.
+fn hessenberg(h: &mut [Vec<f64>]) -> Vec<Vec<f64>> {
+    let size = h.len();
+    let mut v = identity_rows(size);
+    if size < 3 {
+        return v;
+    }
+    let high = size - 1;
+    let mut ort = vec![0f64; size];
+    for m in 1..high {
+        let scale: f64 = (m..=high).map(|i| h[i][m - 1].abs()).sum();
+        if scale == 0f64 {
+            continue;
+        }
+        let mut norm = 0f64;
+        for i in (m..=high).rev() {
+            ort[i] = h[i][m - 1] / scale;
+            norm += ort[i] * ort[i];
+        }
+        let mut g = norm.sqrt();
+        if ort[m] > 0f64 {
+            g = -g;
+        }
+        norm -= ort[m] * g;
+        ort[m] -= g;
+        for j in m..size {
+            let f = (m..=high).rev().map(|i| ort[i] * h[i][j]).sum::<f64>() / norm;
+            for i in m..=high {
+                h[i][j] -= f * ort[i];
+            }
+        }
+        for row in h.iter_mut() {
+            let f = (m..=high).rev().map(|j| ort[j] * row[j]).sum::<f64>() / norm;
+            for j in m..=high {
+                row[j] -= f * ort[j];
+            }
+        }
+        ort[m] *= scale;
+        h[m][m - 1] = scale * g;
+    }
+    for m in (1..high).rev() {
+        if h[m][m - 1] == 0f64 {
+            continue;
+        }
+        for i in m + 1..=high {
+            ort[i] = h[i][m - 1];
+        }
+        for j in m..=high {
+            let g = (m..=high).map(|i| ort[i] * v[i][j]).sum::<f64>() / ort[m] / h[m][m - 1];
+            for i in m..=high {
+                v[i][j] += g * ort[i];
+            }
+        }
+    }
+    return v;
+}
+
+/// Francis double shift QR on a Hessenberg matrix, followed by back substitution for the eigenvectors.
+///
+/// Returns the real and imaginary parts of the eigenvalues. Column `k` of `v` ends up holding the
+/// eigenvector of a real eigenvalue, or the real part of a complex one whose imaginary part is in
+/// column `k + 1`. Fails after `QR_ITERATIONS` steps per eigenvalue.
+fn schur(h: &mut [Vec<f64>], v: &mut [Vec<f64>]) -> Result<(Vec<f64>, Vec<f64>), MatrixError> {
+    let size = h.len();
+    let mut real = vec![0f64; size];
+    let mut imaginary = vec![0f64; size];
+    let eps = f64::EPSILON;
+    let mut exshift = 0f64;
+    let (mut r, mut s, mut z) = (0f64, 0f64, 0f64);
+    let (mut p, mut q, mut t, mut w, mut x, mut y);
+
+    let mut norm = 0f64;
+    for (i, row) in h.iter().enumerate() {
+        norm += row[i.saturating_sub(1)..].iter().map(|val| val.abs()).sum::<f64>();
+    }
+
+    let mut n = size as isize - 1;
+    let mut iterations = 0;
+    let mut total_iterations = 0;
+    while n >= 0 {
+        let nu = n as usize;
+        let mut l = nu;
+        while l > 0 {
+            s = h[l - 1][l - 1].abs() + h[l][l].abs();
+            if s == 0f64 {
+                s = norm;
+            }
+            if h[l][l - 1].abs() < eps * s {
+                break;
+            }
+            l -= 1;
+        }
+
+        if l == nu {
+            h[nu][nu] += exshift;
+            real[nu] = h[nu][nu];
+            imaginary[nu] = 0f64;
+            n -= 1;
+            iterations = 0;
+        } else if l == nu - 1 {
+            w = h[nu][nu - 1] * h[nu - 1][nu];
+            p = (h[nu - 1][nu - 1] - h[nu][nu]) / 2f64;
+            q = p * p + w;
+            z = q.abs().sqrt();
+            h[nu][nu] += exshift;
+            h[nu - 1][nu - 1] += exshift;
+            x = h[nu][nu];
+            if q >= 0f64 {
+                z = if p >= 0f64 { p + z } else { p - z };
+                real[nu - 1] = x + z;
+                real[nu] = real[nu - 1];
+                if z != 0f64 {
+                    real[nu] = x - w / z;
+                }
+                imaginary[nu - 1] = 0f64;
+                imaginary[nu] = 0f64;
+                x = h[nu][nu - 1];
+                s = x.abs() + z.abs();
+                p = x / s;
+                q = z / s;
+                r = p.hypot(q);
+                p /= r;
+                q /= r;
+                for j in nu - 1..size {
+                    z = h[nu - 1][j];
+                    h[nu - 1][j] = q * z + p * h[nu][j];
+                    h[nu][j] = q * h[nu][j] - p * z;
+                }
+                for row in h.iter_mut().take(nu + 1) {
+                    z = row[nu - 1];
+                    row[nu - 1] = q * z + p * row[nu];
+                    row[nu] = q * row[nu] - p * z;
+                }
+                for row in v.iter_mut() {
+                    z = row[nu - 1];
+                    row[nu - 1] = q * z + p * row[nu];
+                    row[nu] = q * row[nu] - p * z;
+                }
+            } else {
+                real[nu - 1] = x + p;
+                real[nu] = x + p;
+                imaginary[nu - 1] = z;
+                imaginary[nu] = -z;
+            }
+            n -= 2;
+            iterations = 0;
+        } else {
+            x = h[nu][nu];
+            y = h[nu - 1][nu - 1];
+            w = h[nu][nu - 1] * h[nu - 1][nu];
+            // Exceptional shifts break cycles of the standard shift.
+            if iterations == 10 {
+                exshift += x;
+                for (i, row) in h.iter_mut().enumerate().take(nu + 1) {
+                    row[i] -= x;
+                }
+                s = h[nu][nu - 1].abs() + h[nu - 1][nu - 2].abs();
+                x = 0.75 * s;
+                y = x;
+                w = -0.4375 * s * s;
+            }
+            if iterations == 30 {
+                s = (y - x) / 2f64;
+                s = s * s + w;
+                if s > 0f64 {
+                    s = s.sqrt();
+                    if y < x {
+                        s = -s;
+                    }
+                    s = x - w / ((y - x) / 2f64 + s);
+                    for (i, row) in h.iter_mut().enumerate().take(nu + 1) {
+                        row[i] -= s;
+                    }
+                    exshift += s;
+                    x = 0.964;
+                    y = x;
+                    w = x;
+                }
+            }
+            iterations += 1;
+            total_iterations += 1;
+            if total_iterations > QR_ITERATIONS * size.max(10) {
+                return Err(MatrixError::NoConvergence);
+            }
+
+            let mut m = nu - 2;
+            loop {
+                z = h[m][m];
+                r = x - z;
+                s = y - z;
+                p = (r * s - w) / h[m + 1][m] + h[m][m + 1];
+                q = h[m + 1][m + 1] - z - r - s;
+                r = h[m + 2][m + 1];
+                s = p.abs() + q.abs() + r.abs();
+                p /= s;
+                q /= s;
+                r /= s;
+                if m == l {
+                    break;
+                }
+                let lhs = h[m][m - 1].abs() * (q.abs() + r.abs());
+                let rhs = eps * (p.abs() * (h[m - 1][m - 1].abs() + z.abs() + h[m + 1][m + 1].abs()));
+                if lhs < rhs {
+                    break;
+                }
+                m -= 1;
+            }
+            for i in m + 2..=nu {
+                h[i][i - 2] = 0f64;
+                if i > m + 2 {
+                    h[i][i - 3] = 0f64;
+                }
+            }
+
+            for k in m..nu {
+                let not_last = k != nu - 1;
+                if k != m {
+                    p = h[k][k - 1];
+                    q = h[k + 1][k - 1];
+                    r = if not_last { h[k + 2][k - 1] } else { 0f64 };
+                    x = p.abs() + q.abs() + r.abs();
+                    if x == 0f64 {
+                        continue;
+                    }
+                    p /= x;
+                    q /= x;
+                    r /= x;
+                }
+                s = (p * p + q * q + r * r).sqrt();
+                if p < 0f64 {
+                    s = -s;
+                }
+                if s != 0f64 {
+                    if k != m {
+                        h[k][k - 1] = -s * x;
+                    } else if l != m {
+                        h[k][k - 1] = -h[k][k - 1];
+                    }
+                    p += s;
+                    x = p / s;
+                    y = q / s;
+                    z = r / s;
+                    q /= p;
+                    r /= p;
+                    for j in k..size {
+                        p = h[k][j] + q * h[k + 1][j];
+                        if not_last {
+                            p += r * h[k + 2][j];
+                            h[k + 2][j] -= p * z;
+                        }
+                        h[k][j] -= p * x;
+                        h[k + 1][j] -= p * y;
+                    }
+                    for row in h.iter_mut().take(nu.min(k + 3) + 1) {
+                        p = x * row[k] + y * row[k + 1];
+                        if not_last {
+                            p += z * row[k + 2];
+                            row[k + 2] -= p * r;
+                        }
+                        row[k] -= p;
+                        row[k + 1] -= p * q;
+                    }
+                    for row in v.iter_mut() {
+                        p = x * row[k] + y * row[k + 1];
+                        if not_last {
+                            p += z * row[k + 2];
+                            row[k + 2] -= p * r;
+                        }
+                        row[k] -= p;
+                        row[k + 1] -= p * q;
+                    }
+                }
+            }
+        }
+    }
+
+    if norm == 0f64 {
+        return Ok((real, imaginary));
+    }
+
+    // Back substitute into the quasi-triangular form for its eigenvectors.
+    for n in (0..size).rev() {
+        p = real[n];
+        q = imaginary[n];
+        if q == 0f64 {
+            let mut l = n;
+            h[n][n] = 1f64;
+            for i in (0..n).rev() {
+                w = h[i][i] - p;
+                r = (l..=n).map(|j| h[i][j] * h[j][n]).sum();
+                if imaginary[i] < 0f64 {
+                    z = w;
+                    s = r;
+                } else {
+                    l = i;
+                    if imaginary[i] == 0f64 {
+                        h[i][n] = if w != 0f64 { -r / w } else { -r / (eps * norm) };
+                    } else {
+                        x = h[i][i + 1];
+                        y = h[i + 1][i];
+                        q = (real[i] - p) * (real[i] - p) + imaginary[i] * imaginary[i];
+                        t = (x * s - z * r) / q;
+                        h[i][n] = t;
+                        h[i + 1][n] = if x.abs() > z.abs() { (-r - w * t) / x } else { (-s - y * t) / z };
+                    }
+                    t = h[i][n].abs();
+                    if (eps * t) * t > 1f64 {
+                        for row in h.iter_mut().take(n + 1).skip(i) {
+                            row[n] /= t;
+                        }
+                    }
+                }
+            }
+        } else if q < 0f64 {
+            let mut l = n - 1;
+            if h[n][n - 1].abs() > h[n - 1][n].abs() {
+                h[n - 1][n - 1] = q / h[n][n - 1];
+                h[n - 1][n] = -(h[n][n] - p) / h[n][n - 1];
+            } else {
+                let val = Complex::new(0f64, -h[n - 1][n]) / Complex::new(h[n - 1][n - 1] - p, q);
+                h[n - 1][n - 1] = val.re;
+                h[n - 1][n] = val.im;
+            }
+            h[n][n - 1] = 0f64;
+            h[n][n] = 1f64;
+            for i in (0..n - 1).rev() {
+                let ra: f64 = (l..=n).map(|j| h[i][j] * h[j][n - 1]).sum();
+                let sa: f64 = (l..=n).map(|j| h[i][j] * h[j][n]).sum();
+                w = h[i][i] - p;
+                if imaginary[i] < 0f64 {
+                    z = w;
+                    r = ra;
+                    s = sa;
+                } else {
+                    l = i;
+                    if imaginary[i] == 0f64 {
+                        let val = Complex::new(-ra, -sa) / Complex::new(w, q);
+                        h[i][n - 1] = val.re;
+                        h[i][n] = val.im;
+                    } else {
+                        x = h[i][i + 1];
+                        y = h[i + 1][i];
+                        let mut vr = (real[i] - p) * (real[i] - p) + imaginary[i] * imaginary[i] - q * q;
+                        let vi = (real[i] - p) * 2f64 * q;
+                        if vr == 0f64 && vi == 0f64 {
+                            vr = eps * norm * (w.abs() + q.abs() + x.abs() + y.abs() + z.abs());
+                        }
+                        let val = Complex::new(x * r - z * ra + q * sa, x * s - z * sa - q * ra) / Complex::new(vr, vi);
+                        h[i][n - 1] = val.re;
+                        h[i][n] = val.im;
+                        if x.abs() > z.abs() + q.abs() {
+                            h[i + 1][n - 1] = (-ra - w * h[i][n - 1] + q * h[i][n]) / x;
+                            h[i + 1][n] = (-sa - w * h[i][n] - q * h[i][n - 1]) / x;
+                        } else {
+                            let val = Complex::new(-r - y * h[i][n - 1], -s - y * h[i][n]) / Complex::new(z, q);
+                            h[i + 1][n - 1] = val.re;
+                            h[i + 1][n] = val.im;
+                        }
+                    }
+                    t = h[i][n - 1].abs().max(h[i][n].abs());
+                    if (eps * t) * t > 1f64 {
+                        for row in h.iter_mut().take(n + 1).skip(i) {
+                            row[n - 1] /= t;
+                            row[n] /= t;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    for j in (0..size).rev() {
+        for row in v.iter_mut() {
+            let val: f64 = (0..=j).map(|k| row[k] * h[k][j]).sum();
+            row[j] = val;
+        }
+    }
+    return Ok((real, imaginary));
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::eq_solver::complex::Complex;
+    use crate::eq_solver::eigen::polynomial_roots;
+    use crate::eq_solver::matrix::{Matrix, MatrixError};
+
+    fn assert_eigenpairs(matrix: &Matrix) {
+        let eigen = matrix.eigen().unwrap();
+        assert_eq!(eigen.values.len(), matrix.rows);
+        for (value, vector) in eigen.values.iter().zip(&eigen.vectors) {
+            for row in 0..matrix.rows {
+                let mut product = Complex::default();
+                for column in 0..matrix.columns {
+                    product = product + Complex::from(matrix[(column, row)]) * vector[column];
+                }
+                let residual = (product - *value * vector[row]).norm();
+                assert!(residual < 1e-10, "{} has residual {}", value, residual);
+            }
+        }
+    }
+
+    #[test]
+    fn symmetric_eigenvalues_are_sorted() {
+        let matrix = Matrix::from_rows(&[[2.0, -1.0, 0.0], [-1.0, 2.0, -1.0], [0.0, -1.0, 2.0]]);
+        let eigen = matrix.symmetric_eigen();
+        let sqrt2 = 2f64.sqrt();
+        for (val, expected) in eigen.values.iter().zip([2.0 - sqrt2, 2.0, 2.0 + sqrt2]) {
+            assert!((val - expected).abs() < 1e-12);
+        }
+        assert_eigenpairs(&matrix);
+    }
+
+    #[test]
+    fn rotation_has_complex_eigenvalues() {
+        let matrix = Matrix::from_rows(&[[0.0, -1.0], [1.0, 0.0]]);
+        let values = matrix.eigenvalues().unwrap();
+        assert!((values[0] - Complex::new(0.0, 1.0)).norm() < 1e-12);
+        assert!((values[1] - Complex::new(0.0, -1.0)).norm() < 1e-12);
+        assert_eigenpairs(&matrix);
+    }
+
+    #[test]
+    fn general_matrix_eigenpairs() {
+        let matrix = Matrix::from_rows(&[
+            [4.0, -2.0, 1.0, 0.5],
+            [3.0, 6.0, -4.0, 2.0],
+            [2.0, 1.0, 8.0, -1.0],
+            [-1.0, 0.5, 2.0, 3.0],
+        ]);
+        assert_eigenpairs(&matrix);
+        let eigen = matrix.eigen().unwrap();
+        let trace: f64 = eigen.values.iter().map(|val| val.re).sum();
+        assert!((trace - 21.0).abs() < 1e-10);
+    }
+
+    #[test]
+    fn roots_of_quartic() {
+        // (x - 1)(x - 2)(x^2 + 1) = x^4 - 3x^3 + 3x^2 - 3x + 2
+        let mut roots = polynomial_roots(&[2.0, -3.0, 3.0, -3.0, 1.0]).unwrap();
+        roots.sort_by(|a, b| a.re.total_cmp(&b.re).then(a.im.total_cmp(&b.im)));
+        let expected = [Complex::new(0.0, -1.0), Complex::new(0.0, 1.0), Complex::new(1.0, 0.0), Complex::new(2.0, 0.0)];
+        for (root, expected) in roots.iter().zip(expected) {
+            assert!((*root - expected).norm() < 1e-10, "{}", root);
+        }
+    }
+
+    #[test]
+    fn gives_up_without_convergence() {
+        let matrix = Matrix::from_rows(&[[f64::NAN, 1.0, 2.0], [2.0, 3.0, 1.0], [0.0, 1.0, 1.0]]);
+        assert_eq!(matrix.eigen().err(), Some(MatrixError::NoConvergence));
+        assert_eq!(polynomial_roots(&[1.0, f64::NAN, 0.0, 1.0]).err(), Some(MatrixError::NoConvergence));
+    }
+}
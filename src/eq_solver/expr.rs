@@ -0,0 +1,576 @@
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+use crate::eq_solver::builtin::{builtin, Builtin};
+use crate::eq_solver::compile::{Instruction, Operator, Program};
+use crate::eq_solver::function::{Call, Function, Sum, Mul, Variable, Sub, Div, Pow, Constant};
+
+/// Index of an expression inside an `ExprArena`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ExprId(usize);
+
+impl ExprId {
+    pub fn index(&self) -> usize {
+        return self.0;
+    }
+}
+
+/// Index of an interned argument list inside an `ExprArena`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ArgumentsId(usize);
+
+/// Index of a called function inside an `ExprArena`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct FunctionId(usize);
+
+/// A single expression node. Children are referenced by id, so nodes are cheap to copy
+/// and can be matched on directly.
+#[derive(Clone, Copy, Debug)]
+pub enum Expr {
+    Constant(f64),
+    Variable(usize),
+    Sum(ExprId, ExprId),
+    Sub(ExprId, ExprId),
+    Mul(ExprId, ExprId),
+    Div(ExprId, ExprId),
+    Pow(ExprId, ExprId),
+    Call(FunctionId, ArgumentsId),
+}
+
+impl PartialEq for Expr {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Expr::Constant(left), Expr::Constant(right)) => left.to_bits() == right.to_bits(),
+            (Expr::Variable(left), Expr::Variable(right)) => left == right,
+            (Expr::Sum(a, b), Expr::Sum(c, d)) |
+            (Expr::Sub(a, b), Expr::Sub(c, d)) |
+            (Expr::Mul(a, b), Expr::Mul(c, d)) |
+            (Expr::Div(a, b), Expr::Div(c, d)) |
+            (Expr::Pow(a, b), Expr::Pow(c, d)) => a == c && b == d,
+            (Expr::Call(f, a), Expr::Call(g, b)) => f == g && a == b,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for Expr {}
+
+impl Hash for Expr {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            Expr::Constant(val) => val.to_bits().hash(state),
+            Expr::Variable(id) => id.hash(state),
+            Expr::Sum(a, b) | Expr::Sub(a, b) | Expr::Mul(a, b) |
+            Expr::Div(a, b) | Expr::Pow(a, b) => {
+                a.hash(state);
+                b.hash(state);
+            }
+            Expr::Call(function, arguments) => {
+                function.hash(state);
+                arguments.hash(state);
+            }
+        }
+    }
+}
+
+/// Owns expression nodes; expressions refer to each other through `ExprId`s.
+///
+/// Nodes are hash-consed: adding a node structurally equal to an existing one returns
+/// the existing id, so identical subexpressions are shared and expressions form a DAG.
+/// Argument lists of calls are interned the same way, and called functions by identity.
+#[derive(Clone, Default)]
+pub struct ExprArena {
+    nodes: Vec<Expr>,
+    ids: HashMap<Expr, ExprId>,
+    arguments: Vec<Vec<ExprId>>,
+    arguments_ids: HashMap<Vec<ExprId>, ArgumentsId>,
+    functions: Vec<Arc<Builtin>>,
+}
+
+impl ExprArena {
+    pub fn new() -> ExprArena {
+        ExprArena {
+            nodes: Vec::new(),
+            ids: HashMap::new(),
+            arguments: Vec::new(),
+            arguments_ids: HashMap::new(),
+            functions: Vec::new(),
+        }
+    }
+
+    pub fn add(&mut self, expr: Expr) -> ExprId {
+        if let Some(id) = self.ids.get(&expr) {
+            return *id;
+        }
+        let id = ExprId(self.nodes.len());
+        self.nodes.push(expr);
+        self.ids.insert(expr, id);
+        return id;
+    }
+
+    /// Adds an expression given as postfix instructions and returns its root.
+    pub fn add_code(&mut self, code: &[Instruction]) -> ExprId {
+        let mut stack = Vec::new();
+        for instruction in code {
+            let id = match instruction {
+                Instruction::Constant(val) => self.constant(*val),
+                Instruction::Variable(id) => self.add(Expr::Variable(*id)),
+                Instruction::Operator(Operator::Call(function)) => {
+                    let arguments = stack.split_off(stack.len() - function.arity);
+                    self.add_call(function, &arguments)
+                }
+                Instruction::Operator(operator) => {
+                    let right = stack.pop().unwrap();
+                    let left = stack.pop().unwrap();
+                    self.add(match operator {
+                        Operator::Sum => Expr::Sum(left, right),
+                        Operator::Sub => Expr::Sub(left, right),
+                        Operator::Mul => Expr::Mul(left, right),
+                        Operator::Div => Expr::Div(left, right),
+                        Operator::Pow => Expr::Pow(left, right),
+                        Operator::Call(_) => unreachable!(),
+                    })
+                }
+            };
+            stack.push(id);
+        }
+        return stack.pop().unwrap();
+    }
+
+    /// Adds a call of `function`, without folding constant arguments.
+    pub fn add_call(&mut self, function: &Arc<Builtin>, arguments: &[ExprId]) -> ExprId {
+        assert_eq!(function.arity, arguments.len());
+        let function = match self.functions.iter().position(|known| Arc::ptr_eq(known, function)) {
+            Some(index) => FunctionId(index),
+            None => {
+                self.functions.push(Arc::clone(function));
+                FunctionId(self.functions.len() - 1)
+            }
+        };
+        let arguments = match self.arguments_ids.get(arguments) {
+            Some(id) => *id,
+            None => {
+                let id = ArgumentsId(self.arguments.len());
+                self.arguments.push(arguments.to_vec());
+                self.arguments_ids.insert(arguments.to_vec(), id);
+                id
+            }
+        };
+        return self.add(Expr::Call(function, arguments));
+    }
+
+    pub fn arguments(&self, id: ArgumentsId) -> &[ExprId] {
+        return &self.arguments[id.0];
+    }
+
+    pub fn function(&self, id: FunctionId) -> &Arc<Builtin> {
+        return &self.functions[id.0];
+    }
+
+    pub fn len(&self) -> usize {
+        return self.nodes.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.nodes.is_empty();
+    }
+
+    pub fn output(&self, id: ExprId, variables: &[f64]) -> f64 {
+        return match self[id] {
+            Expr::Constant(val) => val,
+            Expr::Variable(variable) => variables[variable],
+            Expr::Sum(a, b) => self.output(a, variables) + self.output(b, variables),
+            Expr::Sub(a, b) => self.output(a, variables) - self.output(b, variables),
+            Expr::Mul(a, b) => self.output(a, variables) * self.output(b, variables),
+            Expr::Div(a, b) => self.output(a, variables) / self.output(b, variables),
+            Expr::Pow(a, b) => self.output(a, variables).powf(self.output(b, variables)),
+            Expr::Call(function, arguments) => {
+                let inputs: Vec<f64> = self.arguments(arguments).iter().map(|id| self.output(*id, variables)).collect();
+                (self.function(function).evaluate)(&inputs)
+            }
+        };
+    }
+
+    pub fn depends_on(&self, id: ExprId, variable: usize) -> bool {
+        return match self[id] {
+            Expr::Constant(_) => false,
+            Expr::Variable(id) => id == variable,
+            Expr::Sum(a, b) | Expr::Sub(a, b) | Expr::Mul(a, b) |
+            Expr::Div(a, b) | Expr::Pow(a, b) => {
+                self.depends_on(a, variable) || self.depends_on(b, variable)
+            }
+            Expr::Call(_, arguments) => self.arguments(arguments).iter().any(|id| self.depends_on(*id, variable)),
+        };
+    }
+
+    /// Rebuilds the expression bottom-up, folding constants and dropping neutral elements.
+    ///
+    /// The rules of `sum`, `mul`, `div` and `pow` assume finite operands, so the result can
+    /// differ where the original is not finite: `0 / x` becomes `0` even at `x = 0`, where the
+    /// original is NaN, and `x * 0` becomes `0` even for infinite `x`.
+    pub fn simplify(&mut self, id: ExprId) -> ExprId {
+        return self.simplify_memo(id, &mut HashMap::new());
+    }
+
+    fn simplify_memo(&mut self, id: ExprId, memo: &mut HashMap<ExprId, ExprId>) -> ExprId {
+        if let Some(simplified) = memo.get(&id) {
+            return *simplified;
+        }
+        let simplified = match self[id] {
+            Expr::Constant(_) | Expr::Variable(_) => id,
+            Expr::Sum(a, b) => {
+                let (a, b) = (self.simplify_memo(a, memo), self.simplify_memo(b, memo));
+                self.sum(a, b)
+            }
+            Expr::Sub(a, b) => {
+                let (a, b) = (self.simplify_memo(a, memo), self.simplify_memo(b, memo));
+                self.sub(a, b)
+            }
+            Expr::Mul(a, b) => {
+                let (a, b) = (self.simplify_memo(a, memo), self.simplify_memo(b, memo));
+                self.mul(a, b)
+            }
+            Expr::Div(a, b) => {
+                let (a, b) = (self.simplify_memo(a, memo), self.simplify_memo(b, memo));
+                self.div(a, b)
+            }
+            Expr::Pow(a, b) => {
+                let (a, b) = (self.simplify_memo(a, memo), self.simplify_memo(b, memo));
+                self.pow(a, b)
+            }
+            Expr::Call(function, arguments) => {
+                let arguments: Vec<ExprId> = self.arguments(arguments)
+                    .to_vec()
+                    .into_iter()
+                    .map(|id| self.simplify_memo(id, memo))
+                    .collect();
+                let function = Arc::clone(self.function(function));
+                self.call(&function, &arguments)
+            }
+        };
+        memo.insert(id, simplified);
+        return simplified;
+    }
+
+    /// Symbolic derivative of `id` with respect to the variable with the given slot.
+    pub fn derivative(&mut self, id: ExprId, variable: usize) -> ExprId {
+        return self.derivative_memo(id, variable, &mut HashMap::new());
+    }
+
+    fn derivative_memo(&mut self, id: ExprId, variable: usize, memo: &mut HashMap<ExprId, ExprId>) -> ExprId {
+        if let Some(derivative) = memo.get(&id) {
+            return *derivative;
+        }
+        let derivative = match self[id] {
+            Expr::Constant(_) => self.constant(0f64),
+            Expr::Variable(id) => self.constant(if id == variable { 1f64 } else { 0f64 }),
+            Expr::Sum(a, b) => {
+                let (da, db) = (self.derivative_memo(a, variable, memo), self.derivative_memo(b, variable, memo));
+                self.sum(da, db)
+            }
+            Expr::Sub(a, b) => {
+                let (da, db) = (self.derivative_memo(a, variable, memo), self.derivative_memo(b, variable, memo));
+                self.sub(da, db)
+            }
+            Expr::Mul(a, b) => {
+                let (da, db) = (self.derivative_memo(a, variable, memo), self.derivative_memo(b, variable, memo));
+                let left = self.mul(da, b);
+                let right = self.mul(a, db);
+                self.sum(left, right)
+            }
+            Expr::Div(a, b) => {
+                let (da, db) = (self.derivative_memo(a, variable, memo), self.derivative_memo(b, variable, memo));
+                let left = self.mul(da, b);
+                let right = self.mul(a, db);
+                let numerator = self.sub(left, right);
+                let two = self.constant(2f64);
+                let denominator = self.pow(b, two);
+                self.div(numerator, denominator)
+            }
+            Expr::Pow(a, b) => {
+                let da = self.derivative_memo(a, variable, memo);
+                if self.depends_on(b, variable) {
+                    // d(a^b) = a^b * (b' * ln(a) + b * a' / a)
+                    let db = self.derivative_memo(b, variable, memo);
+                    let ln_a = self.ln(a);
+                    let left = self.mul(db, ln_a);
+                    let b_da = self.mul(b, da);
+                    let right = self.div(b_da, a);
+                    let inner = self.sum(left, right);
+                    self.mul(id, inner)
+                } else {
+                    // d(a^c) = c * a^(c - 1) * a'
+                    let one = self.constant(1f64);
+                    let exponent = self.sub(b, one);
+                    let power = self.pow(a, exponent);
+                    let scaled = self.mul(b, power);
+                    self.mul(scaled, da)
+                }
+            }
+            Expr::Call(function, arguments) => {
+                // Chain rule, summing the partial derivatives times the derivatives of the arguments.
+                let function = Arc::clone(self.function(function));
+                let arguments = self.arguments(arguments).to_vec();
+                let mut derivative = self.constant(0f64);
+                for (i, argument) in arguments.iter().enumerate() {
+                    let da = self.derivative_memo(*argument, variable, memo);
+                    if self.constant_value(da) == Some(0f64) {
+                        continue;
+                    }
+                    let partial = (function.derivative)(self, &arguments, i);
+                    let term = self.mul(partial, da);
+                    derivative = self.sum(derivative, term);
+                }
+                derivative
+            }
+        };
+        memo.insert(id, derivative);
+        return derivative;
+    }
+
+    pub fn compile(&self, id: ExprId) -> Program {
+        return Program::from_arena(self, id);
+    }
+
+    /// Compiles the expression, or builds its tree when the program would be slower.
+    pub fn to_solvable(&self, id: ExprId) -> Box<dyn Function> {
+        let program = self.compile(id);
+        if program.is_trivial() {
+            return self.to_function(id);
+        }
+        return Box::new(program);
+    }
+
+    /// Builds the equivalent trait object tree.
+    pub fn to_function(&self, id: ExprId) -> Box<dyn Function> {
+        return match self[id] {
+            Expr::Constant(val) => Box::new(Constant { val }),
+            Expr::Variable(id) => Box::new(Variable { id }),
+            Expr::Sum(a, b) => Box::new(Sum { left: self.to_function(a), right: self.to_function(b) }),
+            Expr::Sub(a, b) => Box::new(Sub { left: self.to_function(a), right: self.to_function(b) }),
+            Expr::Mul(a, b) => Box::new(Mul { left: self.to_function(a), right: self.to_function(b) }),
+            Expr::Div(a, b) => Box::new(Div { left: self.to_function(a), right: self.to_function(b) }),
+            Expr::Pow(a, b) => Box::new(Pow { base: self.to_function(a), power: self.to_function(b) }),
+            Expr::Call(function, arguments) => Box::new(Call {
+                function: Arc::clone(self.function(function)),
+                inputs: self.arguments(arguments).iter().map(|id| self.to_function(*id)).collect(),
+            }),
+        };
+    }
+
+    pub fn children(&self, id: ExprId) -> Vec<ExprId> {
+        return match self[id] {
+            Expr::Constant(_) | Expr::Variable(_) => vec![],
+            Expr::Sum(a, b) | Expr::Sub(a, b) | Expr::Mul(a, b) |
+            Expr::Div(a, b) | Expr::Pow(a, b) => vec![a, b],
+            Expr::Call(_, arguments) => self.arguments(arguments).to_vec(),
+        };
+    }
+
+    pub fn constant(&mut self, val: f64) -> ExprId {
+        return self.add(Expr::Constant(val));
+    }
+
+    fn constant_value(&self, id: ExprId) -> Option<f64> {
+        return match self[id] {
+            Expr::Constant(val) => Some(val),
+            _ => None,
+        };
+    }
+
+    /// Adds `expr`, replacing it by a constant when all of its inputs are constants.
+    fn fold(&mut self, expr: Expr) -> ExprId {
+        let id = self.add(expr);
+        return self.fold_constants(id);
+    }
+
+    fn fold_constants(&mut self, id: ExprId) -> ExprId {
+        if self.children(id).iter().all(|child| self.constant_value(*child).is_some()) {
+            let val = self.output(id, &[]);
+            return self.constant(val);
+        }
+        return id;
+    }
+
+    /// Adds `a + b`, dropping a zero operand. Like the other constructors below, this folds
+    /// constants and assumes finite operands, see `simplify`.
+    pub fn sum(&mut self, a: ExprId, b: ExprId) -> ExprId {
+        return match (self.constant_value(a), self.constant_value(b)) {
+            (Some(0.0), _) => b,
+            (_, Some(0.0)) => a,
+            _ => self.fold(Expr::Sum(a, b)),
+        };
+    }
+
+    pub fn sub(&mut self, a: ExprId, b: ExprId) -> ExprId {
+        return match self.constant_value(b) {
+            Some(0.0) => a,
+            _ => self.fold(Expr::Sub(a, b)),
+        };
+    }
+
+    /// Adds `a * b`, which is `0` when either operand is `0`, even if the other is infinite.
+    pub fn mul(&mut self, a: ExprId, b: ExprId) -> ExprId {
+        return match (self.constant_value(a), self.constant_value(b)) {
+            (Some(0.0), _) | (_, Some(0.0)) => self.constant(0f64),
+            (Some(1.0), _) => b,
+            (_, Some(1.0)) => a,
+            _ => self.fold(Expr::Mul(a, b)),
+        };
+    }
+
+    /// Adds `a / b`, which is `0` when `a` is `0`, even if `b` is `0` as well.
+    pub fn div(&mut self, a: ExprId, b: ExprId) -> ExprId {
+        return match (self.constant_value(a), self.constant_value(b)) {
+            (Some(0.0), _) => a,
+            (_, Some(1.0)) => a,
+            _ => self.fold(Expr::Div(a, b)),
+        };
+    }
+
+    pub fn pow(&mut self, a: ExprId, b: ExprId) -> ExprId {
+        return match self.constant_value(b) {
+            Some(0.0) => self.constant(1f64),
+            Some(1.0) => a,
+            _ => self.fold(Expr::Pow(a, b)),
+        };
+    }
+
+    /// Adds a call of `function`, evaluating it right away when every argument is a constant.
+    pub fn call(&mut self, function: &Arc<Builtin>, arguments: &[ExprId]) -> ExprId {
+        let id = self.add_call(function, arguments);
+        return self.fold_constants(id);
+    }
+
+    fn ln(&mut self, a: ExprId) -> ExprId {
+        return self.call(builtin("ln", 1), &[a]);
+    }
+}
+
+impl std::ops::Index<ExprId> for ExprArena {
+    type Output = Expr;
+
+    fn index(&self, index: ExprId) -> &Self::Output {
+        return &self.nodes[index.0];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::eq_solver::builtin::builtin;
+    use crate::eq_solver::expr::{Expr, ExprArena};
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+
+    fn hash(expr: &Expr) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        expr.hash(&mut hasher);
+        return hasher.finish();
+    }
+
+    #[test]
+    fn compares_nodes_by_structure_and_bits() {
+        let mut arena = ExprArena::new();
+        let (x, y) = (arena.add(Expr::Variable(0)), arena.add(Expr::Variable(1)));
+        assert_eq!(Expr::Sum(x, y), Expr::Sum(x, y));
+        assert_eq!(hash(&Expr::Sum(x, y)), hash(&Expr::Sum(x, y)));
+        assert_ne!(Expr::Sum(x, y), Expr::Sum(y, x));
+        assert_ne!(Expr::Sum(x, y), Expr::Sub(x, y));
+        assert_ne!(Expr::Mul(x, y), Expr::Div(x, y));
+        assert_ne!(Expr::Variable(0), Expr::Constant(0.0));
+        // Constants compare by bits, so NaN equals itself and the zeros differ.
+        assert_eq!(Expr::Constant(f64::NAN), Expr::Constant(f64::NAN));
+        assert_eq!(hash(&Expr::Constant(f64::NAN)), hash(&Expr::Constant(f64::NAN)));
+        assert_ne!(Expr::Constant(0.0), Expr::Constant(-0.0));
+        assert_eq!(arena.add(Expr::Constant(f64::NAN)), arena.add(Expr::Constant(f64::NAN)));
+        assert_ne!(arena.add(Expr::Constant(0.0)), arena.add(Expr::Constant(-0.0)));
+        let (sin, cos) = (builtin("sin", 1), builtin("cos", 1));
+        assert_eq!(arena.add_call(sin, &[x]), arena.add_call(sin, &[x]));
+        assert_ne!(arena.add_call(sin, &[x]), arena.add_call(cos, &[x]));
+        assert_ne!(arena.add_call(sin, &[x]), arena.add_call(sin, &[y]));
+    }
+
+    #[test]
+    fn simplifies_neutral_elements_and_constants() {
+        let mut arena = ExprArena::new();
+        let x = arena.add(Expr::Variable(0));
+        let (zero, one, two, three) = (arena.constant(0.0), arena.constant(1.0), arena.constant(2.0), arena.constant(3.0));
+        let rules = [
+            (Expr::Sum(x, zero), x),
+            (Expr::Sum(zero, x), x),
+            (Expr::Sub(x, zero), x),
+            (Expr::Mul(x, one), x),
+            (Expr::Mul(one, x), x),
+            (Expr::Mul(x, zero), zero),
+            (Expr::Mul(zero, x), zero),
+            (Expr::Div(x, one), x),
+            (Expr::Div(zero, x), zero),
+            (Expr::Pow(x, zero), one),
+            (Expr::Pow(x, one), x),
+        ];
+        for (expr, expected) in rules {
+            let id = arena.add(expr);
+            assert_eq!(arena.simplify(id), expected, "{:?}", expr);
+        }
+        let product = arena.add(Expr::Mul(two, three));
+        let sum = arena.add(Expr::Sum(x, product));
+        let simplified = arena.simplify(sum);
+        let six = arena.constant(6.0);
+        assert_eq!(arena[simplified], Expr::Sum(x, six));
+        let call = arena.add_call(builtin("cos", 1), &[zero]);
+        assert_eq!(arena.simplify(call), one);
+        // Nested rules apply bottom up: (x * 1 + 0) ^ 1 is x.
+        let product = arena.add(Expr::Mul(x, one));
+        let sum = arena.add(Expr::Sum(product, zero));
+        let power = arena.add(Expr::Pow(sum, one));
+        assert_eq!(arena.simplify(power), x);
+    }
+
+    #[test]
+    fn simplifying_assumes_finite_operands() {
+        let mut arena = ExprArena::new();
+        let x = arena.add(Expr::Variable(0));
+        let zero = arena.constant(0.0);
+        let quotient = arena.add(Expr::Div(zero, x));
+        let product = arena.add(Expr::Mul(x, zero));
+        assert!(arena.output(quotient, &[0.0]).is_nan());
+        assert!(arena.output(product, &[f64::INFINITY]).is_nan());
+        let (quotient, product) = (arena.simplify(quotient), arena.simplify(product));
+        assert_eq!(arena.output(quotient, &[0.0]), 0.0);
+        assert_eq!(arena.output(product, &[f64::INFINITY]), 0.0);
+    }
+
+    #[test]
+    fn derivatives_match_finite_differences() {
+        let formulas = [
+            ("x^3*y-x/y", [1.3, 0.7]),
+            ("x^y+y^x", [1.3, 0.7]),
+            ("sin(x*y)/(1+x^2)", [0.4, -1.2]),
+            ("log(x,y)*exp(0-x)", [2.5, 3.5]),
+            ("atan2(y,x)+hypot(x,y)", [0.8, -0.3]),
+            ("(x-y)^2/sqrt(x)", [2.0, 0.5]),
+        ];
+        for (formula, point) in formulas {
+            let expression = crate::parse(formula, &["x", "y"]).unwrap();
+            for i in 0..2 {
+                let derivative = expression.derivative(i);
+                let step = 1e-6;
+                let (mut above, mut below) = (point, point);
+                above[i] += step;
+                below[i] -= step;
+                let expected = (expression.evaluate(&above) - expression.evaluate(&below)) / (2f64 * step);
+                let actual = derivative.evaluate(&point);
+                assert!((actual - expected).abs() < 1e-6 * expected.abs().max(1f64), "{} by {}: {} != {}", formula, i, actual, expected);
+            }
+        }
+        let mut arena = ExprArena::new();
+        let x = arena.add(Expr::Variable(0));
+        let y = arena.add(Expr::Variable(1));
+        let product = arena.add(Expr::Mul(x, y));
+        // Derivatives by a variable that does not appear are the constant zero.
+        let unrelated = arena.derivative(product, 2);
+        assert_eq!(arena[unrelated], Expr::Constant(0.0));
+        assert_eq!(arena.derivative(product, 0), y);
+    }
+}
@@ -1,8 +1,19 @@
-use std::collections::HashMap;
-use crate::doodlang::parser::FunctionManager;
+use std::sync::Arc;
+use crate::eq_solver::builtin::Builtin;
+use crate::eq_solver::compile::{Instruction, Operator};
 
-pub trait Function {
-    fn output(&mut self, eq: &Box<FunctionManager>) -> f64;
+pub type Evaluator<'a> = Box<dyn FnMut(&[f64]) -> f64 + 'a>;
+
+pub trait Function: Send + Sync {
+    fn output(&self, variables: &[f64]) -> f64;
+
+    /// Appends the instructions evaluating this node in postfix order.
+    fn compile(&self, code: &mut Vec<Instruction>);
+
+    /// Returns an evaluator for repeated calls, which may reuse work from the previous call.
+    fn evaluator(&self) -> Evaluator<'_> {
+        return Box::new(move |variables| self.output(variables));
+    }
 }
 
 pub struct Variable {
@@ -10,8 +21,12 @@ pub struct Variable {
 }
 
 impl Function for Variable {
-    fn output(&mut self, eq: &Box<FunctionManager>) -> f64 {
-        eq.variables[self.id]
+    fn output(&self, variables: &[f64]) -> f64 {
+        variables[self.id]
+    }
+
+    fn compile(&self, code: &mut Vec<Instruction>) {
+        code.push(Instruction::Variable(self.id));
     }
 }
 
@@ -20,9 +35,13 @@ pub struct Constant {
 }
 
 impl Function for Constant {
-    fn output(&mut self, _eq: &Box<FunctionManager>) -> f64 {
+    fn output(&self, _variables: &[f64]) -> f64 {
         self.val
     }
+
+    fn compile(&self, code: &mut Vec<Instruction>) {
+        code.push(Instruction::Constant(self.val));
+    }
 }
 
 
@@ -32,8 +51,14 @@ pub struct Sum {
 }
 
 impl Function for Sum {
-    fn output(&mut self, eq: &Box<FunctionManager>) -> f64 {
-        return self.left.output(eq) + self.right.output(eq);
+    fn output(&self, variables: &[f64]) -> f64 {
+        return self.left.output(variables) + self.right.output(variables);
+    }
+
+    fn compile(&self, code: &mut Vec<Instruction>) {
+        self.left.compile(code);
+        self.right.compile(code);
+        code.push(Instruction::Operator(Operator::Sum));
     }
 }
 
@@ -44,8 +69,14 @@ pub struct Sub {
 }
 
 impl Function for Sub {
-    fn output(&mut self, eq: &Box<FunctionManager>) -> f64 {
-        return self.left.output(eq) - self.right.output(eq);
+    fn output(&self, variables: &[f64]) -> f64 {
+        return self.left.output(variables) - self.right.output(variables);
+    }
+
+    fn compile(&self, code: &mut Vec<Instruction>) {
+        self.left.compile(code);
+        self.right.compile(code);
+        code.push(Instruction::Operator(Operator::Sub));
     }
 }
 
@@ -56,8 +87,14 @@ pub struct Mul {
 }
 
 impl Function for Mul {
-    fn output(&mut self, eq: &Box<FunctionManager>) -> f64 {
-        return self.left.output(eq) * self.right.output(eq);
+    fn output(&self, variables: &[f64]) -> f64 {
+        return self.left.output(variables) * self.right.output(variables);
+    }
+
+    fn compile(&self, code: &mut Vec<Instruction>) {
+        self.left.compile(code);
+        self.right.compile(code);
+        code.push(Instruction::Operator(Operator::Mul));
     }
 }
 
@@ -67,8 +104,14 @@ pub struct Div {
 }
 
 impl Function for Div {
-    fn output(&mut self, eq: &Box<FunctionManager>) -> f64 {
-        return self.left.output(eq) / self.right.output(eq);
+    fn output(&self, variables: &[f64]) -> f64 {
+        return self.left.output(variables) / self.right.output(variables);
+    }
+
+    fn compile(&self, code: &mut Vec<Instruction>) {
+        self.left.compile(code);
+        self.right.compile(code);
+        code.push(Instruction::Operator(Operator::Div));
     }
 }
 
@@ -78,48 +121,32 @@ pub struct Pow {
 }
 
 impl Function for Pow {
-    fn output(&mut self, eq: &Box<FunctionManager>) -> f64 {
-        return self.base.output(eq).powf(self.power.output(eq));
-    }
-}
-
-pub struct Log {
-    pub base: Box<dyn Function>,
-    pub input: Box<dyn Function>,
-}
-
-impl Function for Log {
-    fn output(&mut self, eq: &Box<FunctionManager>) -> f64 {
-        return self.input.output(eq).log(self.base.output(eq));
+    fn output(&self, variables: &[f64]) -> f64 {
+        return self.base.output(variables).powf(self.power.output(variables));
     }
-}
-
-pub struct Ln {
-    pub input: Box<dyn Function>,
-}
 
-impl Function for Ln {
-    fn output(&mut self, eq: &Box<FunctionManager>) -> f64 {
-        return self.input.output(eq).ln();
+    fn compile(&self, code: &mut Vec<Instruction>) {
+        self.base.compile(code);
+        self.power.compile(code);
+        code.push(Instruction::Operator(Operator::Pow));
     }
 }
 
-pub struct Sine {
-    pub input: Box<dyn Function>,
+pub struct Call {
+    pub function: Arc<Builtin>,
+    pub inputs: Vec<Box<dyn Function>>,
 }
 
-impl Function for Sine {
-    fn output(&mut self, eq: &Box<FunctionManager>) -> f64 {
-        return self.input.output(eq).sin();
+impl Function for Call {
+    fn output(&self, variables: &[f64]) -> f64 {
+        let inputs: Vec<f64> = self.inputs.iter().map(|input| input.output(variables)).collect();
+        return (self.function.evaluate)(&inputs);
     }
-}
-
-pub struct Cosine {
-    pub input: Box<dyn Function>,
-}
 
-impl Function for Cosine {
-    fn output(&mut self, eq: &Box<FunctionManager>) -> f64 {
-        return self.input.output(eq).cos();
+    fn compile(&self, code: &mut Vec<Instruction>) {
+        for input in &self.inputs {
+            input.compile(code);
+        }
+        code.push(Instruction::Operator(Operator::Call(Arc::clone(&self.function))));
     }
 }
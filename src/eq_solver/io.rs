@@ -0,0 +1,363 @@
+use crate::eq_solver::matrix::Matrix;
+use crate::eq_solver::scalar::Scalar;
+use crate::eq_solver::sparse::{CooMatrix, CsrMatrix};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+#[derive(Debug)]
+pub enum MatrixIoError {
+    Io(std::io::Error),
+    /// A malformed line, numbered from one.
+    Parse { line: usize, message: String },
+}
+
+impl fmt::Display for MatrixIoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match self {
+            MatrixIoError::Io(err) => write!(f, "{}", err),
+            MatrixIoError::Parse { line, message } => write!(f, "line {}: {}", line, message),
+        };
+    }
+}
+
+impl From<std::io::Error> for MatrixIoError {
+    fn from(err: std::io::Error) -> Self {
+        return MatrixIoError::Io(err);
+    }
+}
+
+fn parse_error<T>(line: usize, message: String) -> Result<T, MatrixIoError> {
+    return Err(MatrixIoError::Parse { line, message });
+}
+
+fn parse_value(line: usize, text: &str) -> Result<f64, MatrixIoError> {
+    return match text.trim().parse::<f64>() {
+        Ok(val) => Ok(val),
+        Err(_) => parse_error(line, format!("not a number: {}", text.trim())),
+    };
+}
+
+fn parse_index(line: usize, text: &str, size: usize) -> Result<usize, MatrixIoError> {
+    return match text.parse::<usize>() {
+        Ok(index) if index >= 1 && index <= size => Ok(index - 1),
+        _ => parse_error(line, format!("index out of range: {}", text)),
+    };
+}
+
+/// Prints one row per line with the columns right aligned, honouring the precision, e.g. `{:.3}`.
+impl<T: Scalar + fmt::Display> fmt::Display for Matrix<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let cells: Vec<String> = self.values
+            .iter()
+            .map(|val| match f.precision() {
+                Some(precision) => format!("{:.*}", precision, val),
+                None => format!("{}", val),
+            })
+            .collect();
+        let mut widths = vec![0; self.columns];
+        for (i, cell) in cells.iter().enumerate() {
+            let column = i % self.columns;
+            widths[column] = widths[column].max(cell.chars().count());
+        }
+        for row in 0..self.rows {
+            if row > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "[")?;
+            for column in 0..self.columns {
+                if column > 0 {
+                    write!(f, " ")?;
+                }
+                write!(f, "{:>width$}", cells[column + row * self.columns], width = widths[column])?;
+            }
+            write!(f, "]")?;
+        }
+        return Ok(());
+    }
+}
+
+impl fmt::Display for CsrMatrix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return fmt::Display::fmt(&self.to_dense(), f);
+    }
+}
+
+impl Matrix {
+    /// One line per row, values separated by commas. Blank lines are skipped.
+    pub fn from_csv(text: &str) -> Result<Matrix, MatrixIoError> {
+        let mut rows: Vec<Vec<f64>> = Vec::new();
+        for (i, line) in text.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let row = line.split(',').map(|cell| parse_value(i + 1, cell)).collect::<Result<Vec<f64>, _>>()?;
+            if let Some(first) = rows.first() {
+                if first.len() != row.len() {
+                    return parse_error(i + 1, format!("expected {} values, found {}", first.len(), row.len()));
+                }
+            }
+            rows.push(row);
+        }
+        return Ok(Matrix::from_rows(&rows));
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::new();
+        for row in 0..self.rows {
+            let cells: Vec<String> = self.row(row).values.iter().map(|val| val.to_string()).collect();
+            csv += &cells.join(",");
+            csv += "\n";
+        }
+        return csv;
+    }
+
+    pub fn read_csv<P: AsRef<Path>>(path: P) -> Result<Matrix, MatrixIoError> {
+        return Matrix::from_csv(&fs::read_to_string(path)?);
+    }
+
+    pub fn write_csv<P: AsRef<Path>>(&self, path: P) -> Result<(), MatrixIoError> {
+        return Ok(fs::write(path, self.to_csv())?);
+    }
+
+    /// Reads a Matrix Market file in either array or coordinate format.
+    pub fn from_matrix_market(text: &str) -> Result<Matrix, MatrixIoError> {
+        return Ok(CooMatrix::from_matrix_market(text)?.to_dense());
+    }
+
+    /// Writes the array format, which lists the values column by column.
+    pub fn to_matrix_market(&self) -> String {
+        let mut text = String::from("%%MatrixMarket matrix array real general\n");
+        text += &format!("{} {}\n", self.rows, self.columns);
+        for column in 0..self.columns {
+            for row in 0..self.rows {
+                text += &format!("{}\n", self[(column, row)]);
+            }
+        }
+        return text;
+    }
+
+    pub fn read_matrix_market<P: AsRef<Path>>(path: P) -> Result<Matrix, MatrixIoError> {
+        return Matrix::from_matrix_market(&fs::read_to_string(path)?);
+    }
+
+    pub fn write_matrix_market<P: AsRef<Path>>(&self, path: P) -> Result<(), MatrixIoError> {
+        return Ok(fs::write(path, self.to_matrix_market())?);
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Symmetry {
+    General,
+    Symmetric,
+    SkewSymmetric,
+}
+
+impl CooMatrix {
+    /// Reads a real, integer or pattern Matrix Market file in array or coordinate format,
+    /// expanding symmetric storage to both triangles.
+    pub fn from_matrix_market(text: &str) -> Result<CooMatrix, MatrixIoError> {
+        let mut lines = text.lines().enumerate();
+        let header = match lines.next() {
+            Some((_, header)) => header.to_lowercase(),
+            None => return parse_error(1, "empty file".to_string()),
+        };
+        let fields: Vec<&str> = header.split_whitespace().collect();
+        if fields.len() != 5 || fields[0] != "%%matrixmarket" || fields[1] != "matrix" {
+            return parse_error(1, format!("not a Matrix Market matrix header: {}", header));
+        }
+        let coordinate = match fields[2] {
+            "coordinate" => true,
+            "array" => false,
+            format => return parse_error(1, format!("unknown format: {}", format)),
+        };
+        let pattern = match fields[3] {
+            "real" | "integer" | "double" => false,
+            "pattern" if coordinate => true,
+            field => return parse_error(1, format!("unsupported field: {}", field)),
+        };
+        let symmetry = match fields[4] {
+            "general" => Symmetry::General,
+            "symmetric" => Symmetry::Symmetric,
+            "skew-symmetric" => Symmetry::SkewSymmetric,
+            symmetry => return parse_error(1, format!("unsupported symmetry: {}", symmetry)),
+        };
+        let mut data = lines.filter(|(_, line)| !line.trim().is_empty() && !line.starts_with('%'));
+        let (size_line, sizes) = match data.next() {
+            Some((i, line)) => (i + 1, line.split_whitespace().collect::<Vec<&str>>()),
+            None => return parse_error(1, "missing size line".to_string()),
+        };
+        let expected_sizes = if coordinate { 3 } else { 2 };
+        if sizes.len() != expected_sizes {
+            return parse_error(size_line, format!("expected {} sizes", expected_sizes));
+        }
+        let mut parsed = Vec::with_capacity(sizes.len());
+        for size in &sizes {
+            match size.parse::<usize>() {
+                Ok(size) => parsed.push(size),
+                Err(_) => return parse_error(size_line, format!("not a size: {}", size)),
+            }
+        }
+        let (rows, columns) = (parsed[0], parsed[1]);
+        let mut coo = CooMatrix::new(columns, rows);
+        let mut push = |column: usize, row: usize, value: f64| {
+            coo.entries.push((column, row, value));
+            if column != row {
+                match symmetry {
+                    Symmetry::General => {}
+                    Symmetry::Symmetric => coo.entries.push((row, column, value)),
+                    Symmetry::SkewSymmetric => coo.entries.push((row, column, -value)),
+                }
+            }
+        };
+        let mut count = 0;
+        if coordinate {
+            for (i, line) in data {
+                let fields: Vec<&str> = line.split_whitespace().collect();
+                if fields.len() != if pattern { 2 } else { 3 } {
+                    return parse_error(i + 1, format!("malformed entry: {}", line));
+                }
+                let row = parse_index(i + 1, fields[0], rows)?;
+                let column = parse_index(i + 1, fields[1], columns)?;
+                let value = if pattern { 1f64 } else { parse_value(i + 1, fields[2])? };
+                push(column, row, value);
+                count += 1;
+            }
+            if count != parsed[2] {
+                return parse_error(size_line, format!("expected {} entries, found {}", parsed[2], count));
+            }
+        } else {
+            // Array values go down the columns; symmetric storage only holds the lower triangle,
+            // skew-symmetric storage only the part below the diagonal, which is zero.
+            let mut positions = (0..columns).flat_map(|column| {
+                let start = match symmetry {
+                    Symmetry::General => 0,
+                    Symmetry::Symmetric => column,
+                    Symmetry::SkewSymmetric => column + 1,
+                };
+                (start..rows).map(move |row| (column, row))
+            });
+            for (i, line) in data {
+                let (column, row) = match positions.next() {
+                    Some(position) => position,
+                    None => return parse_error(i + 1, "too many values".to_string()),
+                };
+                let value = parse_value(i + 1, line)?;
+                if value != 0f64 {
+                    push(column, row, value);
+                }
+                count += 1;
+            }
+            if positions.next().is_some() {
+                return parse_error(size_line, format!("too few values, found {}", count));
+            }
+        }
+        return Ok(coo);
+    }
+
+    /// Writes the general coordinate format.
+    pub fn to_matrix_market(&self) -> String {
+        let mut text = String::from("%%MatrixMarket matrix coordinate real general\n");
+        text += &format!("{} {} {}\n", self.rows, self.columns, self.entries.len());
+        for (column, row, value) in &self.entries {
+            text += &format!("{} {} {}\n", row + 1, column + 1, value);
+        }
+        return text;
+    }
+
+    pub fn read_matrix_market<P: AsRef<Path>>(path: P) -> Result<CooMatrix, MatrixIoError> {
+        return CooMatrix::from_matrix_market(&fs::read_to_string(path)?);
+    }
+
+    pub fn write_matrix_market<P: AsRef<Path>>(&self, path: P) -> Result<(), MatrixIoError> {
+        return Ok(fs::write(path, self.to_matrix_market())?);
+    }
+}
+
+impl CsrMatrix {
+    pub fn from_matrix_market(text: &str) -> Result<CsrMatrix, MatrixIoError> {
+        return Ok(CooMatrix::from_matrix_market(text)?.to_csr());
+    }
+
+    pub fn to_matrix_market(&self) -> String {
+        return self.to_coo().to_matrix_market();
+    }
+
+    pub fn read_matrix_market<P: AsRef<Path>>(path: P) -> Result<CsrMatrix, MatrixIoError> {
+        return CsrMatrix::from_matrix_market(&fs::read_to_string(path)?);
+    }
+
+    pub fn write_matrix_market<P: AsRef<Path>>(&self, path: P) -> Result<(), MatrixIoError> {
+        return Ok(fs::write(path, self.to_matrix_market())?);
+    }
+
+    /// Reads a dense CSV file, keeping only the non-zero values.
+    pub fn from_csv(text: &str) -> Result<CsrMatrix, MatrixIoError> {
+        return Ok(CsrMatrix::from_dense(&Matrix::from_csv(text)?));
+    }
+
+    pub fn to_csv(&self) -> String {
+        return self.to_dense().to_csv();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::eq_solver::io::MatrixIoError;
+    use crate::eq_solver::matrix::Matrix;
+    use crate::eq_solver::sparse::{CooMatrix, CsrMatrix};
+
+    #[test]
+    fn display_aligns_columns() {
+        let matrix = Matrix::from_rows(&[[1.0, -2.5, 3.0], [10.0, 0.0, -300.0]]);
+        assert_eq!(matrix.to_string(), "[ 1 -2.5    3]\n[10    0 -300]");
+        assert_eq!(format!("{:.1}", matrix), "[ 1.0 -2.5    3.0]\n[10.0  0.0 -300.0]");
+    }
+
+    #[test]
+    fn csv_round_trip() {
+        let matrix = Matrix::from_rows(&[[1.5, 2.0], [-3.0, 1e-20]]);
+        assert_eq!(matrix.to_csv(), "1.5,2\n-3,0.00000000000000000001\n");
+        assert_eq!(Matrix::from_csv(&matrix.to_csv()).unwrap(), matrix);
+        assert_eq!(Matrix::from_csv("1, 2\n\n3, 4\n").unwrap(), Matrix::from_rows(&[[1.0, 2.0], [3.0, 4.0]]));
+        match Matrix::from_csv("1,2\n3\n") {
+            Err(MatrixIoError::Parse { line, .. }) => assert_eq!(line, 2),
+            _ => panic!("ragged rows should not parse"),
+        }
+        let sparse = CsrMatrix::from_csv("0,1\n2,0\n").unwrap();
+        assert_eq!(sparse.non_zeros(), 2);
+        assert_eq!(sparse.to_csv(), "0,1\n2,0\n");
+    }
+
+    #[test]
+    fn matrix_market_round_trip() {
+        let matrix = Matrix::from_rows(&[[1.0, 0.0, 2.0], [0.0, 3.0, 0.0]]);
+        let array = matrix.to_matrix_market();
+        assert_eq!(array, "%%MatrixMarket matrix array real general\n2 3\n1\n0\n0\n3\n2\n0\n");
+        assert_eq!(Matrix::from_matrix_market(&array).unwrap(), matrix);
+        let sparse = CsrMatrix::from_dense(&matrix);
+        let coordinate = sparse.to_matrix_market();
+        assert_eq!(coordinate, "%%MatrixMarket matrix coordinate real general\n2 3 3\n1 1 1\n1 3 2\n2 2 3\n");
+        assert_eq!(CsrMatrix::from_matrix_market(&coordinate).unwrap(), sparse);
+        assert_eq!(Matrix::from_matrix_market(&coordinate).unwrap(), matrix);
+    }
+
+    #[test]
+    fn matrix_market_symmetric_storage() {
+        let text = "%%MatrixMarket matrix coordinate real symmetric\n% comment\n3 3 4\n1 1 4\n2 1 -1\n2 2 4\n3 2 -1\n";
+        let expected = Matrix::from_rows(&[[4.0, -1.0, 0.0], [-1.0, 4.0, -1.0], [0.0, -1.0, 0.0]]);
+        assert_eq!(Matrix::from_matrix_market(text).unwrap(), expected);
+        let array = "%%MatrixMarket matrix array real symmetric\n2 2\n1\n2\n3\n";
+        assert_eq!(Matrix::from_matrix_market(array).unwrap(), Matrix::from_rows(&[[1.0, 2.0], [2.0, 3.0]]));
+        let skew = "%%MatrixMarket matrix array real skew-symmetric\n3 3\n1\n2\n3\n";
+        let expected = Matrix::from_rows(&[[0.0, -1.0, -2.0], [1.0, 0.0, -3.0], [2.0, 3.0, 0.0]]);
+        let matrix = Matrix::from_matrix_market(skew).unwrap();
+        assert_eq!(matrix, expected);
+        assert_eq!(Matrix::from_matrix_market(&matrix.to_matrix_market()).unwrap(), expected);
+        assert!(Matrix::from_matrix_market("%%MatrixMarket matrix array real skew-symmetric\n3 3\n1\n2\n3\n4\n").is_err());
+        let pattern = "%%MatrixMarket matrix coordinate pattern general\n2 2 1\n2 1\n";
+        assert_eq!(CooMatrix::from_matrix_market(pattern).unwrap().entries, vec![(0, 1, 1.0)]);
+        assert!(Matrix::from_matrix_market("%%MatrixMarket matrix coordinate real general\n2 2 2\n1 1 1\n").is_err());
+        assert!(Matrix::from_matrix_market("%%MatrixMarket matrix coordinate real general\n2 2 1\n3 1 1\n").is_err());
+    }
+}
@@ -0,0 +1,413 @@
+use crate::eq_solver::matrix::MatrixError;
+use crate::eq_solver::sparse::CsrMatrix;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Preconditioner {
+    None,
+    /// Divides by the diagonal.
+    Jacobi,
+    /// Incomplete LU factorization keeping the sparsity pattern of the matrix.
+    Ilu,
+}
+
+pub struct IterativeSolution {
+    pub x: Vec<f64>,
+    pub iterations: usize,
+    /// Residual norm relative to the norm of the right hand side.
+    pub residual: f64,
+    pub converged: bool,
+}
+
+/// A preconditioner built for one matrix, approximating its inverse.
+enum Factorization {
+    Identity,
+    Jacobi(Vec<f64>),
+    /// `L` (unit diagonal) and `U` in the sparsity pattern of the matrix, with the position of every diagonal entry.
+    Ilu(CsrMatrix, Vec<usize>),
+}
+
+impl Factorization {
+    fn new(preconditioner: Preconditioner, a: &CsrMatrix) -> Result<Factorization, MatrixError> {
+        return match preconditioner {
+            Preconditioner::None => Ok(Factorization::Identity),
+            Preconditioner::Jacobi => {
+                let diagonal = a.diagonal();
+                if diagonal.contains(&0f64) {
+                    return Err(MatrixError::Singular);
+                }
+                Ok(Factorization::Jacobi(diagonal.iter().map(|val| 1f64 / val).collect()))
+            }
+            Preconditioner::Ilu => ilu(a),
+        };
+    }
+
+    /// Writes `M⁻¹ * r` into `z`.
+    fn apply(&self, r: &[f64], z: &mut [f64]) {
+        match self {
+            Factorization::Identity => z.copy_from_slice(r),
+            Factorization::Jacobi(inverse) => {
+                for ((z, r), inverse) in z.iter_mut().zip(r).zip(inverse) {
+                    *z = r * inverse;
+                }
+            }
+            Factorization::Ilu(lu, diagonal) => {
+                for row in 0..lu.rows {
+                    let mut val = r[row];
+                    for index in lu.row_offsets[row]..diagonal[row] {
+                        val -= lu.values[index] * z[lu.column_indices[index]];
+                    }
+                    z[row] = val;
+                }
+                for row in (0..lu.rows).rev() {
+                    let mut val = z[row];
+                    for index in diagonal[row] + 1..lu.row_offsets[row + 1] {
+                        val -= lu.values[index] * z[lu.column_indices[index]];
+                    }
+                    z[row] = val / lu.values[diagonal[row]];
+                }
+            }
+        }
+    }
+}
+
+fn ilu(a: &CsrMatrix) -> Result<Factorization, MatrixError> {
+    assert_eq!(a.rows, a.columns);
+    let mut lu = a.clone();
+    let mut diagonal = Vec::with_capacity(a.rows);
+    for row in 0..a.rows {
+        match lu.column_indices[lu.row_range(row)].binary_search(&row) {
+            Ok(index) => diagonal.push(lu.row_offsets[row] + index),
+            Err(_) => return Err(MatrixError::Singular),
+        }
+    }
+    let mut positions = vec![usize::MAX; a.columns];
+    for row in 0..a.rows {
+        for index in lu.row_range(row) {
+            positions[lu.column_indices[index]] = index;
+        }
+        for index in lu.row_offsets[row]..diagonal[row] {
+            let k = lu.column_indices[index];
+            let pivot = lu.values[diagonal[k]];
+            if pivot == 0f64 {
+                return Err(MatrixError::Singular);
+            }
+            lu.values[index] /= pivot;
+            let factor = lu.values[index];
+            for k_index in diagonal[k] + 1..lu.row_offsets[k + 1] {
+                let position = positions[lu.column_indices[k_index]];
+                if position != usize::MAX {
+                    lu.values[position] -= factor * lu.values[k_index];
+                }
+            }
+        }
+        for index in lu.row_range(row) {
+            positions[lu.column_indices[index]] = usize::MAX;
+        }
+        if lu.values[diagonal[row]] == 0f64 {
+            return Err(MatrixError::Singular);
+        }
+    }
+    return Ok(Factorization::Ilu(lu, diagonal));
+}
+
+/// Preconditioned conjugate gradient, for symmetric positive definite matrices.
+pub struct ConjugateGradient {
+    tolerance: f64,
+    max_iterations: usize,
+    preconditioner: Preconditioner,
+}
+
+impl ConjugateGradient {
+    pub fn new(tolerance: f64, max_iterations: usize, preconditioner: Preconditioner) -> ConjugateGradient {
+        ConjugateGradient {
+            tolerance,
+            max_iterations,
+            preconditioner,
+        }
+    }
+
+    pub fn solve(&self, a: &CsrMatrix, b: &[f64]) -> Result<IterativeSolution, MatrixError> {
+        let preconditioner = Factorization::new(self.preconditioner, a)?;
+        let size = b.len();
+        let b_norm = norm(b);
+        let mut x = vec![0f64; size];
+        let mut r = b.to_vec();
+        let mut z = vec![0f64; size];
+        preconditioner.apply(&r, &mut z);
+        let mut p = z.clone();
+        let mut ap = vec![0f64; size];
+        let mut rz = dot(&r, &z);
+        let mut iterations = 0;
+        while iterations < self.max_iterations && !converged(&r, b_norm, self.tolerance) {
+            a.mul_vec_into(&p, &mut ap);
+            let alpha = rz / dot(&p, &ap);
+            axpy(alpha, &p, &mut x);
+            axpy(-alpha, &ap, &mut r);
+            preconditioner.apply(&r, &mut z);
+            let rz_next = dot(&r, &z);
+            let beta = rz_next / rz;
+            rz = rz_next;
+            for (p, z) in p.iter_mut().zip(&z) {
+                *p = z + beta * *p;
+            }
+            iterations += 1;
+        }
+        return Ok(solution(a, b, x, iterations, self.tolerance));
+    }
+}
+
+/// Restarted GMRES with right preconditioning, for general matrices.
+pub struct Gmres {
+    tolerance: f64,
+    max_iterations: usize,
+    restart: usize,
+    preconditioner: Preconditioner,
+}
+
+impl Gmres {
+    pub fn new(tolerance: f64, max_iterations: usize, restart: usize, preconditioner: Preconditioner) -> Gmres {
+        Gmres {
+            tolerance,
+            max_iterations,
+            restart: restart.max(1),
+            preconditioner,
+        }
+    }
+
+    pub fn solve(&self, a: &CsrMatrix, b: &[f64]) -> Result<IterativeSolution, MatrixError> {
+        let preconditioner = Factorization::new(self.preconditioner, a)?;
+        let size = b.len();
+        let b_norm = norm(b);
+        let mut x = vec![0f64; size];
+        let mut r = b.to_vec();
+        let mut z = vec![0f64; size];
+        let mut iterations = 0;
+        while iterations < self.max_iterations && !converged(&r, b_norm, self.tolerance) {
+            let beta = norm(&r);
+            let mut basis = vec![r.iter().map(|val| val / beta).collect::<Vec<f64>>()];
+            // Column `j` of the Hessenberg matrix, already rotated to upper triangular form.
+            let mut hessenberg: Vec<Vec<f64>> = Vec::with_capacity(self.restart);
+            let mut rotations: Vec<(f64, f64)> = Vec::with_capacity(self.restart);
+            let mut g = vec![beta];
+            for j in 0..self.restart {
+                preconditioner.apply(&basis[j], &mut z);
+                let mut w = a.mul_vec(&z);
+                let mut column = Vec::with_capacity(j + 2);
+                for v in &basis {
+                    let h = dot(&w, v);
+                    axpy(-h, v, &mut w);
+                    column.push(h);
+                }
+                let w_norm = norm(&w);
+                column.push(w_norm);
+                for (i, (c, s)) in rotations.iter().enumerate() {
+                    let (h0, h1) = (column[i], column[i + 1]);
+                    column[i] = c * h0 + s * h1;
+                    column[i + 1] = -s * h0 + c * h1;
+                }
+                let denominator = column[j].hypot(column[j + 1]);
+                let (c, s) = if denominator == 0f64 { (1f64, 0f64) } else { (column[j] / denominator, column[j + 1] / denominator) };
+                column[j] = denominator;
+                column.pop();
+                rotations.push((c, s));
+                g.push(-s * g[j]);
+                g[j] *= c;
+                hessenberg.push(column);
+                iterations += 1;
+                if w_norm == 0f64 || g[j + 1].abs() <= self.tolerance * b_norm || iterations >= self.max_iterations {
+                    break;
+                }
+                basis.push(w.iter().map(|val| val / w_norm).collect());
+            }
+            let k = hessenberg.len();
+            let mut y = vec![0f64; k];
+            for i in (0..k).rev() {
+                let mut val = g[i];
+                for (j, column) in hessenberg.iter().enumerate().skip(i + 1) {
+                    val -= column[i] * y[j];
+                }
+                y[i] = val / hessenberg[i][i];
+            }
+            let mut update = vec![0f64; size];
+            for (y, v) in y.iter().zip(&basis) {
+                axpy(*y, v, &mut update);
+            }
+            preconditioner.apply(&update, &mut z);
+            axpy(1f64, &z, &mut x);
+            r = residual(a, b, &x);
+        }
+        return Ok(solution(a, b, x, iterations, self.tolerance));
+    }
+}
+
+/// Stabilized biconjugate gradient with right preconditioning, for general matrices.
+pub struct BiCgStab {
+    tolerance: f64,
+    max_iterations: usize,
+    preconditioner: Preconditioner,
+}
+
+impl BiCgStab {
+    pub fn new(tolerance: f64, max_iterations: usize, preconditioner: Preconditioner) -> BiCgStab {
+        BiCgStab {
+            tolerance,
+            max_iterations,
+            preconditioner,
+        }
+    }
+
+    pub fn solve(&self, a: &CsrMatrix, b: &[f64]) -> Result<IterativeSolution, MatrixError> {
+        let preconditioner = Factorization::new(self.preconditioner, a)?;
+        let size = b.len();
+        let b_norm = norm(b);
+        let mut x = vec![0f64; size];
+        let mut r = b.to_vec();
+        let r_hat = r.clone();
+        let (mut rho, mut alpha, mut omega) = (1f64, 1f64, 1f64);
+        let mut v = vec![0f64; size];
+        let mut p = vec![0f64; size];
+        let mut y = vec![0f64; size];
+        let mut z = vec![0f64; size];
+        let mut t = vec![0f64; size];
+        let mut iterations = 0;
+        while iterations < self.max_iterations && !converged(&r, b_norm, self.tolerance) {
+            let rho_next = dot(&r_hat, &r);
+            if rho_next == 0f64 || omega == 0f64 {
+                break;
+            }
+            let beta = (rho_next / rho) * (alpha / omega);
+            rho = rho_next;
+            for ((p, r), v) in p.iter_mut().zip(&r).zip(&v) {
+                *p = r + beta * (*p - omega * v);
+            }
+            preconditioner.apply(&p, &mut y);
+            a.mul_vec_into(&y, &mut v);
+            alpha = rho / dot(&r_hat, &v);
+            axpy(alpha, &y, &mut x);
+            axpy(-alpha, &v, &mut r);
+            iterations += 1;
+            if converged(&r, b_norm, self.tolerance) {
+                break;
+            }
+            preconditioner.apply(&r, &mut z);
+            a.mul_vec_into(&z, &mut t);
+            omega = dot(&t, &r) / dot(&t, &t);
+            axpy(omega, &z, &mut x);
+            axpy(-omega, &t, &mut r);
+        }
+        return Ok(solution(a, b, x, iterations, self.tolerance));
+    }
+}
+
+fn dot(a: &[f64], b: &[f64]) -> f64 {
+    return a.iter().zip(b).map(|(a, b)| a * b).sum();
+}
+
+fn norm(a: &[f64]) -> f64 {
+    return dot(a, a).sqrt();
+}
+
+/// `y += alpha * x`
+fn axpy(alpha: f64, x: &[f64], y: &mut [f64]) {
+    for (y, x) in y.iter_mut().zip(x) {
+        *y += alpha * x;
+    }
+}
+
+fn residual(a: &CsrMatrix, b: &[f64], x: &[f64]) -> Vec<f64> {
+    let mut r = a.mul_vec(x);
+    for (r, b) in r.iter_mut().zip(b) {
+        *r = b - *r;
+    }
+    return r;
+}
+
+fn converged(r: &[f64], b_norm: f64, tolerance: f64) -> bool {
+    return norm(r) <= tolerance * b_norm;
+}
+
+/// Reports the true residual of `x`, not the recurrence the solver tracked.
+fn solution(a: &CsrMatrix, b: &[f64], x: Vec<f64>, iterations: usize, tolerance: f64) -> IterativeSolution {
+    let b_norm = norm(b);
+    let r = norm(&residual(a, b, &x));
+    let residual = if b_norm == 0f64 { r } else { r / b_norm };
+    IterativeSolution {
+        x,
+        iterations,
+        residual,
+        converged: residual <= tolerance,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::eq_solver::iterative::{BiCgStab, ConjugateGradient, Gmres, IterativeSolution, Preconditioner};
+    use crate::eq_solver::matrix::MatrixError;
+    use crate::eq_solver::sparse::{CooMatrix, CsrMatrix};
+
+    const PRECONDITIONERS: [Preconditioner; 3] = [Preconditioner::None, Preconditioner::Jacobi, Preconditioner::Ilu];
+
+    /// Tridiagonal `[-1 - c, 2 + d, -1 + c]`, symmetric when `c` is zero.
+    fn tridiagonal(size: usize, c: f64, d: f64) -> CsrMatrix {
+        let mut coo = CooMatrix::new(size, size);
+        for i in 0..size {
+            coo.push(i, i, 2.0 + d);
+            if i > 0 {
+                coo.push(i - 1, i, -1.0 - c);
+            }
+            if i + 1 < size {
+                coo.push(i + 1, i, -1.0 + c);
+            }
+        }
+        return coo.to_csr();
+    }
+
+    fn assert_solves(a: &CsrMatrix, b: &[f64], solution: Result<IterativeSolution, MatrixError>) {
+        let solution = solution.unwrap();
+        assert!(solution.converged, "residual {} after {} iterations", solution.residual, solution.iterations);
+        let ax = a.mul_vec(&solution.x);
+        for (ax, b) in ax.iter().zip(b) {
+            assert!((ax - b).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn conjugate_gradient_solves_poisson() {
+        let a = tridiagonal(100, 0.0, 0.0);
+        let b: Vec<f64> = (0..100).map(|i| (i as f64 * 0.1).sin()).collect();
+        for preconditioner in PRECONDITIONERS {
+            assert_solves(&a, &b, ConjugateGradient::new(1e-10, 500, preconditioner).solve(&a, &b));
+        }
+    }
+
+    #[test]
+    fn ilu_is_exact_for_tridiagonal() {
+        let a = tridiagonal(50, 0.3, 0.5);
+        let b = vec![1.0; 50];
+        let solution = Gmres::new(1e-12, 10, 5, Preconditioner::Ilu).solve(&a, &b).unwrap();
+        assert!(solution.converged);
+        assert_eq!(solution.iterations, 1);
+    }
+
+    #[test]
+    fn nonsymmetric_solvers() {
+        let a = tridiagonal(80, 0.4, 0.2);
+        let b: Vec<f64> = (0..80).map(|i| 1.0 + (i % 7) as f64).collect();
+        for preconditioner in PRECONDITIONERS {
+            assert_solves(&a, &b, Gmres::new(1e-10, 1000, 30, preconditioner).solve(&a, &b));
+            assert_solves(&a, &b, BiCgStab::new(1e-10, 1000, preconditioner).solve(&a, &b));
+        }
+    }
+
+    #[test]
+    fn zero_diagonal_cannot_be_preconditioned() {
+        let mut coo = CooMatrix::new(2, 2);
+        coo.push(1, 0, 1.0);
+        coo.push(0, 1, 1.0);
+        let a = coo.to_csr();
+        let b = [1.0, 2.0];
+        assert!(BiCgStab::new(1e-10, 10, Preconditioner::Jacobi).solve(&a, &b).is_err());
+        assert!(Gmres::new(1e-10, 10, 2, Preconditioner::Ilu).solve(&a, &b).is_err());
+        assert_solves(&a, &b, Gmres::new(1e-10, 10, 2, Preconditioner::None).solve(&a, &b));
+    }
+}
@@ -0,0 +1,210 @@
+use crate::eq_solver::matrix::{Matrix, MatrixError};
+use crate::eq_solver::scalar::Scalar;
+
+/// LU decomposition with partial pivoting, `P * A = L * U`.
+///
+/// `L` (unit diagonal, not stored) and `U` share one matrix in the usual compact form. Exact
+/// scalars such as `Rational` only count zero pivots as singular.
+pub struct LuDecomposition<T: Scalar = f64> {
+    pub lu: Matrix<T>,
+    /// Row of `A` that ended up in each row of `L * U`.
+    pub pivots: Vec<usize>,
+    /// Sign of the row permutation.
+    pub sign: T,
+    /// Whether a pivot vanished next to the other entries of its row in `A`.
+    pub singular: bool,
+}
+
+impl<T: Scalar> LuDecomposition<T> {
+    /// Decomposes `matrix`, failing if it is singular.
+    pub fn new(matrix: &Matrix<T>) -> Result<LuDecomposition<T>, MatrixError> {
+        let lu = LuDecomposition::factor(matrix);
+        if lu.singular {
+            return Err(MatrixError::Singular);
+        }
+        return Ok(lu);
+    }
+
+    /// Decomposes `matrix` even if it is singular, leaving columns without a pivot uneliminated.
+    pub fn factor(matrix: &Matrix<T>) -> LuDecomposition<T> {
+        assert_eq!(matrix.rows, matrix.columns);
+        let size = matrix.rows;
+        let mut lu = Matrix {
+            values: matrix.values.clone(),
+            rows: size,
+            columns: size,
+        };
+        let mut pivots: Vec<usize> = (0..size).collect();
+        let mut sign = T::one();
+        let mut singular = false;
+        // Pivots are judged against their own row, so rows of very different sizes do not make
+        // a well conditioned matrix look singular.
+        let tolerances: Vec<f64> = (0..size)
+            .map(|j| (0..size).fold(0f64, |max, i| max.max(matrix[(i, j)].magnitude())) * size as f64 * T::epsilon())
+            .collect();
+        for k in 0..size {
+            let mut pivot = k;
+            for j in k + 1..size {
+                if lu[(k, j)].magnitude() > lu[(k, pivot)].magnitude() {
+                    pivot = j;
+                }
+            }
+            let largest = lu[(k, pivot)].magnitude();
+            if !largest.is_finite() || largest <= tolerances[pivots[pivot]] {
+                singular = true;
+            }
+            if pivot != k {
+                for i in 0..size {
+                    lu.values.swap(i + k * size, i + pivot * size);
+                }
+                pivots.swap(k, pivot);
+                sign = -sign;
+            }
+            let diagonal = lu[(k, k)];
+            if diagonal == T::zero() {
+                continue;
+            }
+            for j in k + 1..size {
+                let factor = lu[(k, j)] / diagonal;
+                lu[(k, j)] = factor;
+                if factor != T::zero() {
+                    for i in k + 1..size {
+                        let val = lu[(i, k)];
+                        lu[(i, j)] = lu[(i, j)] - factor * val;
+                    }
+                }
+            }
+        }
+        return LuDecomposition {
+            lu,
+            pivots,
+            sign,
+            singular,
+        };
+    }
+
+    pub fn determinant(&self) -> T {
+        let mut determinant = self.sign;
+        for i in 0..self.lu.rows {
+            determinant = determinant * self.lu[(i, i)];
+        }
+        return determinant;
+    }
+
+    /// Solves `A * x = b` for every column of `b`.
+    pub fn solve(&self, b: &Matrix<T>) -> Matrix<T> {
+        let size = self.lu.rows;
+        assert_eq!(b.rows, size);
+        let mut x = Matrix::zeros(b.columns, size);
+        for column in 0..b.columns {
+            for j in 0..size {
+                let mut val = b[(column, self.pivots[j])];
+                for k in 0..j {
+                    val = val - self.lu[(k, j)] * x[(column, k)];
+                }
+                x[(column, j)] = val;
+            }
+            for j in (0..size).rev() {
+                let mut val = x[(column, j)];
+                for k in j + 1..size {
+                    val = val - self.lu[(k, j)] * x[(column, k)];
+                }
+                x[(column, j)] = val / self.lu[(j, j)];
+            }
+        }
+        return x;
+    }
+
+    pub fn inverse(&self) -> Matrix<T> {
+        return self.solve(&Matrix::identity(self.lu.rows));
+    }
+}
+
+impl<T: Scalar> Matrix<T> {
+    pub fn lu(&self) -> Result<LuDecomposition<T>, MatrixError> {
+        return LuDecomposition::new(self);
+    }
+
+    pub fn solve(&self, b: &Matrix<T>) -> Result<Matrix<T>, MatrixError> {
+        return Ok(self.lu()?.solve(b));
+    }
+
+    pub fn inverse(&self) -> Result<Matrix<T>, MatrixError> {
+        return Ok(self.lu()?.inverse());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::eq_solver::matrix::{Matrix, MatrixError};
+    use crate::eq_solver::rational::Rational;
+
+    fn assert_close(actual: &Matrix, expected: &Matrix, tolerance: f64) {
+        assert_eq!((actual.columns, actual.rows), (expected.columns, expected.rows));
+        for (val, expected) in actual.values.iter().zip(&expected.values) {
+            assert!((val - expected).abs() <= tolerance * expected.abs().max(1f64), "{:?} != {:?}", actual, expected);
+        }
+    }
+
+    #[test]
+    fn solves_and_inverts() {
+        let matrix: Matrix = Matrix::from_rows(&[[0.0, 2.0, 1.0], [1.0, 1.0, 0.0], [3.0, 0.0, 1.0]]);
+        let lu = matrix.lu().unwrap();
+        assert!((lu.determinant() - matrix.determinant()).abs() < 1e-12);
+        assert!((matrix.determinant() + 5f64).abs() < 1e-12);
+        let b = Matrix::from_rows(&[[3.0], [2.0], [4.0]]);
+        assert_close(&matrix.solve(&b).unwrap(), &Matrix::from_rows(&[[1.0], [1.0], [1.0]]), 1e-12);
+        let inverse = matrix.inverse().unwrap();
+        assert_close(&(&matrix * &inverse), &Matrix::identity(3), 1e-12);
+    }
+
+    #[test]
+    fn handles_badly_scaled_rows() {
+        let matrix = Matrix::from_rows(&[[1e20, 0.0], [0.0, 1.0]]);
+        assert_eq!(matrix.determinant(), 1e20);
+        assert_close(&matrix.inverse().unwrap(), &Matrix::from_rows(&[[1e-20, 0.0], [0.0, 1.0]]), 1e-12);
+        let b = Matrix::from_rows(&[[2e20], [3.0]]);
+        assert_close(&matrix.solve(&b).unwrap(), &Matrix::from_rows(&[[2.0], [3.0]]), 1e-12);
+    }
+
+    #[test]
+    fn rejects_singular_matrices() {
+        let rank_two: Matrix = Matrix::from_rows(&[[1.0, 2.0, 3.0], [4.0, 5.0, 6.0], [7.0, 8.0, 9.0]]);
+        assert_eq!(rank_two.lu().err(), Some(MatrixError::Singular));
+        assert_eq!(rank_two.inverse().err(), Some(MatrixError::Singular));
+        assert!(rank_two.determinant().abs() < 1e-12);
+        let zero_row = Matrix::from_rows(&[[1.0, 2.0], [0.0, 0.0]]);
+        assert_eq!(zero_row.solve(&Matrix::from_rows(&[[1.0], [1.0]])).err(), Some(MatrixError::Singular));
+        assert_eq!(zero_row.determinant(), 0f64);
+        assert_eq!(Matrix::from_rows(&[[f64::NAN]]).lu().err(), Some(MatrixError::Singular));
+    }
+
+    #[test]
+    fn solves_exactly_over_rationals() {
+        let hilbert = Matrix::from_rows(&[
+            [Rational::new(1, 1), Rational::new(1, 2), Rational::new(1, 3)],
+            [Rational::new(1, 2), Rational::new(1, 3), Rational::new(1, 4)],
+            [Rational::new(1, 3), Rational::new(1, 4), Rational::new(1, 5)],
+        ]);
+        assert_eq!(hilbert.determinant(), Rational::new(1, 2160));
+        let inverse = hilbert.inverse().unwrap();
+        assert_eq!(inverse[(1, 1)], Rational::integer(192));
+        assert_eq!(&hilbert * &inverse, Matrix::identity(3));
+        let b = Matrix::from_rows(&[[Rational::integer(1)], [Rational::integer(0)], [Rational::integer(0)]]);
+        assert_eq!(hilbert.solve(&b).unwrap(), inverse.column(0));
+        // Tiny but non-zero pivots are exact, so only a true zero is singular.
+        let tiny = Matrix::from_rows(&[[Rational::new(1, 1 << 60), Rational::integer(0)], [Rational::integer(0), Rational::integer(1 << 60)]]);
+        assert_eq!(tiny.determinant(), Rational::integer(1));
+        let singular = Matrix::from_rows(&[[Rational::integer(1), Rational::new(1, 3)], [Rational::integer(3), Rational::integer(1)]]);
+        assert_eq!(singular.lu().err(), Some(MatrixError::Singular));
+        assert_eq!(singular.determinant(), Rational::integer(0));
+    }
+
+    #[test]
+    fn decomposes_single_precision() {
+        let matrix = Matrix::from_rows(&[[4f32, 3f32], [6f32, 3f32]]);
+        assert_eq!(matrix.determinant(), -6f32);
+        let x = matrix.solve(&Matrix::from_rows(&[[10f32], [12f32]])).unwrap();
+        assert!((x[(0, 0)] - 1f32).abs() < 1e-6 && (x[(0, 1)] - 2f32).abs() < 1e-6);
+    }
+}
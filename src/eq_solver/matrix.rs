@@ -1,33 +1,108 @@
-use std::ops::{Mul, Index, Neg};
-use crate::print_vec;
+use crate::eq_solver::lu::LuDecomposition;
+use crate::eq_solver::scalar::Scalar;
+use std::fmt;
+use std::thread;
+
+/// Side of the square tiles of the right hand side used by the product.
+const MULTIPLY_BLOCK: usize = 64;
+/// Scalar multiplications above which a product is split between threads.
+const PARALLEL_MULTIPLICATIONS: usize = 1 << 18;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MatrixError {
+    Singular,
+    NotPositiveDefinite,
+    /// An iterative decomposition did not converge within its iteration limit.
+    NoConvergence,
+}
 
+impl fmt::Display for MatrixError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match self {
+            MatrixError::Singular => write!(f, "matrix is singular"),
+            MatrixError::NotPositiveDefinite => write!(f, "matrix is not positive definite"),
+            MatrixError::NoConvergence => write!(f, "decomposition did not converge"),
+        };
+    }
+}
 
-pub struct Matrix {
-    pub values: Vec<f64>,
+/// A dense matrix stored row by row, of `f64` unless another `Scalar` is given.
+///
+/// Elements are indexed as `(column, row)` and constructors take the number of columns first,
+/// so `values[column + row * columns]` is `matrix[(column, row)]`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Matrix<T: Scalar = f64> {
+    pub values: Vec<T>,
     pub rows: usize,
     pub columns: usize,
 }
 
-impl Matrix {
-    pub fn determinant(&self) -> f64 {
-        assert_eq!(self.rows, self.columns);
-        return if self.columns == 1 {
-            self[(0, 0)]
-        } else if self.columns == 2 {
-            self[(0, 0)] * self[(1, 1)] - self[(0, 1)] * self[(1, 0)]
-        } else {
-            let mut sign = 1f64;
-            let mut determinant = 0f64;
-            for i in 0..self.columns {
-                determinant += sign * self[(i, 0)] * self.get_minor(i, 0).determinant();
-                sign *= -1f64;
-            }
-            determinant
-        };
+impl<T: Scalar> Matrix<T> {
+    /// Determinant from the LU decomposition, also of matrices too close to singular to invert.
+    pub fn determinant(&self) -> T {
+        return LuDecomposition::factor(self).determinant();
+    }
+
+    pub fn zeros(columns: usize, rows: usize) -> Matrix<T> {
+        Matrix {
+            values: vec!(T::zero(); rows * columns),
+            rows,
+            columns,
+        }
+    }
+
+    pub fn fill(columns: usize, rows: usize, value: T) -> Matrix<T> {
+        Matrix {
+            values: vec!(value; rows * columns),
+            rows,
+            columns,
+        }
     }
 
+    pub fn identity(size: usize) -> Matrix<T> {
+        let mut identity = Matrix::zeros(size, size);
+        for i in 0..size {
+            identity[(i, i)] = T::one();
+        }
+        return identity;
+    }
 
-    pub fn transpose(&self) -> Matrix {
+    /// Builds a matrix from equally long rows.
+    pub fn from_rows<R: AsRef<[T]>>(rows: &[R]) -> Matrix<T> {
+        let columns = rows.first().map_or(0, |row| row.as_ref().len());
+        let mut values = Vec::with_capacity(rows.len() * columns);
+        for row in rows {
+            assert_eq!(row.as_ref().len(), columns);
+            values.extend_from_slice(row.as_ref());
+        }
+        Matrix {
+            values,
+            rows: rows.len(),
+            columns,
+        }
+    }
+
+    /// A single row as a `columns x 1` matrix.
+    pub fn row(&self, row: usize) -> Matrix<T> {
+        assert!(row < self.rows);
+        Matrix {
+            values: self.values[row * self.columns..(row + 1) * self.columns].to_vec(),
+            rows: 1,
+            columns: self.columns,
+        }
+    }
+
+    /// A single column as a `1 x rows` matrix.
+    pub fn column(&self, column: usize) -> Matrix<T> {
+        assert!(column < self.columns);
+        Matrix {
+            values: (0..self.rows).map(|row| self[(column, row)]).collect(),
+            rows: self.rows,
+            columns: 1,
+        }
+    }
+
+    pub fn transpose(&self) -> Matrix<T> {
         let mut transposed = Matrix::zeros(self.rows, self.columns);
         for i in 0..self.rows {
             for j in 0..self.columns {
@@ -37,10 +112,11 @@ impl Matrix {
         return transposed;
     }
 
-    pub fn get_minor(&self, column: usize, row: usize) -> Matrix {
-        let mut sub: Vec<f64> = Vec::with_capacity(self.values.len() - self.rows - self.columns + 1);
-        for i in 0..self.columns {
-            for j in 0..self.rows {
+    /// The matrix without the given column and row.
+    pub fn get_minor(&self, column: usize, row: usize) -> Matrix<T> {
+        let mut sub: Vec<T> = Vec::with_capacity((self.rows - 1) * (self.columns - 1));
+        for j in 0..self.rows {
+            for i in 0..self.columns {
                 if i != column && j != row {
                     sub.push(self[(i, j)]);
                 }
@@ -53,7 +129,8 @@ impl Matrix {
         }
     }
 
-    pub fn sub_matrix(&self, start: [usize; 2], end: [usize; 2]) -> Matrix {
+    /// The block from `start` up to, not including, `end`, both given as `[column, row]`.
+    pub fn sub_matrix(&self, start: [usize; 2], end: [usize; 2]) -> Matrix<T> {
         let columns = end[0] - start[0];
         let rows = end[1] - start[1];
         let mut sub_matrix = Matrix {
@@ -61,282 +138,401 @@ impl Matrix {
             rows,
             columns,
         };
-        for i in start[0]..end[0] {
-            for j in start[1]..end[1] {
-                sub_matrix.values.push(self[(i, j)]);
-            }
+        for j in start[1]..end[1] {
+            sub_matrix.values.extend_from_slice(&self.values[start[0] + j * self.columns..end[0] + j * self.columns]);
         }
         return sub_matrix;
     }
 
-    pub fn matrix_of_minors(&self) -> (Matrix, f64) {
-        assert_eq!(self.rows, self.columns);
-        return if self.columns == 1 {
-            (self.sub_matrix([0, 0], [1, 1]), self[(0, 0)])
-        } else {
-            let mut minors = Matrix {
-                values: Vec::with_capacity(self.columns * self.rows),
-                columns: self.columns,
-                rows: self.rows,
-            };
-            let mut determinant = 0f64;
-            let mut sign = 1f64;
-            let mut cur_det;
-            for i in 0..self.columns {
-                cur_det = self.get_minor(i, 0).determinant();
-                determinant += sign * self[(i, 0)] * cur_det;
-                sign *= -1f64;
-                minors.values.push(cur_det);
-                for j in 1..self.rows {
-                    cur_det = self.get_minor(i, j).determinant();
-                    minors.values.push(cur_det);
-                }
-            }
-            (minors, determinant)
-        };
+    pub fn hadamard_product_with(&mut self, m0: &Matrix<T>) {
+        self.zip_assign(m0, |a, b| a * b);
     }
 
-    pub fn invert(&self) -> Matrix {
-        assert_eq!(self.rows, self.columns);
-        let (mut minors, determinant) = self.matrix_of_minors();
-        minors.hadamard_product_with(Matrix::checker_board(self.columns, self.rows));
-        return minors.transpose() * (1f64 / determinant);
+    pub fn hadamard_product(m0: &Matrix<T>, m1: &Matrix<T>) -> Matrix<T> {
+        return m0.zip(m1, |a, b| a * b);
     }
 
-    pub fn hadamard_product_with(&mut self, m0: Matrix) {
-        assert_eq!(self.rows, m0.rows);
-        assert_eq!(self.columns, m0.columns);
-        for i in 0..m0.values.len() {
-            self.values[i] *= m0.values[i];
+    pub fn checker_board(columns: usize, rows: usize) -> Matrix<T> {
+        let mut checker_board = Matrix::zeros(columns, rows);
+        for j in 0..rows {
+            for i in 0..columns {
+                checker_board[(i, j)] = if (i + j) % 2 == 0 { T::one() } else { -T::one() };
+            }
         }
+        return checker_board;
     }
 
-
-    pub fn hadamard_product(m0: Matrix, m1: Matrix) {
-        assert_eq!(m0.rows, m1.rows);
-        assert_eq!(m0.columns, m1.columns);
-        let mut out = Matrix {
-            values: Vec::with_capacity(m0.rows * m0.columns),
-            rows: m0.rows,
-            columns: m0.columns,
-        };
-        for i in 0..m0.values.len() {
-            out.values.push(m0.values[i] * m1.values[i]);
+    pub fn map(&self, f: impl Fn(T) -> T) -> Matrix<T> {
+        Matrix {
+            values: self.values.iter().map(|val| f(*val)).collect(),
+            rows: self.rows,
+            columns: self.columns,
         }
     }
 
-    pub fn zeros(columns: usize, rows: usize) -> Matrix {
+    fn zip(&self, other: &Matrix<T>, f: impl Fn(T, T) -> T) -> Matrix<T> {
+        assert_eq!(self.rows, other.rows);
+        assert_eq!(self.columns, other.columns);
         Matrix {
-            values: vec!(0f64; rows * columns),
-            rows,
-            columns,
+            values: self.values.iter().zip(&other.values).map(|(a, b)| f(*a, *b)).collect(),
+            rows: self.rows,
+            columns: self.columns,
         }
     }
 
-    pub fn fill(columns: usize, rows: usize, value: f64) -> Matrix {
-        Matrix {
-            values: vec!(value; rows * columns),
-            rows,
-            columns,
+    fn zip_assign(&mut self, other: &Matrix<T>, f: impl Fn(T, T) -> T) {
+        assert_eq!(self.rows, other.rows);
+        assert_eq!(self.columns, other.columns);
+        for (a, b) in self.values.iter_mut().zip(&other.values) {
+            *a = f(*a, *b);
         }
     }
 
-    pub fn checker_board(columns: usize, rows: usize) -> Matrix {
-        let mut checker_board = Matrix {
-            values: Vec::with_capacity(rows * columns),
-            rows,
-            columns,
+    /// Splits the rows of the product between threads once it is large enough to pay for them.
+    fn multiply(&self, rhs: &Matrix<T>) -> Matrix<T> {
+        let threads = if self.rows * self.columns * rhs.columns < PARALLEL_MULTIPLICATIONS {
+            1
+        } else {
+            thread::available_parallelism().map_or(1, |threads| threads.get())
         };
-        let mut start_sign = 1f64;
-        for _i in 0..columns {
-            let mut sign = start_sign;
-            for _j in 0..rows {
-                checker_board.values.push(sign);
-                sign *= -1f64;
+        return self.multiply_on(rhs, threads);
+    }
+
+    fn multiply_on(&self, rhs: &Matrix<T>, threads: usize) -> Matrix<T> {
+        assert_eq!(self.columns, rhs.rows);
+        let mut matrix = Matrix::zeros(rhs.columns, self.rows);
+        if matrix.values.is_empty() {
+            return matrix;
+        }
+        let threads = threads.min(self.rows);
+        if threads <= 1 {
+            multiply_rows(self, rhs, 0, &mut matrix.values);
+            return matrix;
+        }
+        let rows_per_thread = self.rows.div_ceil(threads);
+        thread::scope(|scope| {
+            for (i, out) in matrix.values.chunks_mut(rows_per_thread * rhs.columns).enumerate() {
+                scope.spawn(move || multiply_rows(self, rhs, i * rows_per_thread, out));
+            }
+        });
+        return matrix;
+    }
+}
+
+/// Writes the rows of `lhs * rhs` starting at `first_row` into `out`.
+///
+/// Every row of the product is built from whole rows of `rhs`, so both operands are read in
+/// storage order instead of walking down the columns of `rhs`. The loops over `k` and the output
+/// columns are tiled so a block of `rhs` stays in cache while it is reused by each row of `lhs`.
+/// Each element still sums its terms in order of `k`, the result does not depend on the tiling.
+fn multiply_rows<T: Scalar>(lhs: &Matrix<T>, rhs: &Matrix<T>, first_row: usize, out: &mut [T]) {
+    let width = rhs.columns;
+    let rows = out.len() / width;
+    for k_start in (0..lhs.columns).step_by(MULTIPLY_BLOCK) {
+        let k_end = (k_start + MULTIPLY_BLOCK).min(lhs.columns);
+        for column_start in (0..width).step_by(MULTIPLY_BLOCK) {
+            let column_end = (column_start + MULTIPLY_BLOCK).min(width);
+            for j in 0..rows {
+                let lhs_row = &lhs.values[(first_row + j) * lhs.columns..(first_row + j + 1) * lhs.columns];
+                let out = &mut out[j * width + column_start..j * width + column_end];
+                for (k, val) in lhs_row.iter().enumerate().take(k_end).skip(k_start) {
+                    let rhs_row = &rhs.values[k * width + column_start..k * width + column_end];
+                    for (out, rhs) in out.iter_mut().zip(rhs_row) {
+                        *out = *out + *val * *rhs;
+                    }
+                }
             }
-            start_sign *= -1f64;
         }
-        return checker_board;
     }
 }
 
-impl std::ops::Index<(usize, usize)> for Matrix {
-    type Output = f64;
+impl<T: Scalar> std::ops::Index<(usize, usize)> for Matrix<T> {
+    type Output = T;
 
     fn index(&self, index: (usize, usize)) -> &Self::Output {
         return &self.values[index.0 + index.1 * self.columns];
     }
 }
 
-impl std::ops::IndexMut<(usize, usize)> for Matrix {
+impl<T: Scalar> std::ops::IndexMut<(usize, usize)> for Matrix<T> {
     fn index_mut(&mut self, index: (usize, usize)) -> &mut Self::Output {
         return &mut self.values[index.0 + index.1 * self.columns];
     }
 }
 
-impl std::ops::Mul<Matrix> for Matrix {
-    type Output = Matrix;
+/// Implements a binary operator for every mix of owned and borrowed matrices through `f(&Matrix, &Matrix)`.
+macro_rules! matrix_operator {
+    ($trait:ident, $method:ident, $f:expr) => {
+        impl<T: Scalar> std::ops::$trait<&Matrix<T>> for &Matrix<T> {
+            type Output = Matrix<T>;
 
-    fn mul(self, rhs: Matrix) -> Self::Output {
-        assert_eq!(self.columns, rhs.rows);
-        let mut matrix = Matrix {
-            values: Vec::with_capacity(self.rows * rhs.columns),
-            columns: self.rows,
-            rows: rhs.columns,
-        };
-        for i in 0..rhs.columns {
-            for j in 0..self.rows {
-                let mut val = 0f64;
-                for k in 0..self.columns {
-                    val += self[(k, j)] * rhs[(i, k)];
-                }
-                matrix.values.push(val);
+            fn $method(self, rhs: &Matrix<T>) -> Self::Output {
+                let f: fn(&Matrix<T>, &Matrix<T>) -> Matrix<T> = $f;
+                return f(self, rhs);
             }
         }
-        return matrix;
-    }
-}
 
-impl std::ops::Mul<&Matrix> for Matrix {
-    type Output = Matrix;
+        impl<T: Scalar> std::ops::$trait<Matrix<T>> for &Matrix<T> {
+            type Output = Matrix<T>;
 
-    fn mul(self, rhs: &Matrix) -> Self::Output {
-        assert_eq!(self.columns, rhs.rows);
-        let mut matrix = Matrix {
-            values: Vec::with_capacity(self.rows * rhs.columns),
-            columns: self.rows,
-            rows: rhs.columns,
-        };
-        for i in 0..rhs.columns {
-            for j in 0..self.rows {
-                let mut val = 0f64;
-                for k in 0..self.columns {
-                    val += self[(k, j)] * rhs[(i, k)];
-                }
-                matrix.values.push(val);
+            fn $method(self, rhs: Matrix<T>) -> Self::Output {
+                return std::ops::$trait::$method(self, &rhs);
             }
         }
-        return matrix;
-    }
-}
 
-impl std::ops::Mul<Matrix> for &Matrix {
-    type Output = Matrix;
+        impl<T: Scalar> std::ops::$trait<&Matrix<T>> for Matrix<T> {
+            type Output = Matrix<T>;
 
-    fn mul(self, rhs: Matrix) -> Self::Output {
-        assert_eq!(self.columns, rhs.rows);
-        let mut matrix = Matrix {
-            values: Vec::with_capacity(self.rows * rhs.columns),
-            columns: self.rows,
-            rows: rhs.columns,
-        };
-        for i in 0..rhs.columns {
-            for j in 0..self.rows {
-                let mut val = 0f64;
-                for k in 0..self.columns {
-                    val += self[(k, j)] * rhs[(i, k)];
-                }
-                matrix.values.push(val);
+            fn $method(self, rhs: &Matrix<T>) -> Self::Output {
+                return std::ops::$trait::$method(&self, rhs);
             }
         }
-        return matrix;
-    }
-}
 
-impl std::ops::MulAssign<f64> for Matrix {
-    fn mul_assign(&mut self, rhs: f64) {
-        for i in 0..self.columns {
-            for j in 0..self.rows {
-                self[(i, j)] *= rhs;
+        impl<T: Scalar> std::ops::$trait<Matrix<T>> for Matrix<T> {
+            type Output = Matrix<T>;
+
+            fn $method(self, rhs: Matrix<T>) -> Self::Output {
+                return std::ops::$trait::$method(&self, &rhs);
             }
         }
-    }
+    };
 }
 
-impl std::ops::Mul<f64> for Matrix {
-    type Output = Matrix;
+matrix_operator!(Add, add, |a, b| a.zip(b, |a, b| a + b));
+matrix_operator!(Sub, sub, |a, b| a.zip(b, |a, b| a - b));
+matrix_operator!(Mul, mul, |a, b| a.multiply(b));
 
-    fn mul(self, rhs: f64) -> Self::Output {
-        let mut matrix = Matrix {
-            values: Vec::with_capacity(self.columns * self.rows),
-            rows: self.rows,
-            columns: self.columns,
-        };
-        for i in 0..self.columns {
-            for j in 0..self.rows {
-                matrix.values.push(self[(i, j)] * rhs);
+/// Implements an element-wise operator with a scalar on owned and borrowed matrices, and its assigning form.
+macro_rules! scalar_operator {
+    ($trait:ident, $method:ident, $assign_trait:ident, $assign_method:ident, $op:tt) => {
+        impl<T: Scalar> std::ops::$trait<T> for &Matrix<T> {
+            type Output = Matrix<T>;
+
+            fn $method(self, rhs: T) -> Self::Output {
+                return self.map(|val| val $op rhs);
             }
         }
-        return matrix;
-    }
-}
 
-impl std::ops::AddAssign<f64> for Matrix {
-    fn add_assign(&mut self, rhs: f64) {
-        for i in 0..self.columns {
-            for j in 0..self.rows {
-                self[(i, j)] += rhs;
+        impl<T: Scalar> std::ops::$trait<T> for Matrix<T> {
+            type Output = Matrix<T>;
+
+            fn $method(mut self, rhs: T) -> Self::Output {
+                std::ops::$assign_trait::$assign_method(&mut self, rhs);
+                return self;
             }
         }
+
+        impl<T: Scalar> std::ops::$assign_trait<T> for Matrix<T> {
+            fn $assign_method(&mut self, rhs: T) {
+                for val in &mut self.values {
+                    *val = *val $op rhs;
+                }
+            }
+        }
+    };
+}
+
+scalar_operator!(Add, add, AddAssign, add_assign, +);
+scalar_operator!(Sub, sub, SubAssign, sub_assign, -);
+scalar_operator!(Mul, mul, MulAssign, mul_assign, *);
+scalar_operator!(Div, div, DivAssign, div_assign, /);
+
+impl std::ops::Mul<Matrix> for f64 {
+    type Output = Matrix;
+
+    fn mul(self, rhs: Matrix) -> Self::Output {
+        return rhs * self;
     }
 }
 
-impl std::ops::Add<f64> for Matrix {
+impl std::ops::Mul<&Matrix> for f64 {
     type Output = Matrix;
-    fn add(self, rhs: f64) -> Self::Output {
-        let mut matrix = Matrix {
-            values: Vec::with_capacity(self.columns * self.rows),
-            rows: self.rows,
-            columns: self.columns,
-        };
-        for i in 0..self.rows {
-            for j in 0..self.columns {
-                matrix[(i, j)] = self[(i, j)] + rhs
-            }
-        }
-        return matrix;
+
+    fn mul(self, rhs: &Matrix) -> Self::Output {
+        return rhs * self;
     }
 }
 
-impl std::ops::SubAssign<f64> for Matrix {
-    fn sub_assign(&mut self, rhs: f64) {
-        for i in 0..self.columns {
-            for j in 0..self.rows {
-                self[(i, j)] -= rhs;
-            }
-        }
+impl<T: Scalar> std::ops::AddAssign<&Matrix<T>> for Matrix<T> {
+    fn add_assign(&mut self, rhs: &Matrix<T>) {
+        self.zip_assign(rhs, |a, b| a + b);
     }
 }
 
-impl std::ops::Sub<f64> for Matrix {
-    type Output = Matrix;
-    fn sub(self, rhs: f64) -> Self::Output {
-        let mut matrix = Matrix {
-            values: Vec::with_capacity(self.columns * self.rows),
-            rows: self.rows,
-            columns: self.columns,
-        };
-        for i in 0..self.rows {
-            for j in 0..self.columns {
-                matrix[(i, j)] = self[(i, j)] - rhs
-            }
+impl<T: Scalar> std::ops::AddAssign<Matrix<T>> for Matrix<T> {
+    fn add_assign(&mut self, rhs: Matrix<T>) {
+        self.zip_assign(&rhs, |a, b| a + b);
+    }
+}
+
+impl<T: Scalar> std::ops::SubAssign<&Matrix<T>> for Matrix<T> {
+    fn sub_assign(&mut self, rhs: &Matrix<T>) {
+        self.zip_assign(rhs, |a, b| a - b);
+    }
+}
+
+impl<T: Scalar> std::ops::SubAssign<Matrix<T>> for Matrix<T> {
+    fn sub_assign(&mut self, rhs: Matrix<T>) {
+        self.zip_assign(&rhs, |a, b| a - b);
+    }
+}
+
+impl<T: Scalar> std::ops::Neg for Matrix<T> {
+    type Output = Matrix<T>;
+
+    fn neg(mut self) -> Self::Output {
+        for val in &mut self.values {
+            *val = -*val;
         }
-        return matrix;
+        return self;
     }
 }
 
-impl std::ops::Neg for Matrix {
-    type Output = Matrix;
+impl<T: Scalar> std::ops::Neg for &Matrix<T> {
+    type Output = Matrix<T>;
 
     fn neg(self) -> Self::Output {
-        let mut out = Matrix {
-            values: Vec::with_capacity(self.values.len()),
-            rows: self.rows,
-            columns: self.columns,
+        return self.map(|val| -val);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::eq_solver::complex::Complex;
+    use crate::eq_solver::dual::Dual;
+    use crate::eq_solver::matrix::Matrix;
+    use crate::eq_solver::rational::Rational;
+
+    fn a() -> Matrix {
+        Matrix::from_rows(&[[1.0, 2.0, 3.0], [4.0, 5.0, 6.0]])
+    }
+
+    #[test]
+    fn indexing_is_column_then_row() {
+        let a = a();
+        assert_eq!((a.columns, a.rows), (3, 2));
+        assert_eq!(a[(2, 0)], 3.0);
+        assert_eq!(a[(0, 1)], 4.0);
+        assert_eq!(a.row(1), Matrix::from_rows(&[[4.0, 5.0, 6.0]]));
+        assert_eq!(a.column(1), Matrix::from_rows(&[[2.0], [5.0]]));
+        assert_eq!(a.transpose(), Matrix::from_rows(&[[1.0, 4.0], [2.0, 5.0], [3.0, 6.0]]));
+        assert_eq!(a.sub_matrix([1, 0], [3, 2]), Matrix::from_rows(&[[2.0, 3.0], [5.0, 6.0]]));
+        assert_eq!(a.get_minor(1, 0), Matrix::from_rows(&[[4.0, 6.0]]));
+    }
+
+    #[test]
+    fn matrix_product() {
+        let b = Matrix::from_rows(&[[1.0, 0.0], [0.0, 1.0], [2.0, -1.0]]);
+        let expected = Matrix::from_rows(&[[7.0, -1.0], [16.0, -1.0]]);
+        assert_eq!(&a() * &b, expected);
+        assert_eq!(a() * &b, expected);
+        assert_eq!(&a() * b.clone(), expected);
+        assert_eq!(a() * b, expected);
+        assert_eq!(&a() * &Matrix::identity(3), a());
+    }
+
+    #[test]
+    fn blocked_threaded_product_matches_naive() {
+        let (columns, inner, rows) = (150, 130, 140);
+        let a = Matrix {
+            values: (0..inner * rows).map(|i| ((i * 7) % 13) as f64 - 6.0).collect(),
+            rows,
+            columns: inner,
         };
-        for i in self.values {
-            out.values.push(i);
+        let b = Matrix {
+            values: (0..columns * inner).map(|i| ((i * 5) % 11) as f64 * 0.5).collect(),
+            rows: inner,
+            columns,
+        };
+        let mut expected = Matrix::zeros(columns, rows);
+        for i in 0..columns {
+            for j in 0..rows {
+                for k in 0..inner {
+                    expected[(i, j)] += a[(k, j)] * b[(i, k)];
+                }
+            }
+        }
+        assert_eq!(&a * &b, expected);
+        for threads in [1, 3, 8] {
+            assert_eq!(a.multiply_on(&b, threads), expected);
         }
-        return out;
+        assert_eq!(&Matrix::<f64>::zeros(0, 3) * &Matrix::zeros(4, 0), Matrix::zeros(4, 3));
+    }
+
+    #[test]
+    fn element_wise_arithmetic() {
+        let b = Matrix::fill(3, 2, 2.0);
+        assert_eq!(&a() + &b, Matrix::from_rows(&[[3.0, 4.0, 5.0], [6.0, 7.0, 8.0]]));
+        assert_eq!(a() - b.clone(), Matrix::from_rows(&[[-1.0, 0.0, 1.0], [2.0, 3.0, 4.0]]));
+        assert_eq!(-a(), Matrix::from_rows(&[[-1.0, -2.0, -3.0], [-4.0, -5.0, -6.0]]));
+        assert_eq!(-&a(), a() * -1.0);
+        assert_eq!(Matrix::hadamard_product(&a(), &b), 2.0 * a());
+        let mut c = a();
+        c.hadamard_product_with(&b);
+        c -= &a();
+        c += a();
+        assert_eq!(c, &a() * 2.0);
+    }
+
+    #[test]
+    fn scalar_arithmetic() {
+        assert_eq!(a() + 1.0, Matrix::from_rows(&[[2.0, 3.0, 4.0], [5.0, 6.0, 7.0]]));
+        assert_eq!(&a() - 1.0, Matrix::from_rows(&[[0.0, 1.0, 2.0], [3.0, 4.0, 5.0]]));
+        assert_eq!(a() / 2.0, a() * 0.5);
+        let mut c = a();
+        c += 1.0;
+        c *= 2.0;
+        c -= 2.0;
+        c /= 2.0;
+        assert_eq!(c, a());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn checker_board_signs() {
+        assert_eq!(Matrix::checker_board(3, 2), Matrix::from_rows(&[[1.0, -1.0, 1.0], [-1.0, 1.0, -1.0]]));
+    }
+
+    #[test]
+    fn single_precision() {
+        let a = Matrix::from_rows(&[[1f32, 2f32], [3f32, 4f32]]);
+        assert_eq!(&a * &Matrix::identity(2), a);
+        assert_eq!(a.transpose() * 2f32, Matrix::from_rows(&[[2f32, 6f32], [4f32, 8f32]]));
+    }
+
+    #[test]
+    fn complex_product() {
+        let i = Complex::new(0.0, 1.0);
+        let one = Complex::from(1.0);
+        let rotation = Matrix::from_rows(&[[one, i], [i, one]]);
+        let expected = Matrix::from_rows(&[[Complex::from(0.0), Complex::new(0.0, 2.0)], [Complex::new(0.0, 2.0), Complex::from(0.0)]]);
+        assert_eq!(&rotation * &rotation, expected);
+    }
+
+    #[test]
+    fn dual_numbers_differentiate_through_products() {
+        // d/dx of [[x, 1], [2, x]] * [x, 3] = [x^2 + 3, 2x + 3x] at x = 2
+        let x = Dual::variable(2.0);
+        let three = Dual::constant(3.0);
+        let a = Matrix::from_rows(&[[x, Dual::constant(1.0)], [Dual::constant(2.0), x]]);
+        let v = Matrix::from_rows(&[[x], [three]]);
+        let product = a * v;
+        assert_eq!(product[(0, 0)], Dual::new(7.0, 4.0));
+        assert_eq!(product[(0, 1)], Dual::new(10.0, 5.0));
+    }
+
+    #[test]
+    fn exact_rational_arithmetic() {
+        let hilbert = Matrix::from_rows(&[
+            [Rational::new(1, 1), Rational::new(1, 2), Rational::new(1, 3)],
+            [Rational::new(1, 2), Rational::new(1, 3), Rational::new(1, 4)],
+            [Rational::new(1, 3), Rational::new(1, 4), Rational::new(1, 5)],
+        ]);
+        let inverse = Matrix::from_rows(&[
+            [Rational::integer(9), Rational::integer(-36), Rational::integer(30)],
+            [Rational::integer(-36), Rational::integer(192), Rational::integer(-180)],
+            [Rational::integer(30), Rational::integer(-180), Rational::integer(180)],
+        ]);
+        assert_eq!(&hilbert * &inverse, Matrix::identity(3));
+        assert_eq!((hilbert - Matrix::identity(3))[(1, 1)], Rational::new(-2, 3));
+    }
+}
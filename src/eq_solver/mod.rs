@@ -1,3 +1,18 @@
 pub mod approx;
+pub mod builtin;
+pub mod cholesky;
+pub mod compile;
+pub mod complex;
+pub mod dual;
+pub mod eigen;
+pub mod expr;
 pub mod function;
+pub mod io;
+pub mod iterative;
+pub mod lu;
 pub mod matrix;
+pub mod qr;
+pub mod rational;
+pub mod scalar;
+pub mod sparse;
+pub mod svd;
@@ -0,0 +1,212 @@
+use crate::eq_solver::matrix::{Matrix, MatrixError};
+
+/// Householder QR decomposition `A = Q * R` of a matrix with at least as many rows as columns.
+///
+/// The Householder vectors are kept below the diagonal of `qr`, the strictly upper part holds `R`
+/// and its diagonal is kept separately.
+pub struct QrDecomposition {
+    pub qr: Matrix,
+    pub r_diagonal: Vec<f64>,
+}
+
+impl QrDecomposition {
+    pub fn new(matrix: &Matrix) -> QrDecomposition {
+        assert!(matrix.rows >= matrix.columns);
+        let mut qr = Matrix {
+            values: matrix.values.clone(),
+            rows: matrix.rows,
+            columns: matrix.columns,
+        };
+        let mut r_diagonal = Vec::with_capacity(matrix.columns);
+        for k in 0..qr.columns {
+            let mut norm = 0f64;
+            for i in k..qr.rows {
+                norm = norm.hypot(qr[(k, i)]);
+            }
+            if norm != 0f64 {
+                if qr[(k, k)] < 0f64 {
+                    norm = -norm;
+                }
+                for i in k..qr.rows {
+                    qr[(k, i)] /= norm;
+                }
+                qr[(k, k)] += 1f64;
+                for j in k + 1..qr.columns {
+                    let mut s = 0f64;
+                    for i in k..qr.rows {
+                        s += qr[(k, i)] * qr[(j, i)];
+                    }
+                    s = -s / qr[(k, k)];
+                    for i in k..qr.rows {
+                        let val = qr[(k, i)];
+                        qr[(j, i)] += s * val;
+                    }
+                }
+            }
+            r_diagonal.push(-norm);
+        }
+        QrDecomposition {
+            qr,
+            r_diagonal,
+        }
+    }
+
+    /// Whether `R` has no (numerically) zero diagonal entry.
+    pub fn is_full_rank(&self) -> bool {
+        let scale = self.r_diagonal.iter().fold(0f64, |max, val| max.max(val.abs()));
+        let tolerance = scale * self.qr.rows.max(self.qr.columns) as f64 * f64::EPSILON;
+        return self.r_diagonal.iter().all(|val| val.abs() > tolerance);
+    }
+
+    pub fn r(&self) -> Matrix {
+        let size = self.qr.columns;
+        let mut r = Matrix::zeros(size, size);
+        for i in 0..size {
+            r[(i, i)] = self.r_diagonal[i];
+            for j in i + 1..size {
+                r[(j, i)] = self.qr[(j, i)];
+            }
+        }
+        return r;
+    }
+
+    /// The first `columns` columns of `Q`.
+    pub fn q(&self) -> Matrix {
+        let mut q = Matrix::zeros(self.qr.columns, self.qr.rows);
+        for k in (0..self.qr.columns).rev() {
+            q[(k, k)] = 1f64;
+            for j in k..self.qr.columns {
+                if self.qr[(k, k)] != 0f64 {
+                    let mut s = 0f64;
+                    for i in k..self.qr.rows {
+                        s += self.qr[(k, i)] * q[(j, i)];
+                    }
+                    s = -s / self.qr[(k, k)];
+                    for i in k..self.qr.rows {
+                        q[(j, i)] += s * self.qr[(k, i)];
+                    }
+                }
+            }
+        }
+        return q;
+    }
+
+    /// Finds `x` minimizing `|A * x - b|` for every column of `b`.
+    pub fn solve(&self, b: &Matrix) -> Result<Matrix, MatrixError> {
+        assert_eq!(b.rows, self.qr.rows);
+        if !self.is_full_rank() {
+            return Err(MatrixError::Singular);
+        }
+        let mut x = Matrix {
+            values: b.values.clone(),
+            rows: b.rows,
+            columns: b.columns,
+        };
+        for k in 0..self.qr.columns {
+            for j in 0..x.columns {
+                let mut s = 0f64;
+                for i in k..self.qr.rows {
+                    s += self.qr[(k, i)] * x[(j, i)];
+                }
+                s = -s / self.qr[(k, k)];
+                for i in k..self.qr.rows {
+                    x[(j, i)] += s * self.qr[(k, i)];
+                }
+            }
+        }
+        for k in (0..self.qr.columns).rev() {
+            for j in 0..x.columns {
+                x[(j, k)] /= self.r_diagonal[k];
+                let val = x[(j, k)];
+                for i in 0..k {
+                    x[(j, i)] -= val * self.qr[(k, i)];
+                }
+            }
+        }
+        x.values.truncate(x.columns * self.qr.columns);
+        x.rows = self.qr.columns;
+        return Ok(x);
+    }
+}
+
+impl Matrix {
+    pub fn qr(&self) -> QrDecomposition {
+        return QrDecomposition::new(self);
+    }
+
+    /// Least squares solution of `self * x = b`, without forming the normal equations.
+    pub fn least_squares(&self, b: &Matrix) -> Result<Matrix, MatrixError> {
+        return self.qr().solve(b);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::eq_solver::matrix::Matrix;
+
+    fn vandermonde(points: &[f64], degree: usize) -> Matrix {
+        let mut matrix = Matrix::zeros(degree + 1, points.len());
+        for i in 0..=degree {
+            for (j, point) in points.iter().enumerate() {
+                matrix[(i, j)] = point.powi(i as i32);
+            }
+        }
+        return matrix;
+    }
+
+    #[test]
+    fn least_squares_matches_exact_solution() {
+        let a = Matrix {
+            values: vec![2.0, 1.0, 1.0, 4.0, -6.0, 0.0, -2.0, 7.0, 2.0],
+            rows: 3,
+            columns: 3,
+        };
+        let b = Matrix {
+            values: vec![5.0, -2.0, 9.0],
+            rows: 3,
+            columns: 1,
+        };
+        let x = a.least_squares(&b).unwrap();
+        for (val, expected) in x.values.iter().zip([1.0, 1.0, 2.0]) {
+            assert!((val - expected).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn least_squares_fits_line() {
+        let points = [0.0, 1.0, 2.0, 3.0];
+        let b = Matrix {
+            values: vec![1.0, 2.0, 2.0, 4.0],
+            rows: 4,
+            columns: 1,
+        };
+        let x = vandermonde(&points, 1).least_squares(&b).unwrap();
+        assert!((x[(0, 0)] - 0.9).abs() < 1e-12);
+        assert!((x[(0, 1)] - 0.9).abs() < 1e-12);
+    }
+
+    #[test]
+    fn q_and_r_reproduce_matrix() {
+        let points: Vec<f64> = (0..8).map(|i| i as f64 * 0.25).collect();
+        let a = vandermonde(&points, 3);
+        let qr = a.qr();
+        let (q, r) = (qr.q(), qr.r());
+        for i in 0..a.columns {
+            for j in 0..a.rows {
+                let val: f64 = (0..a.columns).map(|k| q[(k, j)] * r[(i, k)]).sum();
+                assert!((val - a[(i, j)]).abs() < 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn rank_deficient_is_singular() {
+        let a = Matrix {
+            values: vec![1.0, 2.0, 2.0, 4.0, 3.0, 6.0],
+            rows: 3,
+            columns: 2,
+        };
+        let b = Matrix::fill(1, 3, 1.0);
+        assert!(a.least_squares(&b).is_err());
+    }
+}
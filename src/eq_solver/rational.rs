@@ -0,0 +1,184 @@
+use crate::eq_solver::scalar::Scalar;
+use std::convert::TryFrom;
+use std::fmt;
+
+/// An exact fraction kept in lowest terms with a positive denominator.
+///
+/// Arithmetic is exact in `i128` and panics when a result in lowest terms does not fit in `i64`;
+/// the `checked_` methods return `None` instead.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Rational {
+    numerator: i64,
+    denominator: i64,
+}
+
+impl Rational {
+    pub fn new(numerator: i64, denominator: i64) -> Rational {
+        assert_ne!(denominator, 0);
+        return Rational::reduce(numerator as i128, denominator as i128).expect("rational overflow");
+    }
+
+    /// The fraction in lowest terms, or `None` if it does not fit in `i64`.
+    fn reduce(numerator: i128, denominator: i128) -> Option<Rational> {
+        let divisor = gcd(numerator, denominator) * denominator.signum();
+        return Option::Some(Rational {
+            numerator: i64::try_from(numerator / divisor).ok()?,
+            denominator: i64::try_from(denominator / divisor).ok()?,
+        });
+    }
+
+    pub fn checked_add(self, rhs: Rational) -> Option<Rational> {
+        let numerator = self.numerator as i128 * rhs.denominator as i128 + rhs.numerator as i128 * self.denominator as i128;
+        return Rational::reduce(numerator, self.denominator as i128 * rhs.denominator as i128);
+    }
+
+    pub fn checked_sub(self, rhs: Rational) -> Option<Rational> {
+        let numerator = self.numerator as i128 * rhs.denominator as i128 - rhs.numerator as i128 * self.denominator as i128;
+        return Rational::reduce(numerator, self.denominator as i128 * rhs.denominator as i128);
+    }
+
+    pub fn checked_mul(self, rhs: Rational) -> Option<Rational> {
+        let numerator = self.numerator as i128 * rhs.numerator as i128;
+        return Rational::reduce(numerator, self.denominator as i128 * rhs.denominator as i128);
+    }
+
+    /// Also `None` when `rhs` is zero.
+    pub fn checked_div(self, rhs: Rational) -> Option<Rational> {
+        if rhs.numerator == 0 {
+            return Option::None;
+        }
+        let numerator = self.numerator as i128 * rhs.denominator as i128;
+        return Rational::reduce(numerator, self.denominator as i128 * rhs.numerator as i128);
+    }
+
+    pub fn checked_neg(self) -> Option<Rational> {
+        return Option::Some(Rational {
+            numerator: self.numerator.checked_neg()?,
+            denominator: self.denominator,
+        });
+    }
+
+    pub fn integer(val: i64) -> Rational {
+        return Rational::new(val, 1);
+    }
+
+    pub fn numerator(&self) -> i64 {
+        return self.numerator;
+    }
+
+    pub fn denominator(&self) -> i64 {
+        return self.denominator;
+    }
+
+    pub fn to_f64(self) -> f64 {
+        return self.numerator as f64 / self.denominator as f64;
+    }
+}
+
+fn gcd(a: i128, b: i128) -> i128 {
+    let (mut a, mut b) = (a.abs(), b.abs());
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    return a.max(1);
+}
+
+impl Scalar for Rational {
+    fn zero() -> Self {
+        return Rational::integer(0);
+    }
+
+    fn one() -> Self {
+        return Rational::integer(1);
+    }
+
+    fn magnitude(self) -> f64 {
+        return self.to_f64().abs();
+    }
+
+    fn epsilon() -> f64 {
+        return 0f64;
+    }
+}
+
+impl fmt::Display for Rational {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return if self.denominator == 1 {
+            write!(f, "{}", self.numerator)
+        } else {
+            write!(f, "{}/{}", self.numerator, self.denominator)
+        };
+    }
+}
+
+impl std::ops::Add for Rational {
+    type Output = Rational;
+
+    fn add(self, rhs: Rational) -> Self::Output {
+        return self.checked_add(rhs).expect("rational overflow");
+    }
+}
+
+impl std::ops::Sub for Rational {
+    type Output = Rational;
+
+    fn sub(self, rhs: Rational) -> Self::Output {
+        return self.checked_sub(rhs).expect("rational overflow");
+    }
+}
+
+impl std::ops::Mul for Rational {
+    type Output = Rational;
+
+    fn mul(self, rhs: Rational) -> Self::Output {
+        return self.checked_mul(rhs).expect("rational overflow");
+    }
+}
+
+impl std::ops::Div for Rational {
+    type Output = Rational;
+
+    fn div(self, rhs: Rational) -> Self::Output {
+        assert_ne!(rhs.numerator, 0, "rational division by zero");
+        return self.checked_div(rhs).expect("rational overflow");
+    }
+}
+
+impl std::ops::Neg for Rational {
+    type Output = Rational;
+
+    fn neg(self) -> Self::Output {
+        return self.checked_neg().expect("rational overflow");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::eq_solver::rational::Rational;
+
+    #[test]
+    fn keeps_lowest_terms() {
+        assert_eq!(Rational::new(6, -4), Rational::new(-3, 2));
+        assert_eq!(Rational::new(1, 6) + Rational::new(1, 3), Rational::new(1, 2));
+        assert_eq!(Rational::new(1, 6) - Rational::new(1, 3), Rational::new(-1, 6));
+        assert_eq!(Rational::new(2, 3) * Rational::new(9, 4), Rational::new(3, 2));
+        assert_eq!(Rational::new(2, 3) / Rational::new(-4, 9), Rational::new(-3, 2));
+        assert_eq!(Rational::new(0, -5), Rational::integer(0));
+        assert_eq!(Rational::new(0, -5).denominator(), 1);
+    }
+
+    #[test]
+    fn reports_overflow() {
+        let big = Rational::integer(i64::MAX);
+        assert_eq!(big.checked_add(Rational::integer(1)), Option::None);
+        assert_eq!(big.checked_mul(Rational::integer(2)), Option::None);
+        assert_eq!(Rational::integer(i64::MIN).checked_neg(), Option::None);
+        assert_eq!(Rational::new(1, i64::MAX).checked_mul(Rational::new(1, 2)), Option::None);
+        assert_eq!(Rational::integer(1).checked_div(Rational::integer(0)), Option::None);
+        // Intermediate products past i64 are fine as long as the result fits.
+        let third = Rational::new(1, 3);
+        assert_eq!(big.checked_mul(third).and_then(|value| value.checked_mul(Rational::integer(3))), Option::Some(big));
+        assert_eq!(Rational::new(i64::MAX, 2).checked_mul(Rational::new(2, i64::MAX)), Option::Some(Rational::integer(1)));
+        assert!(std::panic::catch_unwind(|| big + big).is_err());
+    }
+}
@@ -0,0 +1,61 @@
+use std::fmt::Debug;
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+/// Element type of a `Matrix`, a field with the usual arithmetic operators.
+///
+/// Scalars are shared between threads by large matrix products.
+pub trait Scalar:
+    Copy
+    + Send
+    + Sync
+    + PartialEq
+    + Debug
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Div<Output = Self>
+    + Neg<Output = Self>
+{
+    fn zero() -> Self;
+    fn one() -> Self;
+    /// The absolute value as an `f64`, used to choose pivots.
+    fn magnitude(self) -> f64;
+    /// Relative rounding error of the arithmetic, zero for exact scalars.
+    fn epsilon() -> f64;
+}
+
+impl Scalar for f64 {
+    fn zero() -> Self {
+        return 0f64;
+    }
+
+    fn one() -> Self {
+        return 1f64;
+    }
+
+    fn magnitude(self) -> f64 {
+        return self.abs();
+    }
+
+    fn epsilon() -> f64 {
+        return f64::EPSILON;
+    }
+}
+
+impl Scalar for f32 {
+    fn zero() -> Self {
+        return 0f32;
+    }
+
+    fn one() -> Self {
+        return 1f32;
+    }
+
+    fn magnitude(self) -> f64 {
+        return self.abs() as f64;
+    }
+
+    fn epsilon() -> f64 {
+        return f32::EPSILON as f64;
+    }
+}
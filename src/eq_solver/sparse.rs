@@ -0,0 +1,227 @@
+use crate::eq_solver::matrix::Matrix;
+
+/// A sparse matrix as a list of `(column, row, value)` entries, convenient for assembly.
+///
+/// Entries at the same position are summed when converting.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CooMatrix {
+    pub columns: usize,
+    pub rows: usize,
+    pub entries: Vec<(usize, usize, f64)>,
+}
+
+/// A sparse matrix in compressed sparse row form.
+///
+/// The entries of row `j` are `values[row_offsets[j]..row_offsets[j + 1]]`, at the columns in the
+/// same range of `column_indices`, which are kept sorted.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CsrMatrix {
+    pub columns: usize,
+    pub rows: usize,
+    pub row_offsets: Vec<usize>,
+    pub column_indices: Vec<usize>,
+    pub values: Vec<f64>,
+}
+
+impl CooMatrix {
+    pub fn new(columns: usize, rows: usize) -> CooMatrix {
+        CooMatrix {
+            columns,
+            rows,
+            entries: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, column: usize, row: usize, value: f64) {
+        assert!(column < self.columns && row < self.rows);
+        self.entries.push((column, row, value));
+    }
+
+    pub fn to_csr(&self) -> CsrMatrix {
+        let mut entries = self.entries.clone();
+        entries.sort_by_key(|(column, row, _)| (*row, *column));
+        let mut csr = CsrMatrix {
+            columns: self.columns,
+            rows: self.rows,
+            row_offsets: vec![0; self.rows + 1],
+            column_indices: Vec::with_capacity(entries.len()),
+            values: Vec::with_capacity(entries.len()),
+        };
+        let mut last = Option::None;
+        for (column, row, value) in entries {
+            if last == Option::Some((column, row)) {
+                *csr.values.last_mut().unwrap() += value;
+            } else {
+                csr.column_indices.push(column);
+                csr.values.push(value);
+                csr.row_offsets[row + 1] += 1;
+                last = Option::Some((column, row));
+            }
+        }
+        for row in 0..self.rows {
+            csr.row_offsets[row + 1] += csr.row_offsets[row];
+        }
+        return csr;
+    }
+
+    pub fn to_dense(&self) -> Matrix {
+        let mut dense = Matrix::zeros(self.columns, self.rows);
+        for (column, row, value) in &self.entries {
+            dense[(*column, *row)] += *value;
+        }
+        return dense;
+    }
+}
+
+impl CsrMatrix {
+    /// Keeps every non-zero element of `dense`.
+    pub fn from_dense(dense: &Matrix) -> CsrMatrix {
+        let mut csr = CsrMatrix {
+            columns: dense.columns,
+            rows: dense.rows,
+            row_offsets: Vec::with_capacity(dense.rows + 1),
+            column_indices: Vec::new(),
+            values: Vec::new(),
+        };
+        csr.row_offsets.push(0);
+        for row in 0..dense.rows {
+            for column in 0..dense.columns {
+                let value = dense[(column, row)];
+                if value != 0f64 {
+                    csr.column_indices.push(column);
+                    csr.values.push(value);
+                }
+            }
+            csr.row_offsets.push(csr.values.len());
+        }
+        return csr;
+    }
+
+    pub fn to_dense(&self) -> Matrix {
+        let mut dense = Matrix::zeros(self.columns, self.rows);
+        for row in 0..self.rows {
+            for index in self.row_range(row) {
+                dense[(self.column_indices[index], row)] = self.values[index];
+            }
+        }
+        return dense;
+    }
+
+    pub fn to_coo(&self) -> CooMatrix {
+        let mut coo = CooMatrix::new(self.columns, self.rows);
+        for row in 0..self.rows {
+            for index in self.row_range(row) {
+                coo.entries.push((self.column_indices[index], row, self.values[index]));
+            }
+        }
+        return coo;
+    }
+
+    pub fn identity(size: usize) -> CsrMatrix {
+        CsrMatrix {
+            columns: size,
+            rows: size,
+            row_offsets: (0..=size).collect(),
+            column_indices: (0..size).collect(),
+            values: vec![1f64; size],
+        }
+    }
+
+    /// Number of stored entries.
+    pub fn non_zeros(&self) -> usize {
+        return self.values.len();
+    }
+
+    /// Positions in `column_indices` and `values` of the entries of `row`.
+    pub fn row_range(&self, row: usize) -> std::ops::Range<usize> {
+        return self.row_offsets[row]..self.row_offsets[row + 1];
+    }
+
+    pub fn get(&self, column: usize, row: usize) -> f64 {
+        let range = self.row_range(row);
+        return match self.column_indices[range.clone()].binary_search(&column) {
+            Ok(index) => self.values[range.start + index],
+            Err(_) => 0f64,
+        };
+    }
+
+    pub fn diagonal(&self) -> Vec<f64> {
+        return (0..self.rows.min(self.columns)).map(|i| self.get(i, i)).collect();
+    }
+
+    pub fn transpose(&self) -> CsrMatrix {
+        let mut coo = CooMatrix::new(self.rows, self.columns);
+        for row in 0..self.rows {
+            for index in self.row_range(row) {
+                coo.entries.push((row, self.column_indices[index], self.values[index]));
+            }
+        }
+        return coo.to_csr();
+    }
+
+    /// Writes `self * x` into `out`.
+    pub fn mul_vec_into(&self, x: &[f64], out: &mut [f64]) {
+        assert_eq!(x.len(), self.columns);
+        assert_eq!(out.len(), self.rows);
+        for (row, out) in out.iter_mut().enumerate() {
+            let range = self.row_range(row);
+            *out = self.column_indices[range.clone()]
+                .iter()
+                .zip(&self.values[range])
+                .map(|(column, value)| value * x[*column])
+                .sum();
+        }
+    }
+
+    pub fn mul_vec(&self, x: &[f64]) -> Vec<f64> {
+        let mut out = vec![0f64; self.rows];
+        self.mul_vec_into(x, &mut out);
+        return out;
+    }
+}
+
+impl From<&CooMatrix> for CsrMatrix {
+    fn from(coo: &CooMatrix) -> Self {
+        return coo.to_csr();
+    }
+}
+
+impl From<&Matrix> for CsrMatrix {
+    fn from(dense: &Matrix) -> Self {
+        return CsrMatrix::from_dense(dense);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::eq_solver::matrix::Matrix;
+    use crate::eq_solver::sparse::{CooMatrix, CsrMatrix};
+
+    #[test]
+    fn coo_sums_duplicates() {
+        let mut coo = CooMatrix::new(3, 2);
+        coo.push(2, 1, 1.0);
+        coo.push(0, 0, 2.0);
+        coo.push(2, 1, 3.0);
+        let csr = coo.to_csr();
+        assert_eq!(csr.non_zeros(), 2);
+        assert_eq!(csr.row_offsets, vec![0, 1, 2]);
+        assert_eq!(csr.get(2, 1), 4.0);
+        assert_eq!(csr.get(1, 1), 0.0);
+        assert_eq!(csr.to_dense(), coo.to_dense());
+    }
+
+    #[test]
+    fn dense_round_trip_and_products() {
+        let dense = Matrix::from_rows(&[[4.0, 0.0, 1.0], [0.0, 0.0, 0.0], [-2.0, 3.0, 0.0]]);
+        let csr = CsrMatrix::from_dense(&dense);
+        assert_eq!(csr.non_zeros(), 4);
+        assert_eq!(csr.to_dense(), dense);
+        assert_eq!(csr.to_coo().to_csr(), csr);
+        assert_eq!(csr.transpose().to_dense(), dense.transpose());
+        assert_eq!(csr.diagonal(), vec![4.0, 0.0, 0.0]);
+        let x = [1.0, 2.0, 3.0];
+        let expected = &dense * &Matrix::from_rows(&[[1.0], [2.0], [3.0]]);
+        assert_eq!(csr.mul_vec(&x), expected.values);
+    }
+}
@@ -0,0 +1,230 @@
+use crate::eq_solver::matrix::Matrix;
+
+const JACOBI_SWEEPS: usize = 100;
+
+/// Thin singular value decomposition `A = U * S * Vᵀ`, singular values in descending order.
+///
+/// Column `k` of `u` and `v` are the left and right singular vectors of `singular_values[k]`.
+pub struct SingularValueDecomposition {
+    pub u: Matrix,
+    pub singular_values: Vec<f64>,
+    pub v: Matrix,
+}
+
+impl SingularValueDecomposition {
+    /// One-sided Jacobi rotations, orthogonalizing the columns of the matrix.
+    pub fn new(matrix: &Matrix) -> SingularValueDecomposition {
+        if matrix.rows < matrix.columns {
+            let transposed = SingularValueDecomposition::new(&matrix.transpose());
+            return SingularValueDecomposition {
+                u: transposed.v,
+                singular_values: transposed.singular_values,
+                v: transposed.u,
+            };
+        }
+        let (rows, columns) = (matrix.rows, matrix.columns);
+        let mut u: Vec<Vec<f64>> = (0..columns)
+            .map(|column| (0..rows).map(|row| matrix[(column, row)]).collect())
+            .collect();
+        let mut v: Vec<Vec<f64>> = (0..columns)
+            .map(|column| (0..columns).map(|row| if row == column { 1f64 } else { 0f64 }).collect())
+            .collect();
+        for _sweep in 0..JACOBI_SWEEPS {
+            let mut rotated = false;
+            for i in 0..columns {
+                for j in i + 1..columns {
+                    let alpha = dot(&u[i], &u[i]);
+                    let beta = dot(&u[j], &u[j]);
+                    let gamma = dot(&u[i], &u[j]);
+                    if gamma == 0f64 || gamma.abs() <= f64::EPSILON * (alpha * beta).sqrt() {
+                        continue;
+                    }
+                    rotated = true;
+                    let zeta = (beta - alpha) / (2f64 * gamma);
+                    let t = zeta.signum() / (zeta.abs() + zeta.hypot(1f64));
+                    let c = 1f64 / t.hypot(1f64);
+                    let s = t * c;
+                    rotate(&mut u, i, j, c, s);
+                    rotate(&mut v, i, j, c, s);
+                }
+            }
+            if !rotated {
+                break;
+            }
+        }
+        let norms: Vec<f64> = u.iter().map(|column| dot(column, column).sqrt()).collect();
+        let mut order: Vec<usize> = (0..columns).collect();
+        order.sort_by(|i, j| norms[*j].total_cmp(&norms[*i]));
+        let mut svd = SingularValueDecomposition {
+            u: Matrix::zeros(columns, rows),
+            singular_values: Vec::with_capacity(columns),
+            v: Matrix::zeros(columns, columns),
+        };
+        for (k, i) in order.into_iter().enumerate() {
+            let norm = norms[i];
+            for (row, val) in u[i].iter().enumerate() {
+                svd.u[(k, row)] = if norm == 0f64 { 0f64 } else { val / norm };
+            }
+            for (row, val) in v[i].iter().enumerate() {
+                svd.v[(k, row)] = *val;
+            }
+            svd.singular_values.push(norm);
+        }
+        return svd;
+    }
+
+    /// Singular values at or below this are treated as zero by default.
+    pub fn default_tolerance(&self) -> f64 {
+        let largest = self.singular_values.first().copied().unwrap_or(0f64);
+        return self.u.rows.max(self.v.rows) as f64 * f64::EPSILON * largest;
+    }
+
+    pub fn rank(&self, tolerance: f64) -> usize {
+        return self.singular_values.iter().filter(|val| **val > tolerance).count();
+    }
+
+    /// Ratio of the largest to the smallest singular value, infinite for rank deficient matrices.
+    pub fn condition_number(&self) -> f64 {
+        let largest = self.singular_values.first().copied().unwrap_or(0f64);
+        let smallest = self.singular_values.last().copied().unwrap_or(0f64);
+        return if smallest <= self.default_tolerance() { f64::INFINITY } else { largest / smallest };
+    }
+
+    /// Moore-Penrose pseudo-inverse, ignoring singular values at or below `tolerance`.
+    pub fn pseudo_inverse(&self, tolerance: f64) -> Matrix {
+        let mut inverse = Matrix::zeros(self.u.rows, self.v.rows);
+        for (k, val) in self.singular_values.iter().enumerate() {
+            if *val <= tolerance {
+                continue;
+            }
+            for i in 0..self.u.rows {
+                for j in 0..self.v.rows {
+                    inverse[(i, j)] += self.v[(k, j)] * self.u[(k, i)] / val;
+                }
+            }
+        }
+        return inverse;
+    }
+}
+
+impl Matrix {
+    pub fn svd(&self) -> SingularValueDecomposition {
+        return SingularValueDecomposition::new(self);
+    }
+
+    pub fn singular_values(&self) -> Vec<f64> {
+        return self.svd().singular_values;
+    }
+
+    /// Number of singular values above `tolerance`.
+    pub fn rank(&self, tolerance: f64) -> usize {
+        return self.svd().rank(tolerance);
+    }
+
+    pub fn pinv(&self) -> Matrix {
+        let svd = self.svd();
+        return svd.pseudo_inverse(svd.default_tolerance());
+    }
+
+    pub fn condition_number(&self) -> f64 {
+        return self.svd().condition_number();
+    }
+
+    /// Orthonormal basis of the vectors `x` with `self * x = 0`, one per column.
+    pub fn null_space(&self) -> Matrix {
+        // Pad with zero rows so the decomposition has a right singular vector for every column.
+        let mut padded = Matrix::zeros(self.columns, self.rows.max(self.columns));
+        padded.values[..self.values.len()].copy_from_slice(&self.values);
+        let svd = padded.svd();
+        let rank = svd.rank(svd.default_tolerance());
+        let mut basis = Matrix::zeros(self.columns - rank, self.columns);
+        for k in rank..self.columns {
+            for j in 0..self.columns {
+                basis[(k - rank, j)] = svd.v[(k, j)];
+            }
+        }
+        return basis;
+    }
+}
+
+fn dot(a: &[f64], b: &[f64]) -> f64 {
+    return a.iter().zip(b).map(|(a, b)| a * b).sum();
+}
+
+fn rotate(columns: &mut [Vec<f64>], i: usize, j: usize, c: f64, s: f64) {
+    for row in 0..columns[i].len() {
+        let (a, b) = (columns[i][row], columns[j][row]);
+        columns[i][row] = c * a - s * b;
+        columns[j][row] = s * a + c * b;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::eq_solver::matrix::Matrix;
+
+    #[test]
+    fn reconstructs_matrix() {
+        for matrix in [
+            Matrix::from_rows(&[[3.0, 2.0, 2.0], [2.0, 3.0, -2.0]]),
+            Matrix::from_rows(&[[1.0, 2.0], [3.0, 4.0], [5.0, 6.0]]),
+        ] {
+            let svd = matrix.svd();
+            for i in 0..matrix.columns {
+                for j in 0..matrix.rows {
+                    let val: f64 = (0..svd.singular_values.len())
+                        .map(|k| svd.u[(k, j)] * svd.singular_values[k] * svd.v[(k, i)])
+                        .sum();
+                    assert!((val - matrix[(i, j)]).abs() < 1e-12);
+                }
+            }
+        }
+        let values = Matrix::from_rows(&[[3.0, 2.0, 2.0], [2.0, 3.0, -2.0]]).singular_values();
+        assert!((values[0] - 5.0).abs() < 1e-12);
+        assert!((values[1] - 3.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn rank_and_null_space() {
+        let matrix = Matrix::from_rows(&[[1.0, 2.0, 3.0], [2.0, 4.0, 6.0]]);
+        let svd = matrix.svd();
+        assert_eq!(svd.rank(svd.default_tolerance()), 1);
+        assert_eq!(matrix.condition_number(), f64::INFINITY);
+        let null_space = matrix.null_space();
+        assert_eq!(null_space.columns, 2);
+        for k in 0..null_space.columns {
+            for j in 0..matrix.rows {
+                let val: f64 = (0..matrix.columns).map(|i| matrix[(i, j)] * null_space[(k, i)]).sum();
+                assert!(val.abs() < 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn pinv_of_full_rank_matrices() {
+        let square = Matrix::from_rows(&[[4.0, 7.0], [2.0, 6.0]]);
+        let inverse = square.pinv();
+        let expected = square.inverse().unwrap();
+        for (val, expected) in inverse.values.iter().zip(&expected.values) {
+            assert!((val - expected).abs() < 1e-12);
+        }
+        let tall = Matrix::from_rows(&[[1.0, 0.0], [1.0, 1.0], [1.0, 2.0], [1.0, 3.0]]);
+        let b = Matrix {
+            values: vec![1.0, 2.0, 2.0, 4.0],
+            rows: 4,
+            columns: 1,
+        };
+        let pinv = tall.pinv();
+        let least_squares = tall.least_squares(&b).unwrap();
+        for k in 0..2 {
+            let val: f64 = (0..4).map(|j| pinv[(j, k)] * b[(0, j)]).sum();
+            assert!((val - least_squares[(0, k)]).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn condition_number_of_diagonal() {
+        let matrix = Matrix::from_rows(&[[100.0, 0.0], [0.0, 0.5]]);
+        assert!((matrix.condition_number() - 200.0).abs() < 1e-10);
+    }
+}
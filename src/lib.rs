@@ -0,0 +1,143 @@
+#![allow(dead_code, clippy::needless_return)]
+//! Numerical equation solver and the doodlang language built on it.
+//!
+//! The functions here cover single formulas. `Interpreter` runs whole programs and returns
+//! what every statement produced.
+
+pub mod doodlang;
+pub mod eq_solver;
+
+pub use crate::doodlang::interpreter::{Diagnostic, EigenSolution, Interpreter, Outcome, RunResult, ScopeResult, Solution};
+pub use crate::doodlang::parser::ParseError;
+pub use crate::eq_solver::builtin::FunctionRegistry;
+
+use crate::doodlang::parser::FunctionManager;
+use crate::eq_solver::approx::SecantSolver;
+use crate::eq_solver::compile::Program;
+use crate::eq_solver::expr::{ExprArena, ExprId};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+/// A parsed formula. Variables are numbered in the order they were given to `parse`.
+///
+/// An equation `a = b` is kept as `a - b`, so its solutions are the roots of the expression.
+#[derive(Clone)]
+pub struct Expression {
+    pub arena: ExprArena,
+    pub root: ExprId,
+    pub variables: Vec<String>,
+}
+
+impl Expression {
+    pub fn evaluate(&self, values: &[f64]) -> f64 {
+        return self.arena.output(self.root, values);
+    }
+
+    /// The derivative with respect to the variable at `variable`.
+    pub fn derivative(&self, variable: usize) -> Expression {
+        let mut arena = self.arena.clone();
+        let root = arena.derivative(self.root, variable);
+        return Expression {
+            arena,
+            root,
+            variables: self.variables.clone(),
+        };
+    }
+}
+
+/// Parses `formula`, which may use the given variables and every builtin function.
+/// Whitespace is ignored.
+pub fn parse(formula: &str, variables: &[&str]) -> Result<Expression, ParseError> {
+    return parse_with(&FunctionRegistry::new(), formula, variables);
+}
+
+/// Like `parse`, calling the functions of `functions` instead of only the builtins.
+pub fn parse_with(functions: &FunctionRegistry, formula: &str, variables: &[&str]) -> Result<Expression, ParseError> {
+    let formula: String = formula.chars().filter(|cur| !cur.is_whitespace()).collect();
+    let scope: Vec<(&str, f64)> = variables.iter().map(|name| (*name, 0f64)).collect();
+    let mut function_manager = FunctionManager::with_functions(&scope, functions.clone());
+    let mut arena = ExprArena::new();
+    let root = function_manager.parse(&mut arena, &formula)?;
+    return Ok(Expression {
+        arena,
+        root,
+        variables: variables.iter().map(|name| name.to_string()).collect(),
+    });
+}
+
+/// Compiles a parsed formula for fast repeated evaluation.
+pub fn compile(expression: &Expression) -> Program {
+    return expression.arena.compile(expression.root);
+}
+
+/// Evaluates `formula` once with the given variable values.
+pub fn evaluate(formula: &str, variables: &[(&str, f64)]) -> Result<f64, ParseError> {
+    return evaluate_with(&FunctionRegistry::new(), formula, variables);
+}
+
+/// Like `evaluate`, calling the functions of `functions` instead of only the builtins.
+pub fn evaluate_with(functions: &FunctionRegistry, formula: &str, variables: &[(&str, f64)]) -> Result<f64, ParseError> {
+    let names: Vec<&str> = variables.iter().map(|(name, _)| *name).collect();
+    let values: Vec<f64> = variables.iter().map(|(_, value)| *value).collect();
+    return Ok(parse_with(functions, formula, &names)?.evaluate(&values));
+}
+
+/// Solves `equation` for `unknown` with the secant method, keeping the other variables fixed.
+/// The value given for `unknown` is ignored; initial guesses come from a fixed seed.
+pub fn solve(equation: &str, variables: &[(&str, f64)], unknown: &str) -> Result<Solution, ParseError> {
+    return solve_with(&FunctionRegistry::new(), equation, variables, unknown);
+}
+
+/// Like `solve`, calling the functions of `functions` instead of only the builtins.
+pub fn solve_with(functions: &FunctionRegistry, equation: &str, variables: &[(&str, f64)], unknown: &str) -> Result<Solution, ParseError> {
+    let names: Vec<&str> = variables.iter().map(|(name, _)| *name).collect();
+    if !names.contains(&unknown) {
+        return Err(ParseError::new(format!("Unknown variable: {}", unknown)));
+    }
+    let expression = parse_with(functions, equation, &names)?;
+    let function = expression.arena.to_solvable(expression.root);
+    let function_manager = FunctionManager::new(variables);
+    let solver = SecantSolver::new([-5f64, 5f64], 0.1f64, 100, 10000, 0f64);
+    let value = solver.solve_for_param(&mut StdRng::seed_from_u64(0), function.as_ref(), &function_manager, unknown);
+    let mut values = function_manager.variables.clone();
+    let slot = function_manager.ids[unknown];
+    values[slot] = value;
+    let slope = expression.derivative(slot).evaluate(&values);
+    return Ok(Solution::new(unknown, equation, value, function.output(&values).abs(), slope));
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{compile, evaluate, evaluate_with, parse, parse_with, solve, solve_with, FunctionRegistry};
+    use crate::eq_solver::function::Function;
+
+    #[test]
+    fn evaluates_and_compiles() {
+        assert_eq!(evaluate("2 * x + sqrt(y)", &[("x", 3.0), ("y", 16.0)]), Ok(10.0));
+        let expression = parse("x ^ 3", &["x"]).unwrap();
+        assert_eq!(compile(&expression).output(&[2.0]), 8.0);
+        assert_eq!(expression.derivative(0).evaluate(&[2.0]), 12.0);
+        assert_eq!(parse("x + w", &["x"]).err().unwrap().message, "Unknown variable: w");
+        assert!(parse("sin(x", &["x"]).is_err());
+        assert!(parse("x +", &["x"]).is_err());
+    }
+
+    #[test]
+    fn solves_equations() {
+        let solution = solve("x * y = 6", &[("x", 0.0), ("y", 4.0)], "x").unwrap();
+        assert!((solution.value - 1.5).abs() < 1e-9);
+        assert!(solution.residual < 1e-9);
+        assert!(solve("x = 1", &[("x", 0.0)], "z").is_err());
+    }
+
+    #[test]
+    fn calls_registered_functions() {
+        let mut functions = FunctionRegistry::new();
+        functions.register("triple", 1, |x| 3.0 * x[0]);
+        assert_eq!(evaluate_with(&functions, "triple(x) + 1", &[("x", 2.0)]), Ok(7.0));
+        assert_eq!(parse_with(&functions, "triple(x)", &["x"]).unwrap().derivative(0).evaluate(&[2.0]).round(), 3.0);
+        let solution = solve_with(&functions, "triple(x) = 6", &[("x", 0.0)], "x").unwrap();
+        assert!((solution.value - 2.0).abs() < 1e-9);
+        assert!(parse("triple(x)", &["x"]).is_err());
+    }
+}
@@ -1,44 +1,445 @@
-use std::fmt::Display;
-use crate::eq_solver::matrix::Matrix;
-use doodlang::parser::{Parser, FunctionManager, Node, generate_tree};
-use crate::eq_solver::approx::{PolyApprox, SecantSolver};
-use crate::doodlang::interpreter::DoodlangInterpreter;
+#![allow(clippy::needless_return)]
+
+use doodlang::doodlang::format::format;
+use doodlang::doodlang::interpreter::TEST_SEED;
+use doodlang::doodlang::repl::Repl;
+use doodlang::doodlang::report::{diff, report, Format, CSV_HEADER};
+use doodlang::Interpreter;
+use std::env;
 use std::fs;
-use text_io::read;
+use std::io::{self, BufRead, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::thread;
+use std::time::{Duration, SystemTime};
 
-mod eq_solver;
-mod doodlang;
+/// Some equation was not solved to within `SOLVED_RESIDUAL` or some assertion failed.
+const EXIT_FAILED: i32 = 1;
+/// Some statement could not be parsed or executed.
+const EXIT_PROGRAM_ERROR: i32 = 2;
+const EXIT_USAGE: i32 = 64;
+const EXIT_NO_INPUT: i32 = 66;
+/// How often `--watch` checks the program files.
+const WATCH_INTERVAL: Duration = Duration::from_millis(500);
 
-fn main() {
-    let file: String = read!();
-    let code = fs::read_to_string(file)
-        .expect("Something went wrong reading the file");
-    let mut interpreter = DoodlangInterpreter::new(&code[0..code.len()]);
-    interpreter.run();
+const USAGE: &str = "usage: doodlang [options] [<program>... | - | -e <code>]
+       doodlang repl [--seed <n>] [--set <name>=<value>]
+       doodlang fmt [--check] [<program>... | -]
+       doodlang test [--seed <n>] <directory or program>...
+
+Runs doodlang programs. `-` reads a program from standard input.
+`repl` starts an interactive session that keeps its variables between inputs.
+`fmt` rewrites programs in canonical style, or with `--check` lists the ones
+that are not and exits with 1. Without programs it formats standard input.
+`test` runs every program in the given directories, seed 5 unless given, and
+exits with 1 if any assertion fails or any statement is in error.
+
+options:
+    -e <code>              run <code>, wrapped in a scope if it has none
+    --seed <n>             start every program from seed <n>
+    --set <name>=<value>   replace every assignment to <name>
+    --format text|json|csv output format, text by default
+    --quiet                print nothing but errors
+    --watch                run again whenever a program file changes and
+                           print how the results differ from the last run
+    -h, --help             print this message
+
+exit codes:
+    0   every equation was solved
+    1   some equation was not solved or some assertion failed
+    2   some statement could not be parsed or executed
+    64  bad command line
+    66  a program could not be read";
+
+enum Input {
+    File(String),
+    Stdin,
+    Inline(String),
+}
+
+impl Input {
+    fn name(&self) -> &str {
+        return match self {
+            Input::File(path) => path,
+            Input::Stdin => "<stdin>",
+            Input::Inline(_) => "<inline>",
+        };
+    }
+
+    fn read(&self) -> io::Result<String> {
+        return match self {
+            Input::File(path) => fs::read_to_string(path),
+            Input::Stdin => {
+                let mut code = String::new();
+                io::stdin().read_to_string(&mut code)?;
+                Ok(code)
+            }
+            Input::Inline(code) if code.contains('{') => Ok(code.clone()),
+            Input::Inline(code) => Ok(format!("{{{}}}", code)),
+        };
+    }
 }
 
-pub fn print_tree(node: &Node<[usize; 2]>, formula: &String) {
-    if node.children.is_empty() {
-        print!("({})", &formula[node.value[0]..node.value[1]]);
+struct Options {
+    inputs: Vec<Input>,
+    seed: Option<u64>,
+    overrides: Vec<(String, f64)>,
+    format: Format,
+    quiet: bool,
+    watch: bool,
+}
+
+fn usage_error(message: &str) -> ! {
+    eprintln!("doodlang: {}\n\n{}", message, USAGE);
+    process::exit(EXIT_USAGE);
+}
+
+/// Accepts both `--name value` and `--name=value`.
+fn value(name: &str, inline: Option<&str>, args: &mut impl Iterator<Item = String>) -> String {
+    return match inline {
+        Some(value) => value.to_string(),
+        None => match args.next() {
+            Some(value) => value,
+            None => usage_error(&format!("{} needs a value", name)),
+        },
+    };
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Options {
+    let mut options = Options {
+        inputs: Vec::new(),
+        seed: None,
+        overrides: Vec::new(),
+        format: Format::Text,
+        quiet: false,
+        watch: false,
+    };
+    while let Some(arg) = args.next() {
+        let (name, inline) = match arg.find('=') {
+            Some(index) if arg.starts_with("--") => (&arg[..index], Some(&arg[index + 1..])),
+            _ => (arg.as_str(), None),
+        };
+        match name {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            "--quiet" => options.quiet = true,
+            "--watch" => options.watch = true,
+            "-e" => options.inputs.push(Input::Inline(value(name, inline, &mut args))),
+            "--seed" => {
+                let seed = value(name, inline, &mut args);
+                options.seed = match seed.parse::<u64>() {
+                    Ok(seed) => Some(seed),
+                    Err(_) => usage_error(&format!("Seed has to be a non-negative integer: {}", seed)),
+                };
+            }
+            "--set" => {
+                let assignment = value(name, inline, &mut args);
+                let parsed = assignment
+                    .split_once('=')
+                    .and_then(|(variable, value)| Some((variable.trim().to_string(), value.trim().parse::<f64>().ok()?)));
+                match parsed {
+                    Some(assignment) => options.overrides.push(assignment),
+                    None => usage_error(&format!("--set expects <name>=<number>, found {}", assignment)),
+                }
+            }
+            "--format" => {
+                let format = value(name, inline, &mut args);
+                options.format = match Format::parse(&format) {
+                    Some(format) => format,
+                    None => usage_error(&format!("Unknown format: {}", format)),
+                };
+            }
+            "-" => options.inputs.push(Input::Stdin),
+            _ if arg.starts_with('-') => usage_error(&format!("Unknown option: {}", arg)),
+            _ => options.inputs.push(Input::File(arg)),
+        }
+    }
+    return options;
+}
+
+fn interpreter(options: &Options) -> Interpreter {
+    let mut interpreter = Interpreter::new();
+    if let Some(seed) = options.seed {
+        interpreter.set_seed(seed);
+    }
+    for (name, value) in &options.overrides {
+        interpreter.set_variable(name, *value);
+    }
+    return interpreter;
+}
+
+fn repl(options: &Options) {
+    if !options.inputs.is_empty() {
+        usage_error("repl takes no programs");
+    }
+    let interactive = io::stdin().is_terminal();
+    let mut repl = Repl::new(interpreter(options));
+    if interactive {
+        println!("doodlang repl, :help lists the commands");
+    }
+    let mut lines = io::stdin().lock().lines();
+    while !repl.finished {
+        if interactive {
+            print!("{}", if repl.is_pending() { "... " } else { "> " });
+            io::stdout().flush().unwrap();
+        }
+        let line = match lines.next() {
+            Some(Ok(line)) => line,
+            _ => break,
+        };
+        for output in repl.input(&line) {
+            match output {
+                Ok(output) => println!("{}", output),
+                Err(message) => eprintln!("error: {}", message),
+            }
+        }
+    }
+}
+
+/// Runs one program and prints its diagnostics. Returns the report and the exit code it warrants.
+fn run_input(options: &Options, interpreter: &mut Interpreter, seed: u64, input: &Input) -> io::Result<(String, i32)> {
+    let code = input.read()?;
+    // Every program starts from the same seed so each one can be reproduced on its own.
+    interpreter.set_seed(seed);
+    let result = interpreter.run(&code);
+    for diagnostic in &result.diagnostics {
+        if options.inputs.len() > 1 {
+            eprint!("{}: ", input.name());
+        }
+        eprintln!("{}", diagnostic);
+    }
+    let exit_code = if !result.is_ok() {
+        EXIT_PROGRAM_ERROR
+    } else if !result.all_converged() || !result.all_passed() {
+        EXIT_FAILED
     } else {
-        for child in &node.children {
-            print!("(");
-            print_tree(child, formula);
-            print!(")");
+        0
+    };
+    return Ok((report(options.format, input.name(), &result), exit_code));
+}
+
+fn modified(input: &Input) -> Option<SystemTime> {
+    return match input {
+        Input::File(path) => fs::metadata(path).and_then(|metadata| metadata.modified()).ok(),
+        _ => None,
+    };
+}
+
+/// Re-runs every program whose file changed and prints how its results differ from the last run.
+/// `times` are the modification times from before the last run, so edits made during it are not missed.
+fn watch(options: &Options, interpreter: &mut Interpreter, seed: u64, mut times: Vec<Option<SystemTime>>, mut reports: Vec<String>) -> ! {
+    loop {
+        thread::sleep(WATCH_INTERVAL);
+        for (i, input) in options.inputs.iter().enumerate() {
+            let time = modified(input);
+            if time == times[i] {
+                continue;
+            }
+            times[i] = time;
+            match run_input(options, interpreter, seed, input) {
+                Ok((report, _)) => {
+                    if !options.quiet {
+                        println!("==> {} changed <==", input.name());
+                        if report == reports[i] {
+                            println!("results unchanged");
+                        } else {
+                            print!("{}", diff(&reports[i], &report));
+                        }
+                    }
+                    reports[i] = report;
+                }
+                Err(err) => eprintln!("doodlang: cannot read {}: {}", input.name(), err),
+            }
+        }
+    }
+}
+
+fn fmt(args: impl Iterator<Item = String>) -> ! {
+    let mut check = false;
+    let mut paths = Vec::new();
+    for arg in args {
+        match arg.as_str() {
+            "--check" => check = true,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            "-" => {}
+            _ if arg.starts_with('-') => usage_error(&format!("Unknown option: {}", arg)),
+            _ => paths.push(arg),
+        }
+    }
+    if paths.is_empty() {
+        let code = match Input::Stdin.read() {
+            Ok(code) => code,
+            Err(err) => {
+                eprintln!("doodlang: cannot read <stdin>: {}", err);
+                process::exit(EXIT_NO_INPUT);
+            }
+        };
+        let formatted = format(&code);
+        if check {
+            process::exit(if formatted == code { 0 } else { 1 });
+        }
+        print!("{}", formatted);
+        process::exit(0);
+    }
+    let mut unformatted = false;
+    for path in &paths {
+        let code = match fs::read_to_string(path) {
+            Ok(code) => code,
+            Err(err) => {
+                eprintln!("doodlang: cannot read {}: {}", path, err);
+                process::exit(EXIT_NO_INPUT);
+            }
+        };
+        let formatted = format(&code);
+        if formatted == code {
+            continue;
+        }
+        if check {
+            println!("{}", path);
+            unformatted = true;
+        } else if let Err(err) = fs::write(path, formatted) {
+            eprintln!("doodlang: cannot write {}: {}", path, err);
+            process::exit(EXIT_NO_INPUT);
+        }
+    }
+    process::exit(if unformatted { 1 } else { 0 });
+}
+
+/// Adds `path`, or every `.dood` file below it, to `programs`.
+fn find_programs(path: &Path, programs: &mut Vec<PathBuf>) -> io::Result<()> {
+    if !path.is_dir() {
+        programs.push(path.to_path_buf());
+        return Ok(());
+    }
+    let mut entries: Vec<PathBuf> = fs::read_dir(path)?.map(|entry| entry.map(|entry| entry.path())).collect::<io::Result<_>>()?;
+    entries.sort();
+    for entry in entries {
+        if entry.is_dir() {
+            find_programs(&entry, programs)?;
+        } else if entry.extension().is_some_and(|extension| extension == "dood") {
+            programs.push(entry);
         }
     }
+    return Ok(());
 }
 
-pub fn print_vec<T: Display>(vec: &Vec<T>) {
-    for val in vec {
-        print!("{} ", val);
+fn test(options: &Options) -> ! {
+    if options.inputs.is_empty() || options.inputs.iter().any(|input| !matches!(input, Input::File(_))) {
+        usage_error("test needs directories or program files");
     }
+    let mut programs = Vec::new();
+    for input in &options.inputs {
+        if let Err(err) = find_programs(Path::new(input.name()), &mut programs) {
+            eprintln!("doodlang: cannot read {}: {}", input.name(), err);
+            process::exit(EXIT_NO_INPUT);
+        }
+    }
+    let mut interpreter = interpreter(options);
+    let mut failures = Vec::new();
+    let mut assertions = 0;
+    let mut failed = 0;
+    let mut errors = 0;
+    for program in &programs {
+        let code = match fs::read_to_string(program) {
+            Ok(code) => code,
+            Err(err) => {
+                eprintln!("doodlang: cannot read {}: {}", program.display(), err);
+                process::exit(EXIT_NO_INPUT);
+            }
+        };
+        interpreter.set_seed(options.seed.unwrap_or(TEST_SEED));
+        let result = interpreter.run(&code);
+        let before = failures.len();
+        for assertion in result.assertions() {
+            assertions += 1;
+            if !assertion.passed {
+                failed += 1;
+                failures.push(format!("{}:{}: assertion {}", program.display(), assertion.line, assertion));
+            }
+        }
+        for diagnostic in &result.diagnostics {
+            errors += 1;
+            failures.push(format!("{}:{}: error: {}\n    {};", program.display(), diagnostic.line, diagnostic.message, diagnostic.statement));
+        }
+        let verdict = if failures.len() == before { "ok" } else { "FAILED" };
+        println!("{:<6} {} ({} assertions)", verdict, program.display(), result.assertions().count());
+    }
+    if !failures.is_empty() {
+        println!("\nfailures:");
+        for failure in &failures {
+            println!("{}", failure);
+        }
+    }
+    let verdict = if failures.is_empty() { "ok" } else { "FAILED" };
+    println!(
+        "\ntest result: {}. {} programs, {} assertions, {} failed, {} errors",
+        verdict,
+        programs.len(),
+        assertions,
+        failed,
+        errors
+    );
+    process::exit(if failures.is_empty() { 0 } else { EXIT_FAILED });
 }
 
-fn stuff(range: usize) -> [usize; 2] {
-    let mut vec: Vec<[usize; 2]> = Vec::new();
-    for i in 0..range {
-        vec.push([0usize, i]);
+fn main() {
+    let mut args = env::args().skip(1).peekable();
+    match args.peek().map(String::as_str) {
+        Some("test") => {
+            args.next();
+            test(&parse_args(args));
+        }
+        Some("repl") => {
+            args.next();
+            repl(&parse_args(args));
+            return;
+        }
+        Some("fmt") => {
+            args.next();
+            fmt(args);
+        }
+        _ => {}
+    }
+    let options = parse_args(args);
+    if options.inputs.is_empty() {
+        usage_error("no program given");
+    }
+    if options.watch && options.inputs.iter().any(|input| !matches!(input, Input::File(_))) {
+        usage_error("--watch only works with program files");
+    }
+    let mut interpreter = interpreter(&options);
+    let seed = options.seed.unwrap_or_else(|| interpreter.seed());
+    let mut exit_code = 0;
+    let mut reports = Vec::new();
+    let times: Vec<Option<SystemTime>> = options.inputs.iter().map(modified).collect();
+    if options.format == Format::Csv && !options.quiet {
+        println!("{}", CSV_HEADER);
+    }
+    for input in &options.inputs {
+        let (report, code) = match run_input(&options, &mut interpreter, seed, input) {
+            Ok(run) => run,
+            Err(err) => {
+                eprintln!("doodlang: cannot read {}: {}", input.name(), err);
+                process::exit(EXIT_NO_INPUT);
+            }
+        };
+        if !options.quiet {
+            if options.inputs.len() > 1 && options.format == Format::Text {
+                println!("==> {} <==", input.name());
+            }
+            print!("{}", report);
+        }
+        // Program errors take precedence over unsolved equations.
+        exit_code = exit_code.max(code);
+        reports.push(report);
+    }
+    if options.watch {
+        watch(&options, &mut interpreter, seed, times, reports);
     }
-    return vec[0];
+    process::exit(exit_code);
 }
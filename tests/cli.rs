@@ -0,0 +1,84 @@
+#![allow(clippy::needless_return)]
+
+//! Runs the `doodlang` binary to check its argument parsing, output and exit codes.
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// Runs `doodlang` with `args` and `stdin`, returning the exit code, standard output and error.
+fn run(args: &[&str], stdin: &str) -> (i32, String, String) {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_doodlang"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(stdin.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    return (output.status.code().unwrap(), stdout, stderr);
+}
+
+#[test]
+fn runs_inline_code() {
+    let (code, stdout, stderr) = run(&["--seed", "5", "-e", "x: 1; solve(x) <- x * 2 = 3;"], "");
+    assert_eq!(code, 0, "{}", stderr);
+    assert!(stdout.starts_with("seed: 5\nformula: x*2=3\nx: 1.5 with abs err: 0\n"), "{}", stdout);
+    let (code, stdout, _) = run(&["--seed=5", "--format=json", "-e", "x: 1;"], "");
+    assert_eq!(code, 0);
+    assert!(stdout.starts_with("{\"program\":\"<inline>\",\"seed\":5,"), "{}", stdout);
+}
+
+#[test]
+fn maps_results_to_exit_codes() {
+    let (code, _, _) = run(&["-e", "x: 1; solve(x) <- x^2 = 0 - 1;"], "");
+    assert_eq!(code, 1);
+    let (code, _, _) = run(&["-e", "x: 1; assert x == 2;"], "");
+    assert_eq!(code, 1);
+    let (code, _, stderr) = run(&["-e", "x: 1; solve(x) <- foo(x) = 1;"], "");
+    assert_eq!(code, 2);
+    assert!(stderr.contains("Unknown function: foo"), "{}", stderr);
+    let (code, _, stderr) = run(&["-e", "solve(x) <- x = 2"], "");
+    assert_eq!(code, 2);
+    assert!(stderr.contains("Missing `;`"), "{}", stderr);
+    // Program errors take precedence over unsolved equations.
+    let (code, _, _) = run(&["-e", "x: 1; solve(x) <- x^2 = 0 - 1;", "-e", "x: 1; y"], "");
+    assert_eq!(code, 2);
+}
+
+#[test]
+fn reads_standard_input() {
+    let (code, stdout, _) = run(&["--seed", "1", "-"], "{\n    x: 1;\n    solve(x) <- x = 4;\n}\n");
+    assert_eq!(code, 0);
+    assert!(stdout.contains("x: 4 with abs err: 0"), "{}", stdout);
+}
+
+#[test]
+fn overrides_assignments() {
+    let (code, stdout, _) = run(&["--seed", "1", "--set", "y=9", "--set=z = 2", "-e", "y: 1; z: 5; x: 1; solve(x) <- x * z = y;"], "");
+    assert_eq!(code, 0);
+    assert!(stdout.contains("x: 4.5 with abs err: 0"), "{}", stdout);
+}
+
+#[test]
+fn prints_only_errors_when_quiet() {
+    let (code, stdout, _) = run(&["--quiet", "-e", "x: 1; solve(x) <- x = 2;"], "");
+    assert_eq!((code, stdout.as_str()), (0, ""));
+    let (code, stdout, stderr) = run(&["--quiet", "-e", "x: 1; solve(x) <- foo(x) = 1;"], "");
+    assert_eq!((code, stdout.as_str()), (2, ""));
+    assert!(stderr.contains("Unknown function: foo"), "{}", stderr);
+}
+
+#[test]
+fn rejects_bad_command_lines() {
+    for args in [&["--seed", "-1", "-e", "x: 1;"][..], &["--set", "y", "-e", "x: 1;"], &["--format", "xml", "-e", "x: 1;"], &["--bogus"], &[], &["-e"]] {
+        let (code, _, stderr) = run(args, "");
+        assert_eq!(code, 64, "{:?}", args);
+        assert!(stderr.contains("usage: doodlang"), "{:?}: {}", args, stderr);
+    }
+    let (code, _, stderr) = run(&["does/not/exist.dood"], "");
+    assert_eq!(code, 66);
+    assert!(stderr.contains("cannot read does/not/exist.dood"), "{}", stderr);
+}
@@ -0,0 +1,110 @@
+#![allow(clippy::needless_return)]
+
+//! Runs every program under `programs/` with `TEST_SEED`, the seed `doodlang test` uses, and
+//! compares its JSON report with the expected output in `tests/golden`. Run with `DOODLANG_BLESS=1` to accept the current output:
+//!
+//!     DOODLANG_BLESS=1 cargo test --test programs
+
+use doodlang::doodlang::json::Json;
+use doodlang::doodlang::interpreter::TEST_SEED;
+use doodlang::doodlang::report::{report, Format};
+use doodlang::Interpreter;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Numbers may differ by this much, relative to their size above 1, so last digit differences
+/// between math libraries do not fail the comparison.
+const TOLERANCE: f64 = 1e-9;
+
+fn files(directory: &Path, extension: &str) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = match fs::read_dir(directory) {
+        Ok(entries) => entries.map(|entry| entry.unwrap().path()).filter(|path| path.extension().is_some_and(|found| found == extension)).collect(),
+        Err(_) => Vec::new(),
+    };
+    files.sort();
+    return files;
+}
+
+/// Returns where `actual` first differs from `expected`.
+fn compare(expected: &Json, actual: &Json, path: &str) -> Result<(), String> {
+    match (expected, actual) {
+        (Json::Number(expected), Json::Number(actual)) => {
+            if (expected - actual).abs() > TOLERANCE * expected.abs().max(1.0) {
+                return Err(format!("{}: expected {}, found {}", path, expected, actual));
+            }
+        }
+        (Json::Array(expected), Json::Array(actual)) => {
+            if expected.len() != actual.len() {
+                return Err(format!("{}: expected {} elements, found {}", path, expected.len(), actual.len()));
+            }
+            for (i, (expected, actual)) in expected.iter().zip(actual).enumerate() {
+                compare(expected, actual, &format!("{}[{}]", path, i))?;
+            }
+        }
+        (Json::Object(expected), Json::Object(actual)) => {
+            let keys: Vec<&String> = expected.iter().map(|(key, _)| key).collect();
+            let found: Vec<&String> = actual.iter().map(|(key, _)| key).collect();
+            if keys != found {
+                return Err(format!("{}: expected the members {:?}, found {:?}", path, keys, found));
+            }
+            for ((key, expected), (_, actual)) in expected.iter().zip(actual) {
+                compare(expected, actual, &format!("{}.{}", path, key))?;
+            }
+        }
+        (expected, actual) => {
+            if expected != actual {
+                return Err(format!("{}: expected {}, found {}", path, expected, actual));
+            }
+        }
+    }
+    return Ok(());
+}
+
+#[test]
+fn programs_match_golden_output() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let golden = root.join("tests").join("golden");
+    let bless = env::var_os("DOODLANG_BLESS").is_some();
+    let programs = files(&root.join("programs"), "dood");
+    assert!(!programs.is_empty(), "no programs found");
+    let mut failures = Vec::new();
+    for program in &programs {
+        let name = program.file_stem().unwrap().to_str().unwrap();
+        let mut interpreter = Interpreter::new();
+        interpreter.set_seed(TEST_SEED);
+        let result = interpreter.run(&fs::read_to_string(program).unwrap());
+        let actual = Json::parse(&report(Format::Json, &format!("programs/{}.dood", name), &result)).unwrap();
+        let expected = golden.join(format!("{}.json", name));
+        if bless {
+            fs::create_dir_all(&golden).unwrap();
+            fs::write(&expected, actual.pretty()).unwrap();
+            continue;
+        }
+        let expected = match fs::read_to_string(&expected) {
+            Ok(text) => Json::parse(&text).unwrap_or_else(|err| panic!("{} is not JSON: {}", expected.display(), err)),
+            Err(_) => {
+                failures.push(format!("{}: no expected output at {}", name, expected.display()));
+                continue;
+            }
+        };
+        if let Err(difference) = compare(&expected, &actual, name) {
+            failures.push(difference);
+        }
+    }
+    for stale in files(&golden, "json") {
+        let name = stale.file_stem().unwrap().to_str().unwrap();
+        if !programs.iter().any(|program| program.file_stem().unwrap() == name) {
+            if bless {
+                fs::remove_file(&stale).unwrap();
+            } else {
+                failures.push(format!("{}: expected output without a program", stale.display()));
+            }
+        }
+    }
+    assert!(
+        failures.is_empty(),
+        "\n{}\n\nrun `DOODLANG_BLESS=1 cargo test --test programs` to accept the new output",
+        failures.join("\n")
+    );
+}